@@ -0,0 +1,372 @@
+//! Python bindings for rim galaxies, imported as `rim`:
+//!
+//! ```python
+//! import rim
+//!
+//! galaxy = rim.Galaxy.generate(systems=100, seed=42)
+//! for system in galaxy:
+//!     for planet in system.planets:
+//!         print(planet.designation, planet.planet_class, len(planet.moons))
+//! galaxy.save("campaign.rim")
+//! ```
+//!
+//! Systems, planets and moons handed to Python are copies, so they don't change
+//! along with the galaxy they came from.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use pyo3::exceptions::{PyKeyError, PyOSError, PyValueError};
+use pyo3::prelude::*;
+
+use rim::{Galaxy, Moon, Planet, Shape, StarSystem, SystemId};
+
+/// A galaxy of star systems joined by lanes.
+#[pyclass(name = "Galaxy", module = "rim")]
+struct PyGalaxy {
+    galaxy: Galaxy,
+}
+
+#[pymethods]
+impl PyGalaxy {
+    /// Generates a new galaxy, with the same defaults rim starts a new galaxy with.
+    /// The same seed always gives the same galaxy, and no seed gives a random one.
+    /// `shape` is "sphere", "disc" or "ring". Settings rim couldn't start with either, like
+    /// fewer than 2 systems or an amplitude of 0, raise ValueError.
+    #[staticmethod]
+    #[pyo3(signature = (systems = 200, connections_per_system = 5, amplitude = 250.0, radius = 50.0, seed = None, shape = "sphere"))]
+    fn generate(systems: usize, connections_per_system: usize, amplitude: f64, radius: f64, seed: Option<u64>, shape: &str) -> PyResult<PyGalaxy> {
+        let shape = Shape::from_name(shape)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown shape {}, expected sphere, disc or ring", shape)))?;
+        Galaxy::check_params(systems, connections_per_system, amplitude, radius).map_err(PyValueError::new_err)?;
+        let galaxy = match seed {
+            None => {Galaxy::new(systems, connections_per_system, amplitude, radius, shape)}
+            Some(seed) => {Galaxy::with_seed(seed, systems, connections_per_system, amplitude, radius, shape)}
+        };
+        Ok(PyGalaxy { galaxy })
+    }
+
+    /// Reads a `.rim` file.
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<PyGalaxy> {
+        let data = fs::read(&path)
+            .map_err(|err| PyOSError::new_err(format!("Could not read {}: {}", path.display(), err)))?;
+        let galaxy = serde_json::from_slice(&data)
+            .map_err(|err| PyValueError::new_err(format!("{} is not a rim galaxy: {}", path.display(), err)))?;
+        Ok(PyGalaxy { galaxy })
+    }
+
+    /// Writes the galaxy to a `.rim` file.
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        let data = serde_json::to_vec(&self.galaxy).map_err(|err| PyValueError::new_err(err.to_string()))?;
+        fs::write(&path, data)
+            .map_err(|err| PyOSError::new_err(format!("Could not write {}: {}", path.display(), err)))
+    }
+
+    /// Every system, in the order they're stored in.
+    #[getter]
+    fn systems(&self) -> Vec<PyStarSystem> {
+        self.galaxy.systems().iter().map(|system| PyStarSystem::new(&self.galaxy, system)).collect()
+    }
+
+    /// The names of the parties exploring the galaxy.
+    #[getter]
+    fn parties(&self) -> Vec<String> {
+        self.galaxy.parties().iter().map(|party| party.name.clone()).collect()
+    }
+
+    /// The system with a hex name like "3FA2C1", or None.
+    fn find(&self, name: &str) -> Option<PyStarSystem> {
+        let name = usize::from_str_radix(name, 16).ok()?;
+        self.galaxy.systems().iter().find(|system| system.name == name)
+            .map(|system| PyStarSystem::new(&self.galaxy, system))
+    }
+
+    /// Ids of the systems along the route from `start` to `end` with the fewest lanes,
+    /// both ends included, or None if no lanes lead there.
+    fn shortest_path(&self, start: u64, end: u64) -> PyResult<Option<Vec<u64>>> {
+        let path = self.galaxy.shortest_path(self.id(start)?, self.id(end)?);
+        Ok(path.map(|path| path.into_iter().map(|id| id.0).collect()))
+    }
+
+    /// How many lanes away every system reachable from `start` is, by id.
+    fn hops_from(&self, start: u64) -> PyResult<HashMap<u64, usize>> {
+        let hops = self.galaxy.hops_from([self.id(start)?]);
+        Ok(hops.into_iter().map(|(id, hops)| (id.0, hops)).collect())
+    }
+
+    fn __len__(&self) -> usize {
+        self.galaxy.len()
+    }
+
+    fn __iter__(&self) -> SystemIter {
+        SystemIter { systems: self.systems().into_iter() }
+    }
+
+    /// The system with the given id.
+    fn __getitem__(&self, id: u64) -> PyResult<PyStarSystem> {
+        Ok(PyStarSystem::new(&self.galaxy, &self.galaxy[self.id(id)?]))
+    }
+
+    fn __contains__(&self, id: u64) -> bool {
+        self.galaxy.contains(SystemId(id))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Galaxy with {} systems>", self.galaxy.len())
+    }
+}
+
+impl PyGalaxy {
+    fn id(&self, id: u64) -> PyResult<SystemId> {
+        if self.galaxy.contains(SystemId(id)) {
+            Ok(SystemId(id))
+        }
+        else {
+            Err(PyKeyError::new_err(format!("No system with id {}", id)))
+        }
+    }
+}
+
+#[pyclass(module = "rim")]
+struct SystemIter {
+    systems: std::vec::IntoIter<PyStarSystem>,
+}
+
+#[pymethods]
+impl SystemIter {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<PyStarSystem> {
+        slf.systems.next()
+    }
+}
+
+/// A star system. Lanes and paths refer to systems by `id`, while `name` is the hex
+/// name rim shows.
+#[pyclass(name = "StarSystem", module = "rim")]
+#[derive(Clone)]
+struct PyStarSystem {
+    system: StarSystem,
+    explored_by: Vec<String>,
+}
+
+impl PyStarSystem {
+    fn new(galaxy: &Galaxy, system: &StarSystem) -> PyStarSystem {
+        let explored_by = galaxy.parties().iter()
+            .filter(|party| party.has_explored(system.id()))
+            .map(|party| party.name.clone())
+            .collect();
+        PyStarSystem { system: system.clone(), explored_by }
+    }
+}
+
+#[pymethods]
+impl PyStarSystem {
+    #[getter]
+    fn id(&self) -> u64 {
+        self.system.id().0
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        format!("{:X}", self.system.name)
+    }
+
+    /// Where the system rests, as (x, y, z). On the map it drifts a little around this.
+    #[getter]
+    fn position(&self) -> (f32, f32, f32) {
+        let origin = self.system.origin();
+        (origin.x, origin.y, origin.z)
+    }
+
+    /// Ids of the systems this one has lanes to.
+    #[getter]
+    fn connections(&self) -> Vec<u64> {
+        self.system.connections.iter().map(|id| id.0).collect()
+    }
+
+    /// Whether any party has been to the system.
+    #[getter]
+    fn explored(&self) -> bool {
+        !self.explored_by.is_empty()
+    }
+
+    /// The names of the parties that have been to the system.
+    #[getter]
+    fn explored_by(&self) -> Vec<String> {
+        self.explored_by.clone()
+    }
+
+    /// Whether the planets and star are known. Unscanned systems have no planets and no star mass.
+    #[getter]
+    fn scanned(&self) -> bool {
+        self.system.system_data.is_some()
+    }
+
+    /// In solar masses.
+    #[getter]
+    fn star_mass(&self) -> Option<f64> {
+        self.system.system_data.as_ref().map(|data| data.star_solar_masses())
+    }
+
+    #[getter]
+    fn habitability(&self) -> Option<f64> {
+        self.system.system_data.as_ref().map(|data| data.habitability())
+    }
+
+    #[getter]
+    fn planets(&self) -> Vec<PyPlanet> {
+        let planets = self.system.system_data.iter().flat_map(|data| data.planets.iter().enumerate());
+        planets.map(|(i, planet)| PyPlanet::new(&self.system, i, planet)).collect()
+    }
+
+    #[getter]
+    fn notes(&self) -> String {
+        self.system.notes.clone()
+    }
+
+    #[getter]
+    fn tags(&self) -> Vec<String> {
+        self.system.tags.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<StarSystem {:X}>", self.system.name)
+    }
+}
+
+/// A planet, in order of its orbit.
+#[pyclass(name = "Planet", module = "rim")]
+#[derive(Clone)]
+struct PyPlanet {
+    designation: String,
+    planet: Planet,
+    moons: Vec<PyMoon>,
+}
+
+impl PyPlanet {
+    fn new(system: &StarSystem, index: usize, planet: &Planet) -> PyPlanet {
+        let moons = planet.moons.iter().enumerate()
+            .map(|(j, moon)| PyMoon { designation: system.moon_designation(index, j), moon: moon.clone() })
+            .collect();
+        PyPlanet { designation: system.planet_designation(index), planet: planet.clone(), moons }
+    }
+}
+
+#[pymethods]
+impl PyPlanet {
+    /// Like "3FA2C1-B".
+    #[getter]
+    fn designation(&self) -> String {
+        self.designation.clone()
+    }
+
+    /// The class as it's written in `.rim` files, like "GasGiant".
+    #[getter]
+    fn planet_class(&self) -> String {
+        format!("{:?}", self.planet.class)
+    }
+
+    /// In Earth masses.
+    #[getter]
+    fn mass(&self) -> f64 {
+        self.planet.mass
+    }
+
+    #[getter]
+    fn orbit_radius(&self) -> f64 {
+        self.planet.orbit_radius
+    }
+
+    /// How far around its orbit the planet is, from 0 to 1.
+    #[getter]
+    fn orbit_completion(&self) -> f64 {
+        self.planet.orbit_completion
+    }
+
+    /// The inner and outer edge of the rings in planet radii, or None.
+    #[getter]
+    fn rings(&self) -> Option<(f64, f64)> {
+        self.planet.rings.as_ref().map(|rings| (rings.inner, rings.outer))
+    }
+
+    #[getter]
+    fn habitability(&self) -> f64 {
+        self.planet.class.habitability()
+    }
+
+    #[getter]
+    fn moons(&self) -> Vec<PyMoon> {
+        self.moons.clone()
+    }
+
+    #[getter]
+    fn notes(&self) -> String {
+        self.planet.notes.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Planet {} {:?}>", self.designation, self.planet.class)
+    }
+}
+
+#[pyclass(name = "Moon", module = "rim")]
+#[derive(Clone)]
+struct PyMoon {
+    designation: String,
+    moon: Moon,
+}
+
+#[pymethods]
+impl PyMoon {
+    /// Like "3FA2C1-B2".
+    #[getter]
+    fn designation(&self) -> String {
+        self.designation.clone()
+    }
+
+    /// The type as it's written in `.rim` files, like "SubsurfaceOcean".
+    #[getter]
+    fn moon_type(&self) -> String {
+        format!("{:?}", self.moon.moon_type)
+    }
+
+    #[getter]
+    fn mass(&self) -> f64 {
+        self.moon.mass
+    }
+
+    #[getter]
+    fn orbital_radius(&self) -> f64 {
+        self.moon.orbital_radius
+    }
+
+    /// How far around its orbit the moon is, from 0 to 1.
+    #[getter]
+    fn orbit_completion(&self) -> f64 {
+        self.moon.orbit_completion
+    }
+
+    #[getter]
+    fn notes(&self) -> String {
+        self.moon.notes.clone()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<Moon {} {:?}>", self.designation, self.moon.moon_type)
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rim")]
+fn rim_python(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGalaxy>()?;
+    m.add_class::<PyStarSystem>()?;
+    m.add_class::<PyPlanet>()?;
+    m.add_class::<PyMoon>()?;
+    Ok(())
+}
@@ -0,0 +1,31 @@
+# Run with `maturin develop` then `pytest` from the python directory.
+import pytest
+
+import rim
+
+
+def layout(galaxy):
+    return [(system.name, system.position, system.connections, [planet.planet_class for planet in system.planets])
+            for system in galaxy]
+
+
+def test_same_seed_gives_same_galaxy():
+    for shape in ["sphere", "disc", "ring"]:
+        first = rim.Galaxy.generate(systems=40, connections_per_system=3, seed=42, shape=shape)
+        second = rim.Galaxy.generate(systems=40, connections_per_system=3, seed=42, shape=shape)
+        other = rim.Galaxy.generate(systems=40, connections_per_system=3, seed=43, shape=shape)
+        assert layout(first) == layout(second)
+        assert layout(first) != layout(other)
+
+
+@pytest.mark.parametrize("kwargs", [
+    {"systems": 1},
+    {"systems": 10, "connections_per_system": 10},
+    {"amplitude": 0.0},
+    {"amplitude": float("nan")},
+    {"radius": -5.0},
+    {"shape": "cube"},
+])
+def test_bad_settings_raise_value_error(kwargs):
+    with pytest.raises(ValueError):
+        rim.Galaxy.generate(**kwargs)
@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::DEFAULT_CONFIG;
+use crate::filter::Filter;
+use crate::map::{Galaxy, Shape};
+use crate::input::Bindings;
+
+
+/// Everything a player can tune without recompiling. See `default_config.toml` for
+/// what each setting does.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    pub window: WindowConfig,
+    pub camera: CameraConfig,
+    pub hud: HudConfig,
+    pub galaxy: GalaxyParams,
+    pub starfield: StarfieldConfig,
+    pub server: ServerConfig,
+    pub export: ExportConfig,
+    pub bindings: Bindings,
+    /// Named filter presets for the map.
+    pub filters: BTreeMap<String, Filter>,
+    /// The file the config was read from, or would be if it existed. Bindings changed
+    /// in game are saved back here.
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+    /// Whether `--journal` asked for the undo history to be kept in a file next to the save.
+    #[serde(skip)]
+    pub journal: bool,
+}
+
+/// How the game window sits on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WindowMode {
+    Windowed,
+    /// A window without decorations covering the whole monitor.
+    Borderless,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub fn from_name(name: &str) -> Option<WindowMode> {
+        match name.to_ascii_lowercase().as_str() {
+            "windowed" | "window" => Some(WindowMode::Windowed),
+            "borderless" => Some(WindowMode::Borderless),
+            "fullscreen" => Some(WindowMode::Fullscreen),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WindowConfig {
+    pub mode: WindowMode,
+    /// None picks a size that fits the monitor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<(i32, i32)>,
+    pub vsync: bool,
+    /// 0 doesn't limit the frame rate.
+    pub target_fps: u32,
+    /// Scale the HUD by the monitor's content scale.
+    pub hidpi: bool,
+    /// Extra HUD scale on top of the HiDPI one.
+    pub ui_scale: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CameraConfig {
+    pub zoom_speed: f32,
+    pub map: MapCamera,
+    pub system: OrbitCamera,
+    pub editor: OrbitCamera,
+}
+
+/// The map camera slowly turns by itself and can be dragged around and panned.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MapCamera {
+    /// Auto-rotate speed.
+    pub orbit_speed: f32,
+    /// How fast the keys and stick turn and tilt the camera.
+    pub turn_speed: f32,
+    /// Furthest the camera can tilt towards the poles, in radians.
+    pub max_pitch: f32,
+    /// How quickly the camera settles on a focused system. Higher is snappier.
+    pub smoothing: f32,
+    pub distance: f32,
+    pub fov: (f32, f32),
+}
+
+/// A camera steered around its target with the arrow keys.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OrbitCamera {
+    pub orbit_speed: f32,
+    pub pitch_speed: f32,
+    pub distance: f32,
+    pub fov: (f32, f32),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct HudConfig {
+    pub font_size: i32,
+    pub small_font_size: i32,
+    /// 0 fits as many lines as the window has room for.
+    pub max_lines: usize,
+}
+
+/// What `Galaxy::new` is called with when there's no save to load, and what the
+/// new galaxy wizard starts from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GalaxyParams {
+    pub systems: usize,
+    pub connections_per_system: usize,
+    pub amplitude: f64,
+    pub radius: f64,
+    pub shape: Shape,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct StarfieldConfig {
+    pub stars: usize,
+    pub radius: f32,
+}
+
+/// The JSON API other programs can use to query and drive a running session.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ServerConfig {
+    pub enabled: bool,
+    /// 0 picks any free port.
+    pub port: u16,
+}
+
+/// How player copies of the galaxy are made.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ExportConfig {
+    /// Leave lanes between unexplored systems out of player copies.
+    pub hide_unknown_lanes: bool,
+}
+
+impl Config {
+    /// Loads the defaults, then the user's config file, then `--set=key=value` overrides,
+    /// then the window flags. `--config=path` picks a config file other than the usual one.
+    pub fn load(flags: &[String]) -> Result<Config, String> {
+        let mut table: toml::Table = toml::from_str(DEFAULT_CONFIG).expect("default config is valid TOML");
+
+        let explicit = flags.iter().find_map(|flag| flag.strip_prefix("--config="));
+        let path = explicit.map(PathBuf::from).or_else(config_path);
+        if let Some(path) = &path {
+            match fs::read_to_string(path) {
+                Ok(text) => {
+                    let user: toml::Table = toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+                    merge(&mut table, user);
+                }
+                // A missing config file just means the defaults, unless it was asked for
+                Err(err) if explicit.is_some() => {return Err(format!("Could not read {}: {}", path.display(), err))}
+                Err(_) => {}
+            }
+        }
+
+        for set in flags.iter().filter_map(|flag| flag.strip_prefix("--set=")) {
+            let (key, value) = set.split_once('=').ok_or(format!("--set needs key=value, got {}", set))?;
+            // Let plain words through as strings so --set=window.mode=fullscreen works without quotes
+            let parsed: toml::Table = toml::from_str(&format!("{} = {}", key, value))
+                .or_else(|_| toml::from_str(&format!("{} = {:?}", key, value)))
+                .map_err(|err| format!("--set={}: {}", set, err))?;
+            merge(&mut table, parsed);
+        }
+
+        let mut config: Config = toml::Value::Table(table).try_into().map_err(|err| format!("Bad config: {}", err))?;
+        config.path = path;
+        config.journal = flags.iter().any(|flag| flag == "--journal");
+        config.window.apply_flags(flags)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Catches settings that parse fine but would break the game.
+    fn validate(&self) -> Result<(), String> {
+        let cameras = [
+            ("camera.map", self.camera.map.distance, self.camera.map.fov),
+            ("camera.system", self.camera.system.distance, self.camera.system.fov),
+            ("camera.editor", self.camera.editor.distance, self.camera.editor.fov),
+        ];
+        for (name, distance, (min_fov, max_fov)) in cameras {
+            if distance <= 0.0 {
+                return Err(format!("{}.distance must be above 0", name))
+            }
+            if min_fov <= 0.0 || min_fov > max_fov {
+                return Err(format!("{}.fov must be [min, max] with 0 < min <= max", name))
+            }
+        }
+        let map = &self.camera.map;
+        if map.max_pitch <= 0.0 || map.max_pitch >= std::f32::consts::FRAC_PI_2 {
+            return Err("camera.map.max_pitch must be between 0 and pi/2".to_string())
+        }
+        if map.smoothing <= 0.0 {
+            return Err("camera.map.smoothing must be above 0".to_string())
+        }
+        if self.window.ui_scale <= 0.0 {
+            return Err("window.ui_scale must be above 0".to_string())
+        }
+        if let Some((width, height)) = self.window.size && (width <= 0 || height <= 0) {
+            return Err("window.size must be [width, height] in pixels".to_string())
+        }
+        if self.hud.font_size < 1 || self.hud.small_font_size < 1 {
+            return Err("hud font sizes must be at least 1".to_string())
+        }
+        let galaxy = &self.galaxy;
+        Galaxy::check_params(galaxy.systems, galaxy.connections_per_system, galaxy.amplitude, galaxy.radius)
+            .map_err(|err| format!("galaxy.{}", err))?;
+        if self.starfield.radius <= 0.0 {
+            return Err("starfield.radius must be above 0".to_string())
+        }
+        Ok(())
+    }
+}
+
+impl WindowConfig {
+    /// Overrides settings from command line flags: `--window=windowed|borderless|fullscreen`,
+    /// `--size=1280x720`, `--vsync`, `--no-vsync`, `--fps=60`, `--hidpi`, `--no-hidpi` and `--ui-scale=1.5`.
+    /// Flags that aren't about the window are ignored.
+    pub fn apply_flags(&mut self, flags: &[String]) -> Result<(), String> {
+        for flag in flags {
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (flag.as_str(), None)
+            };
+            match (name, value) {
+                ("--window", Some(value)) => {
+                    self.mode = WindowMode::from_name(value)
+                        .ok_or(format!("Unknown window mode {}, expected windowed, borderless or fullscreen", value))?;
+                }
+                ("--size", Some(value)) => {
+                    self.size = Some(parse_size(value).ok_or(format!("Bad window size {}, expected something like 1280x720", value))?);
+                }
+                ("--vsync", None) => {self.vsync = true}
+                ("--no-vsync", None) => {self.vsync = false}
+                ("--fps", Some(value)) => {
+                    self.target_fps = value.parse().map_err(|_| format!("Bad frame rate {}", value))?;
+                }
+                ("--hidpi", None) => {self.hidpi = true}
+                ("--no-hidpi", None) => {self.hidpi = false}
+                ("--ui-scale", Some(value)) => {
+                    self.ui_scale = value.parse().ok().filter(|scale: &f32| *scale > 0.0)
+                        .ok_or(format!("Bad UI scale {}", value))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The bindings `default_config.toml` ships with.
+pub(crate) fn default_bindings() -> Bindings {
+    let mut table: toml::Table = toml::from_str(DEFAULT_CONFIG).expect("default config is valid TOML");
+    table.remove("bindings").expect("default config has bindings").try_into().expect("default bindings are valid")
+}
+
+/// Replaces one table of the config file at `path`, like `[bindings]`, keeping its other
+/// settings. Used for things changed in game. Comments in the file are lost.
+pub(crate) fn save_section<T: Serialize>(path: &Path, section: &str, value: &T) -> Result<(), String> {
+    let mut table: toml::Table = match fs::read_to_string(path) {
+        Ok(text) => {toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?}
+        Err(_) => {toml::Table::new()}
+    };
+    let value = toml::Value::try_from(value).map_err(|err| err.to_string())?;
+    table.insert(section.to_string(), value);
+    let text = toml::to_string(&table).map_err(|err| err.to_string())?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+    }
+    fs::write(path, text).map_err(|err| format!("Could not write {}: {}", path.display(), err))
+}
+
+/// Where the config file lives when `--config` isn't given.
+pub(crate) fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => {PathBuf::from(dir)}
+        None => {
+            match env::var_os("APPDATA") {
+                Some(dir) => {PathBuf::from(dir)}
+                None => {PathBuf::from(env::var_os("HOME")?).join(".config")}
+            }
+        }
+    };
+    Some(base.join("rim").join("config.toml"))
+}
+
+/// Lays `overrides` over `base`, going into tables so a file that sets one camera
+/// speed keeps the defaults for the rest.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(table)) => {merge(base_table, table)}
+            (_, value) => {base.insert(key, value);}
+        }
+    }
+}
+
+fn parse_size(value: &str) -> Option<(i32, i32)> {
+    let (width, height) = value.to_ascii_lowercase().split_once('x')
+        .map(|(w, h)| (w.trim().parse().ok(), h.trim().parse().ok()))?;
+    match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads `config` as the user's config file, named after the test so tests running at
+    /// once don't share one, with `flags` on top.
+    fn load(name: &str, config: &str, flags: &[&str]) -> Result<Config, String> {
+        let path = env::temp_dir().join(format!("rim-config-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, config).unwrap();
+        let mut all = vec![format!("--config={}", path.display())];
+        all.extend(flags.iter().map(|flag| flag.to_string()));
+        let config = Config::load(&all);
+        let _ = fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn partial_tables_keep_their_other_defaults() {
+        let defaults = load("defaults", "", &[]).unwrap();
+        let config = load("partial", "[camera.map]\norbit_speed = 0.5\n\n[galaxy]\nsystems = 50\n", &[]).unwrap();
+        assert_eq!(config.camera.map.orbit_speed, 0.5);
+        assert_eq!(config.camera.map.turn_speed, defaults.camera.map.turn_speed);
+        assert_eq!(config.camera.map.fov, defaults.camera.map.fov);
+        assert_eq!(config.camera.system.orbit_speed, defaults.camera.system.orbit_speed);
+        assert_eq!(config.galaxy.systems, 50);
+        assert_eq!(config.galaxy.connections_per_system, defaults.galaxy.connections_per_system);
+        assert_eq!(config.galaxy.shape, defaults.galaxy.shape);
+    }
+
+    #[test]
+    fn set_overrides_the_config_file() {
+        let config = load("set", "[window]\nmode = \"borderless\"\n", &["--set=window.mode=fullscreen", "--set=hud.max_lines=5"]).unwrap();
+        assert_eq!(config.window.mode, WindowMode::Fullscreen);
+        assert_eq!(config.hud.max_lines, 5);
+        let config = load("set-quoted", "", &["--set=window.mode=\"windowed\"", "--set=camera.map.fov=[10.0, 60.0]"]).unwrap();
+        assert_eq!(config.window.mode, WindowMode::Windowed);
+        assert_eq!(config.camera.map.fov, (10.0, 60.0));
+        assert!(load("set-bad", "", &["--set=window.mode"]).is_err());
+        assert!(load("set-unknown", "", &["--set=window.colour=red"]).is_err());
+    }
+
+    #[test]
+    fn bad_cameras_are_rejected() {
+        for set in [
+            "--set=camera.map.fov=[60.0, 10.0]",
+            "--set=camera.system.fov=[0.0, 10.0]",
+            "--set=camera.editor.fov=[-5.0, 10.0]",
+            "--set=camera.map.max_pitch=0.0",
+            "--set=camera.map.max_pitch=1.6",
+            "--set=camera.map.distance=0.0",
+        ] {
+            let err = load("bad-camera", "", &[set]).unwrap_err();
+            assert!(err.starts_with("camera."), "{} gave {}", set, err);
+        }
+    }
+
+    #[test]
+    fn sizes_are_width_by_height() {
+        assert_eq!(parse_size("1280x720"), Some((1280, 720)));
+        assert_eq!(parse_size("800 X 600"), Some((800, 600)));
+        assert_eq!(parse_size("0x720"), None);
+        assert_eq!(parse_size("1280"), None);
+        assert_eq!(parse_size("widex720"), None);
+    }
+}
@@ -0,0 +1,246 @@
+use std::fmt::Write;
+
+use serde_json::json;
+
+use crate::map::{Galaxy, StarSystem};
+use crate::party::Perspective;
+
+/// Formats the lane network can be written out in for other graph tools.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    Dot,
+    GraphMl,
+    GeoJson,
+}
+
+impl GraphFormat {
+    pub const ALL: [GraphFormat; 3] = [GraphFormat::Dot, GraphFormat::GraphMl, GraphFormat::GeoJson];
+
+    pub fn from_name(name: &str) -> Option<GraphFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Some(GraphFormat::Dot),
+            "graphml" => Some(GraphFormat::GraphMl),
+            "geojson" | "json" => Some(GraphFormat::GeoJson),
+            _ => None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::GraphMl => "graphml",
+            GraphFormat::GeoJson => "geojson",
+        }
+    }
+}
+
+pub fn export_graph(galaxy: &Galaxy, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(galaxy),
+        GraphFormat::GraphMl => to_graphml(galaxy),
+        GraphFormat::GeoJson => to_geojson(galaxy),
+    }
+}
+
+/// Every lane once, as the two systems it joins and its length.
+fn lanes(galaxy: &Galaxy) -> Vec<(&StarSystem, &StarSystem, f32)> {
+    let mut lanes = Vec::new();
+    for system in galaxy.systems() {
+        for &conn in &system.connections {
+            if conn > system.id() {
+                let other = &galaxy[conn];
+                lanes.push((system, other, (other.origin() - system.origin()).length()));
+            }
+        }
+    }
+    lanes
+}
+
+fn planet_count(system: &StarSystem) -> Option<usize> {
+    system.system_data.as_ref().map(|data| data.planets.len())
+}
+
+fn star_mass(system: &StarSystem) -> Option<f64> {
+    system.system_data.as_ref().map(|data| data.star_mass)
+}
+
+fn to_dot(galaxy: &Galaxy) -> String {
+    let mut out = String::from("graph galaxy {\n");
+    out += "    node [shape=point];\n";
+    for system in galaxy.systems() {
+        let pos = system.origin();
+        // pos is a top-down projection so neato -n can lay the graph out like the map
+        let _ = write!(out, "    {} [label=\"{:X}\", pos=\"{},{}\", x={}, y={}, z={}, explored={}",
+            system.id().0, system.name, pos.x, pos.z, pos.x, pos.y, pos.z, galaxy.is_explored(system.id(), Perspective::Everyone));
+        if let Some(count) = planet_count(system) {
+            let _ = write!(out, ", planets={}", count);
+        }
+        if let Some(mass) = star_mass(system) {
+            // DOT numerals can't have exponents, so the mass in kilograms goes in quotes
+            let _ = write!(out, ", star_mass=\"{:e}\"", mass);
+        }
+        out += "];\n";
+    }
+    for (a, b, length) in lanes(galaxy) {
+        let _ = writeln!(out, "    {} -- {} [length={}];", a.id().0, b.id().0, length);
+    }
+    out += "}\n";
+    out
+}
+
+fn to_graphml(galaxy: &Galaxy) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out += "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n";
+    for (id, domain, kind) in [
+        ("name", "node", "string"),
+        ("x", "node", "float"),
+        ("y", "node", "float"),
+        ("z", "node", "float"),
+        ("explored", "node", "boolean"),
+        ("planets", "node", "int"),
+        ("star_mass", "node", "double"),
+        ("length", "edge", "float"),
+    ] {
+        let _ = writeln!(out, "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>", id, domain, id, kind);
+    }
+    out += "  <graph id=\"galaxy\" edgedefault=\"undirected\">\n";
+    for system in galaxy.systems() {
+        let pos = system.origin();
+        let _ = writeln!(out, "    <node id=\"s{}\">", system.id().0);
+        let _ = writeln!(out, "      <data key=\"name\">{}</data>", xml_escape(&format!("{:X}", system.name)));
+        let _ = writeln!(out, "      <data key=\"x\">{}</data>", pos.x);
+        let _ = writeln!(out, "      <data key=\"y\">{}</data>", pos.y);
+        let _ = writeln!(out, "      <data key=\"z\">{}</data>", pos.z);
+        let _ = writeln!(out, "      <data key=\"explored\">{}</data>", galaxy.is_explored(system.id(), Perspective::Everyone));
+        if let Some(count) = planet_count(system) {
+            let _ = writeln!(out, "      <data key=\"planets\">{}</data>", count);
+        }
+        if let Some(mass) = star_mass(system) {
+            let _ = writeln!(out, "      <data key=\"star_mass\">{:e}</data>", mass);
+        }
+        out += "    </node>\n";
+    }
+    for (a, b, length) in lanes(galaxy) {
+        let _ = writeln!(out, "    <edge source=\"s{}\" target=\"s{}\">", a.id().0, b.id().0);
+        let _ = writeln!(out, "      <data key=\"length\">{}</data>", length);
+        out += "    </edge>\n";
+    }
+    out += "  </graph>\n</graphml>\n";
+    out
+}
+
+/// GeoJSON with the galaxy sphere treated as a globe: coordinates are longitude and
+/// latitude in degrees, so any map tool can draw it. The 3D position is kept in the properties.
+fn to_geojson(galaxy: &Galaxy) -> String {
+    let mut features = Vec::new();
+    for system in galaxy.systems() {
+        let pos = system.origin();
+        features.push(json!({
+            "type": "Feature",
+            "id": system.id().0,
+            "geometry": {"type": "Point", "coordinates": lon_lat(system)},
+            "properties": {
+                "kind": "system",
+                "name": format!("{:X}", system.name),
+                "position": [pos.x, pos.y, pos.z],
+                "explored": galaxy.is_explored(system.id(), Perspective::Everyone),
+                "planets": planet_count(system),
+                "star_mass": star_mass(system),
+            }
+        }));
+    }
+    for (a, b, length) in lanes(galaxy) {
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {"type": "LineString", "coordinates": [lon_lat(a), lon_lat(b)]},
+            "properties": {
+                "kind": "lane",
+                "source": a.id().0,
+                "target": b.id().0,
+                "length": length,
+            }
+        }));
+    }
+    serde_json::to_string_pretty(&json!({"type": "FeatureCollection", "features": features})).unwrap()
+}
+
+fn lon_lat(system: &StarSystem) -> [f32; 2] {
+    let pos = system.origin();
+    let radius = pos.length().max(f32::EPSILON);
+    [pos.z.atan2(pos.x).to_degrees(), (pos.y / radius).clamp(-1.0, 1.0).asin().to_degrees()]
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::SystemId;
+    use crate::system::StarSystemData;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    /// Two systems joined by a lane: 0 explored and scanned on the globe's equator, 1
+    /// unscanned a quarter turn round and halfway up.
+    fn two_systems() -> Galaxy {
+        let data = StarSystemData::generate(&mut StdRng::seed_from_u64(3));
+        serde_json::from_value(json!({
+            "version": 2,
+            "next_id": 2,
+            "systems": [
+                {"id": 0, "position": [10.0, 0.0, 0.0], "origin": [10.0, 0.0, 0.0], "drift_direction": [0.0, 0.0, 0.0],
+                    "connections": [1], "name": 0xABC, "system_data": data},
+                {"id": 1, "position": [0.0, 5.0, 5.0], "origin": [0.0, 5.0, 5.0], "drift_direction": [0.0, 0.0, 0.0],
+                    "connections": [0], "name": 0xDEF, "system_data": null},
+            ],
+            "parties": [{"name": "Players", "explored": {"0": 0}}]
+        })).unwrap()
+    }
+
+    #[test]
+    fn dot_quotes_the_star_mass() {
+        let galaxy = two_systems();
+        let dot = to_dot(&galaxy);
+        let mass = galaxy[SystemId(0)].system_data.as_ref().unwrap().star_mass;
+        assert!(dot.starts_with("graph galaxy {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains(&format!("    0 [label=\"ABC\", pos=\"10,0\", x=10, y=0, z=0, explored=true, planets={}, star_mass=\"{:e}\"];\n",
+            galaxy[SystemId(0)].system_data.as_ref().unwrap().planets.len(), mass)));
+        assert!(dot.contains("    1 [label=\"DEF\", pos=\"0,5\", x=0, y=5, z=5, explored=false];\n"));
+        assert!(dot.contains("    0 -- 1 [length="));
+        // Unquoted numbers with exponents are what Graphviz chokes on
+        assert!(format!("{:e}", mass).contains('e'));
+        assert!(!dot.contains(&format!("={:e}", mass)));
+    }
+
+    #[test]
+    fn graphml_is_escaped() {
+        assert_eq!(xml_escape("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+        let graphml = to_graphml(&two_systems());
+        assert!(graphml.contains("    <node id=\"s0\">\n      <data key=\"name\">ABC</data>\n"));
+        assert!(graphml.contains("      <data key=\"explored\">false</data>\n"));
+        assert!(graphml.contains("    <edge source=\"s0\" target=\"s1\">\n"));
+        assert!(graphml.ends_with("  </graph>\n</graphml>\n"));
+    }
+
+    #[test]
+    fn geojson_places_systems_by_longitude_and_latitude() {
+        let geojson: serde_json::Value = serde_json::from_str(&to_geojson(&two_systems())).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(features.len(), 3);
+        let coordinates = |feature: &serde_json::Value| -> Vec<f64> {
+            feature["geometry"]["coordinates"].as_array().unwrap().iter().map(|value| value.as_f64().unwrap()).collect()
+        };
+        let close = |found: Vec<f64>, expected: [f64; 2]| {
+            assert!(found.iter().zip(expected).all(|(found, expected)| (found - expected).abs() < 1e-4), "{:?} isn't {:?}", found, expected);
+        };
+        close(coordinates(&features[0]), [0.0, 0.0]);
+        close(coordinates(&features[1]), [90.0, 45.0]);
+        assert_eq!(features[1]["properties"]["planets"], serde_json::Value::Null);
+        assert_eq!(features[2]["geometry"]["type"], "LineString");
+        assert_eq!(features[2]["properties"]["source"], 0);
+        assert_eq!(features[2]["properties"]["target"], 1);
+    }
+}
@@ -1,24 +1,118 @@
+use crate::export::{self, GraphFormat};
+use crate::history::History;
 use crate::map::Galaxy;
+use crate::party::Perspective;
 use crate::system::StarSystemData;
 
+use serde::{Serialize, Deserialize};
+
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
 use std::sync::mpsc;
 use std::fs;
+use std::io;
+use std::path::Path;
 
 
 
-pub(crate) fn load_file(path: &String) -> Option<Galaxy> {
-    let data: Vec<u8>  = fs::read(&path).ok()?;
+pub fn load_file(path: &String) -> Option<Galaxy> {
+    let data: Vec<u8>  = fs::read(path).ok()?;
     serde_json::from_slice(&data).ok()
 }
 
-pub(crate) fn save(path: &String, save: Galaxy) {
-    let data = serde_json::to_vec(&save).unwrap();
-    fs::write(path, data).unwrap();
+pub fn save(path: &String, save: Galaxy) {
+    try_save(path, &save).unwrap();
+}
+
+/// Like `save`, for saving while rim is running, where failing shouldn't take it down.
+pub fn try_save(path: &String, galaxy: &Galaxy) -> io::Result<()> {
+    let data = serde_json::to_vec(galaxy).map_err(io::Error::other)?;
+    fs::write(path, data)
+}
+
+/// When the file at `path` was last modified, or None if it can't be read.
+pub fn modified(path: &String) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Path next to the save with its extension swapped, for exported files.
+pub fn export_path(path: &String, extension: &str) -> String {
+    Path::new(path).with_extension(extension).to_string_lossy().into_owned()
+}
+
+pub fn export_graph(path: &String, galaxy: &Galaxy, format: GraphFormat) -> io::Result<()> {
+    fs::write(path, export::export_graph(galaxy, format))
+}
+
+pub fn export_svg(path: &String, svg: String) -> io::Result<()> {
+    fs::write(path, svg)
+}
+
+/// Writes a redacted copy of the galaxy for players, see `Galaxy::redacted`.
+pub fn export_player_copy(path: &String, galaxy: &Galaxy, perspective: Perspective, hide_unknown_lanes: bool) -> io::Result<()> {
+    let data = serde_json::to_vec(&galaxy.redacted(perspective, hide_unknown_lanes)).map_err(io::Error::other)?;
+    fs::write(path, data)
+}
+
+/// The undo history of a save, kept next to it so it can be picked up again next session.
+/// It remembers a hash of the save it was written alongside, so a save that was changed
+/// some other way doesn't get stale edits replayed onto it.
+#[derive(Serialize, Deserialize)]
+struct Journal<H> {
+    save_hash: u64,
+    history: H
+}
+
+/// Where the galaxy is saved if rim crashes, to be offered back next time the save is opened.
+pub fn recovery_path(path: &String) -> String {
+    format!("{}.recovery", path)
+}
+
+pub fn save_recovery(path: &String, galaxy: &Galaxy) -> io::Result<()> {
+    let data = serde_json::to_vec(galaxy).map_err(io::Error::other)?;
+    fs::write(recovery_path(path), data)
+}
+
+pub fn load_recovery(path: &String) -> Option<Galaxy> {
+    load_file(&recovery_path(path))
+}
+
+pub fn discard_recovery(path: &String) {
+    let _ = fs::remove_file(recovery_path(path));
+}
+
+pub fn journal_path(path: &String) -> String {
+    format!("{}.journal", path)
+}
+
+/// The undo history kept alongside a save, or None if there isn't one for the save as it
+/// is now. Errors if there is a journal but it can't be read.
+pub fn load_journal(path: &String) -> Result<Option<History>, String> {
+    let (save, data) = match (fs::read(path), fs::read(journal_path(path))) {
+        (Ok(save), Ok(data)) => {(save, data)}
+        _ => {return Ok(None)}
+    };
+    let journal: Journal<History> = serde_json::from_slice(&data)
+        .map_err(|err| format!("{} couldn't be read: {}", journal_path(path), err))?;
+    if journal.save_hash != hash_bytes(&save) {
+        return Ok(None)
+    }
+    Ok(Some(journal.history))
+}
+
+/// Writes the undo history next to the save, which has to be written first.
+pub fn save_journal(path: &String, history: &History) -> io::Result<()> {
+    let save = fs::read(path)?;
+    let data = serde_json::to_vec(&Journal { save_hash: hash_bytes(&save), history }).map_err(io::Error::other)?;
+    fs::write(journal_path(path), data)
+}
+
+/// 64 bit FNV-1a, which unlike std's hashers gives the same hash whatever rim was built with.
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
 }
 
-pub(crate) fn generate_system_data() -> StarSystemData {
+pub fn generate_system_data() -> StarSystemData {
     loop {
         match try_generate_system() {
             None => {}
@@ -34,9 +128,18 @@ fn try_generate_system() -> Option<StarSystemData> {
         let result = StarSystemData::new();
         let _ = tx.send(result);
     });
-    match rx.recv_timeout(Duration::from_micros(100)) {
-        Ok(data) => return Some(data),
-        Err(_) => {}
-    } 
-    return None;
+    rx.recv_timeout(Duration::from_micros(100)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn journal_hash_is_fnv_1a() {
+        // Journals on disk rely on these never changing
+        assert_eq!(hash_bytes(b""), 0xcbf29ce484222325);
+        assert_eq!(hash_bytes(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash_bytes(b"foobar"), 0x85944171f73967e8);
+    }
 }
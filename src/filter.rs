@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use serde::{Serialize, Deserialize};
+
+use crate::map::{Galaxy, StarSystem, SystemId};
+use crate::party::Perspective;
+use crate::system::{MoonType, PlanetClass};
+
+/// Criteria for highlighting systems on the map. Every criterion that's set has to hold,
+/// and systems that haven't been scanned only match filters that don't look at planets or stars.
+/// Saved presets are stored in the `[filters]` config table in this shape.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Filter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub planet_class: Option<PlanetClass>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub more_planets_than: Option<usize>,
+    /// In solar masses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_star_mass: Option<f64>,
+    /// In solar masses.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_star_mass: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moon_type: Option<MoonType>,
+    /// Unexplored systems at most this many lanes from an explored one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unexplored_within: Option<usize>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        *self == Filter::default()
+    }
+
+    /// The systems the filter lets through, with what's explored seen from `perspective`.
+    pub fn matching(&self, galaxy: &Galaxy, perspective: Perspective) -> HashSet<SystemId> {
+        let explored = galaxy.explored(perspective);
+        let hops = self.unexplored_within.map(|_| galaxy.hops_from(explored.iter().copied()));
+        galaxy.systems().iter()
+            .filter(|system| {
+                match (self.unexplored_within, &hops) {
+                    (Some(max_hops), Some(hops)) => {
+                        !explored.contains(&system.id()) && hops.get(&system.id()).is_some_and(|hops| *hops <= max_hops)
+                    }
+                    _ => {true}
+                }
+            })
+            .filter(|system| self.matches_data(system))
+            .map(StarSystem::id)
+            .collect()
+    }
+
+    fn matches_data(&self, system: &StarSystem) -> bool {
+        let needs_data = self.planet_class.is_some() || self.more_planets_than.is_some()
+            || self.min_star_mass.is_some() || self.max_star_mass.is_some() || self.moon_type.is_some();
+        let data = match &system.system_data {
+            None => {return !needs_data}
+            Some(data) => {data}
+        };
+        let star_mass = data.star_solar_masses();
+        self.planet_class.is_none_or(|class| data.planets.iter().any(|planet| planet.class == class))
+            && self.more_planets_than.is_none_or(|count| data.planets.len() > count)
+            && self.min_star_mass.is_none_or(|min| star_mass >= min)
+            && self.max_star_mass.is_none_or(|max| star_mass <= max)
+            && self.moon_type.is_none_or(|moon_type| {
+                data.planets.iter().any(|planet| planet.moons.iter().any(|moon| moon.moon_type == moon_type))
+            })
+    }
+
+    /// A short summary for the HUD, like "OceanWorld planets, more than 3 planets".
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(class) = self.planet_class {
+            parts.push(format!("{:?} planets", class));
+        }
+        if let Some(count) = self.more_planets_than {
+            parts.push(format!("more than {} planets", count));
+        }
+        match (self.min_star_mass, self.max_star_mass) {
+            (Some(min), Some(max)) => {parts.push(format!("star {}-{} solar masses", min, max))}
+            (Some(min), None) => {parts.push(format!("star over {} solar masses", min))}
+            (None, Some(max)) => {parts.push(format!("star under {} solar masses", max))}
+            (None, None) => {}
+        }
+        if let Some(moon_type) = self.moon_type {
+            parts.push(format!("{:?} moons", moon_type));
+        }
+        if let Some(hops) = self.unexplored_within {
+            parts.push(format!("unexplored within {} hops", hops));
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rim::system::{StarSystemData, SOLAR_MASS};
+    use serde_json::json;
+
+    /// A star of `solar_masses` with planets of these classes, each with these moons.
+    fn data(solar_masses: f64, planets: &[(PlanetClass, &[MoonType])]) -> StarSystemData {
+        let mut data = StarSystemData { star_mass: solar_masses * SOLAR_MASS, planets: Vec::new() };
+        for (class, moons) in planets {
+            let planet = data.add_planet(*class);
+            let planet = &mut data.planets[planet];
+            planet.moons.clear();
+            for moon in moons.iter() {
+                planet.add_moon(*moon);
+            }
+        }
+        data
+    }
+
+    /// Systems 0, 1, 2... joined in a line, with the first explored by one party and the
+    /// last by another.
+    fn line(data: Vec<Option<StarSystemData>>) -> Galaxy {
+        let last = data.len() - 1;
+        let systems: Vec<_> = data.into_iter().enumerate().map(|(i, data)| {
+            let connections: Vec<usize> = [i.checked_sub(1), Some(i + 1).filter(|next| *next <= last)].into_iter().flatten().collect();
+            json!({
+                "id": i, "position": [0.0, 0.0, 0.0], "origin": [0.0, 0.0, 0.0], "drift_direction": [0.0, 0.0, 0.0],
+                "connections": connections, "name": i, "system_data": data,
+            })
+        }).collect();
+        serde_json::from_value(json!({
+            "version": 2,
+            "systems": systems,
+            "parties": [{"name": "First", "explored": {"0": 0}}, {"name": "Second", "explored": {last.to_string(): 0}}]
+        })).unwrap()
+    }
+
+    fn ids(ids: &[u64]) -> HashSet<SystemId> {
+        ids.iter().copied().map(SystemId).collect()
+    }
+
+    #[test]
+    fn planets_and_moons_are_looked_for() {
+        let galaxy = line(vec![
+            Some(data(1.0, &[(PlanetClass::OceanWorld, &[]), (PlanetClass::Desert, &[]), (PlanetClass::GasGiant, &[])])),
+            Some(data(1.0, &[(PlanetClass::GasGiant, &[MoonType::SubsurfaceOcean, MoonType::Asteroid])])),
+            Some(data(1.0, &[])),
+            None,
+        ]);
+        let ocean_worlds = Filter { planet_class: Some(PlanetClass::OceanWorld), ..Filter::default() };
+        assert_eq!(ocean_worlds.matching(&galaxy, Perspective::Everyone), ids(&[0]));
+        let many_planets = Filter { more_planets_than: Some(2), ..Filter::default() };
+        assert_eq!(many_planets.matching(&galaxy, Perspective::Everyone), ids(&[0]));
+        let some_planets = Filter { more_planets_than: Some(0), ..Filter::default() };
+        assert_eq!(some_planets.matching(&galaxy, Perspective::Everyone), ids(&[0, 1]));
+        let ocean_moons = Filter { moon_type: Some(MoonType::SubsurfaceOcean), ..Filter::default() };
+        assert_eq!(ocean_moons.matching(&galaxy, Perspective::Everyone), ids(&[1]));
+        assert_eq!(Filter::default().matching(&galaxy, Perspective::Everyone), ids(&[0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn star_mass_bounds_are_inclusive() {
+        let galaxy = line(vec![Some(data(0.5, &[])), Some(data(1.0, &[])), Some(data(2.0, &[])), Some(data(4.0, &[])), None]);
+        let between = Filter { min_star_mass: Some(1.0), max_star_mass: Some(2.0), ..Filter::default() };
+        assert_eq!(between.matching(&galaxy, Perspective::Everyone), ids(&[1, 2]));
+        let heavy = Filter { min_star_mass: Some(2.0), ..Filter::default() };
+        assert_eq!(heavy.matching(&galaxy, Perspective::Everyone), ids(&[2, 3]));
+        let light = Filter { max_star_mass: Some(0.5), ..Filter::default() };
+        assert_eq!(light.matching(&galaxy, Perspective::Everyone), ids(&[0]));
+    }
+
+    #[test]
+    fn unexplored_within_counts_lanes_from_the_perspective() {
+        let galaxy = line(vec![None, None, None, None, None, None]);
+        let near = Filter { unexplored_within: Some(1), ..Filter::default() };
+        assert_eq!(near.matching(&galaxy, Perspective::Everyone), ids(&[1, 4]));
+        assert_eq!(near.matching(&galaxy, Perspective::Party(0)), ids(&[1]));
+        assert_eq!(near.matching(&galaxy, Perspective::Party(1)), ids(&[4]));
+        let further = Filter { unexplored_within: Some(2), ..Filter::default() };
+        assert_eq!(further.matching(&galaxy, Perspective::Party(0)), ids(&[1, 2]));
+        // Explored systems are never unexplored, however close
+        let here = Filter { unexplored_within: Some(0), ..Filter::default() };
+        assert!(here.matching(&galaxy, Perspective::Everyone).is_empty());
+    }
+
+    #[test]
+    fn criteria_combine() {
+        let galaxy = line(vec![
+            None,
+            Some(data(1.0, &[(PlanetClass::OceanWorld, &[])])),
+            Some(data(1.0, &[(PlanetClass::Desert, &[])])),
+            Some(data(1.0, &[(PlanetClass::OceanWorld, &[])])),
+            None,
+        ]);
+        let filter = Filter { planet_class: Some(PlanetClass::OceanWorld), unexplored_within: Some(2), ..Filter::default() };
+        assert_eq!(filter.matching(&galaxy, Perspective::Party(0)), ids(&[1]));
+        assert_eq!(filter.matching(&galaxy, Perspective::Everyone), ids(&[1, 3]));
+        let filter = Filter { moon_type: Some(MoonType::Asteroid), ..filter };
+        assert!(filter.matching(&galaxy, Perspective::Everyone).is_empty());
+        // Unscanned systems can't be shown to have any planets
+        let scanned = Filter { more_planets_than: Some(0), ..Filter::default() };
+        assert_eq!(scanned.matching(&galaxy, Perspective::Everyone), ids(&[1, 2, 3]));
+    }
+}
@@ -1,55 +1,273 @@
+use std::collections::HashSet;
 use std::f64::consts::PI;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::sync::Arc;
+use std::thread;
 
 use raylib::prelude::*;
 
 use crate::{
-    map::{self, Galaxy}, 
-    system::{MoonType, PlanetClass, StarSystemData}, 
-    utils::{self, point_on_3d_circle}
+    config::{self, Config, WindowConfig, WindowMode},
+    export::GraphFormat,
+    file_generator,
+    filter::Filter,
+    heatmap::{self, Heatmap, Metric},
+    history::{self, Edit, History},
+    input::{Action, Input},
+    map::{self, Galaxy, SystemId}, 
+    party::{self, Party, Perspective},
+    picking::{self, Picker, View},
+    search,
+    server::{self, Command, Response, Server},
+    menu,
+    session::{self, Message, Session},
+    svg::{self, Projection},
+    system::{Moon, MoonType, PlanetClass, StarSystemData}, 
+    ui::{TextField, TextFieldEvent},
+    utils::{self, point_on_3d_circle},
+    vector::Vec3,
+    watch::SaveWatcher
 };
 
-pub(crate) fn start_gameloop(save: Option<Galaxy>) -> Galaxy {
-    let (mut rl, thread) = raylib::init()
-        .log_level(TraceLogLevel::LOG_NONE)
-        .undecorated()
-        .size(1920, 1080)
-        .title("Rim")
-        .build();
-    rl.set_target_fps(60);
-    rl.set_exit_key(None);
+/// Runs rim in a window from `open_window` until it closes. Returns the galaxy and its history,
+/// with where to save them, or None if they shouldn't be saved.
+pub(crate) fn start_gameloop(rl: &mut RaylibHandle, thread: &RaylibThread, save: Option<Galaxy>, history: History, save_path: String, config: Config, session: Option<Session>) -> (Galaxy, History, Option<String>) {
     let galaxy = match save {
-        None => map::Galaxy::new(200, 5, 250.0, 50.0),
+        None => {
+            let params = &config.galaxy;
+            map::Galaxy::new(params.systems, params.connections_per_system, params.amplitude, params.radius, params.shape)
+        }
         Some(saved_galaxy) => saved_galaxy
     };
 
-    let stars = get_stars(500, 140.0);
-    let mut game_data = GameData {state: GameState::MapView, galaxy, hovered: None, focused: None, stars, orbit_angle: None };
-    gameloop(&mut rl, &thread, &mut game_data);
-    return game_data.galaxy
+    let stars = get_stars(config.starfield.stars, config.starfield.radius);
+    let server = config.server.enabled.then(|| Server::start(config.server.port));
+    // A joined session's galaxy lives with the host, not in a file
+    let watcher = (!matches!(session, Some(Session::Player(_)))).then(|| SaveWatcher::new(&save_path));
+    let saved_edits = Some(history.edits());
+    // Left behind if rim crashed the last time this save was open
+    let recovery = match &session {
+        Some(Session::Player(_)) => {None}
+        _ => {file_generator::load_recovery(&save_path)}
+    };
+    let mut game_data = GameData {
+        state: GameState::MapView, galaxy, history, save_path, hovered: None, focused: None, stars, map_orbit: MapOrbit::new(config.camera.map.fov.1), notice: None, input: Input::new(config.bindings.clone()), filter: Filter::default(), heatmap: None, home: None, focused_planet: None, server: None, session, route: Vec::new(), perspective: Perspective::Everyone, party: 0, watcher, saved_edits, map_cache: None, pause: None, quit: false, config
+    };
+    match server {
+        None => {}
+        Some(Ok(server)) => {
+            game_data.notify(format!("Serving the API on http://127.0.0.1:{}", server.port));
+            game_data.server = Some(server);
+        }
+        Some(Err(err)) => {game_data.notify(format!("Could not start the API server: {}", err))}
+    }
+    match &game_data.session {
+        None if game_data.read_only() => {game_data.notify("This is a player copy, so it can be looked at but not changed".to_string())}
+        None => {}
+        Some(Session::Host(host)) => {game_data.notify(format!("Hosting a shared session on port {}", host.port))}
+        Some(Session::Player(player)) => {game_data.notify(format!("Joined the session at {}", player.address))}
+    }
+    if let Some(recovered) = recovery {
+        offer_recovery(rl, thread, &mut game_data, recovered);
+    }
+    run_gameloop(rl, thread, &mut game_data);
+    let save_to = match &game_data.watcher {
+        Some(watcher) if watcher.newer_on_disk() => {confirm_overwrite(rl, thread, &game_data)}
+        _ => {Some(game_data.save_path.clone())}
+    };
+    (game_data.galaxy, game_data.history, save_to)
+}
+
+
+/// Runs the game loop, saving the galaxy to a recovery file if anything panics on the way
+/// so the session isn't lost, before carrying on with the panic.
+fn run_gameloop(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    // A joined session's galaxy lives with the host, so there's nothing to recover
+    let recoverable = !matches!(game_data.session, Some(Session::Player(_)));
+    let recovery_path = file_generator::recovery_path(&game_data.save_path);
+    // Shared so the hook that was there before can be put back afterwards
+    let previous_hook: Arc<dyn Fn(&PanicHookInfo) + Send + Sync> = Arc::from(panic::take_hook());
+    let hook = Arc::clone(&previous_hook);
+    panic::set_hook(Box::new(move |info| {
+        hook(info);
+        // The server and session threads can panic without taking the game down
+        if recoverable && thread::current().name() == Some("main") {
+            eprintln!("Rim crashed, saving the galaxy to {}", recovery_path);
+        }
+    }));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| gameloop(rl, thread, game_data)));
+    panic::set_hook(Box::new(move |info| previous_hook(info)));
+    if let Err(panic) = result {
+        if recoverable {
+            match file_generator::save_recovery(&game_data.save_path, &game_data.galaxy) {
+                Ok(()) => {eprintln!("Saved. Opening {} again will offer to restore it.", game_data.save_path)}
+                Err(err) => {eprintln!("Could not save the galaxy: {}", err)}
+            }
+        }
+        panic::resume_unwind(panic);
+    }
+}
+
+/// Asks whether to carry on from the galaxy saved when rim last crashed, in place of the save.
+fn offer_recovery(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData, recovered: Galaxy) {
+    let lines = [
+        format!("Rim crashed the last time {} was open, and saved the galaxy as it was then.", game_data.save_path),
+        String::new(),
+        "R: Restore it".to_string(),
+        "D: Discard it and open the save".to_string(),
+        "Esc: Open the save, and ask again next time".to_string(),
+    ];
+    let keys = [KeyboardKey::KEY_R, KeyboardKey::KEY_D, KeyboardKey::KEY_ESCAPE];
+    match ask(rl, thread, game_data, "RESTORE AFTER CRASH", &lines, &keys) {
+        Some(KeyboardKey::KEY_R) => {
+            game_data.galaxy = recovered;
+            // Undo was for the save, and nothing on disk matches the restored galaxy yet
+            game_data.history = History::default();
+            game_data.saved_edits = None;
+            file_generator::discard_recovery(&game_data.save_path);
+            game_data.notify("Restored the galaxy from before the crash".to_string());
+        }
+        Some(KeyboardKey::KEY_D) => {file_generator::discard_recovery(&game_data.save_path)}
+        _ => {}
+    }
+    // The key that answered shouldn't also count as Back on the map
+    game_data.input.consume(Action::Back);
+}
+
+/// Fills the window with a question until one of `keys` is pressed, and returns which.
+/// Returns None if the window is closed first, unless it was already closing when asked.
+fn ask(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &GameData, title: &str, lines: &[String], keys: &[KeyboardKey]) -> Option<KeyboardKey> {
+    let closing = rl.window_should_close();
+    while closing || !rl.window_should_close() {
+        if let Some(key) = keys.iter().find(|key| rl.is_key_pressed(**key)) {
+            return Some(*key)
+        }
+        let mut d = rl.begin_drawing(thread);
+        d.clear_background(Color::BLACK);
+        let font_size = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+        let margin = font_size;
+        let text_color = Color::new(200, 200, 200, 255);
+        d.draw_text(title, margin, margin, font_size * 2, text_color);
+        let top = margin + font_size * 4;
+        for (row, line) in lines.iter().enumerate() {
+            d.draw_text(line, margin, top + row as i32 * font_size * 3 / 2, font_size, text_color);
+        }
+    }
+    None
+}
+
+/// Opens the game window, sized and placed for the monitor it's on.
+pub(crate) fn open_window(window: &WindowConfig) -> (RaylibHandle, RaylibThread) {
+    let mut builder = raylib::init();
+    builder.log_level(TraceLogLevel::LOG_NONE)
+        .resizable()
+        .size(window.size.map_or(1280, |size| size.0), window.size.map_or(720, |size| size.1))
+        .title("Rim");
+    if window.vsync {
+        builder.vsync();
+    }
+    let (mut rl, thread) = builder.build();
+    rl.set_exit_key(None);
+    rl.set_target_fps(window.target_fps);
+    rl.set_window_min_size(640, 480);
+
+    // The monitor size is only known once the window exists
+    let monitor = get_current_monitor();
+    let (monitor_width, monitor_height) = (get_monitor_width(monitor), get_monitor_height(monitor));
+    match window.mode {
+        WindowMode::Windowed => {
+            let (mut width, mut height) = (rl.get_screen_width(), rl.get_screen_height());
+            if window.size.is_none() && monitor_width > 0 && monitor_height > 0 {
+                width = width.min(monitor_width * 9 / 10);
+                height = height.min(monitor_height * 9 / 10);
+                rl.set_window_size(width, height);
+            }
+            rl.set_window_position((monitor_width - width).max(0) / 2, (monitor_height - height).max(0) / 2);
+        }
+        WindowMode::Borderless => {
+            rl.toggle_borderless_windowed();
+        }
+        WindowMode::Fullscreen => {
+            if window.size.is_none() {
+                rl.set_window_size(monitor_width, monitor_height);
+            }
+            rl.toggle_fullscreen();
+        }
+    }
+    (rl, thread)
 }
 
+/// Switches the open window to another mode, the way `open_window` sets it up.
+fn set_window_mode(rl: &mut RaylibHandle, window: &mut WindowConfig, mode: WindowMode) {
+    match window.mode {
+        WindowMode::Windowed => {}
+        WindowMode::Borderless => {rl.toggle_borderless_windowed()}
+        WindowMode::Fullscreen => {rl.toggle_fullscreen()}
+    }
+    let monitor = get_current_monitor();
+    let (monitor_width, monitor_height) = (get_monitor_width(monitor), get_monitor_height(monitor));
+    match mode {
+        WindowMode::Windowed => {
+            if window.mode == WindowMode::Fullscreen {
+                let (width, height) = window.size.unwrap_or((monitor_width * 9 / 10, monitor_height * 9 / 10));
+                rl.set_window_size(width, height);
+                rl.set_window_position((monitor_width - width).max(0) / 2, (monitor_height - height).max(0) / 2);
+            }
+        }
+        WindowMode::Borderless => {rl.toggle_borderless_windowed()}
+        WindowMode::Fullscreen => {
+            if window.size.is_none() {
+                rl.set_window_size(monitor_width, monitor_height);
+            }
+            rl.toggle_fullscreen();
+        }
+    }
+    window.mode = mode;
+}
 
 enum GameState {
     MapView,
     StarSystemView,
+    /// A close-up of `GameData::focused_planet` and its moons.
+    PlanetView,
+    Editor,
+    Bindings,
 }
 
 
-fn gameloop(rl: &mut RaylibHandle, thread: &RaylibThread, mut game_data: &mut GameData) {
-    while !rl.window_should_close() {
+fn gameloop(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    while running(rl, game_data) {
         match &game_data.state {
             GameState::MapView => {
-                gameloop_map_view(rl, thread, &mut game_data);
+                gameloop_map_view(rl, thread, game_data);
             }
             GameState::StarSystemView => {
-                gameloop_star_system_view(rl, thread, &mut game_data)
+                gameloop_star_system_view(rl, thread, game_data)
+            }
+            GameState::PlanetView => {
+                gameloop_planet_view(rl, thread, game_data)
+            }
+            GameState::Editor => {
+                gameloop_editor(rl, thread, game_data)
+            }
+            GameState::Bindings => {
+                gameloop_bindings(rl, thread, game_data)
             }
         }
     }
 }
 
+/// Whether rim should keep going, until the window is closed or Quit is picked from the pause menu.
+fn running(rl: &RaylibHandle, game_data: &GameData) -> bool {
+    !rl.window_should_close() && !game_data.quit
+}
+
 fn gameloop_star_system_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
-    game_data.galaxy.systems[game_data.focused.unwrap()].explored = true;
+    let focus = game_data.focused.unwrap();
+    if !game_data.read_only() && !game_data.galaxy.parties()[game_data.party].has_explored(focus) {
+        let edit = Edit::SetExplored { party: game_data.party, id: focus, before: None, after: Some(party::now()) };
+        game_data.history.apply(&mut game_data.galaxy, edit);
+    }
     let mut camera = Camera3D::orthographic(
         Vector3::new(100.0, 30.0, 100.0),
         Vector3::new(0.0, 0.0, 0.0),
@@ -57,72 +275,142 @@ fn gameloop_star_system_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_
         90.0,
     );
     
-    let mut goofy_orbits: Vec<Vec<(Vector3, u8)>>;
+    let mut goofy_orbits: Vec<Vec<(Vec3, u8)>>;
     let mut orbit_angle = 0.0f32;
     let mut pitch_angle = 45.0f32;
-    let orbit_speed = 0.2;        
-    let orbit_radius = 150.0;
-    let pitch_speed = 0.2;
+    let settings = game_data.config.camera.system.clone();
+    let zoom_speed = game_data.config.camera.zoom_speed;
+    let (min_fov, max_fov) = settings.fov;
 
-    let mut selected_planet: Option<usize> = None;
+    // Coming back from a planet close-up keeps that planet selected
+    let mut selected_planet: Option<usize> = game_data.focused_planet.take();
+    let mut selected_moon: Option<usize> = None;
     let mut highlighted_planet: Option<usize>;
+    if selected_planet.is_some() {
+        camera.fovy -= 20.0;
+    }
 
-    while !rl.window_should_close() {
+    while running(rl, game_data) {
         
 
-        highlighted_planet = game_data.galaxy
-            .systems[game_data.focused.unwrap()]
-            .system_data.clone().unwrap().closest_planet_to_mouse(rl, &camera);
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
-            selected_planet = highlighted_planet;
-            if None == selected_planet {
-                camera.fovy += 20.0;
+        game_data.input.update(rl);
+        sync(game_data);
+        update_pause(rl, thread, game_data);
+        // A host can change or hide the system while a player is looking at it
+        if !system_viewable(game_data, focus) {
+            break
+        }
+        let num_planets = game_data.galaxy[focus].system_data.as_ref().unwrap().planets.len();
+        if selected_planet.is_some_and(|i| i >= num_planets) {
+            selected_planet = None;
+            camera.fovy = max_fov;
+        }
+        let num_moons = selected_planet.map_or(0, |i| game_data.galaxy[focus].system_data.as_ref().unwrap().planets[i].moons.len());
+        if selected_moon.is_some_and(|j| j >= num_moons) {
+            selected_moon = None;
+        }
+        let selected = Body::selected(selected_planet, selected_moon);
+        let panel = BodyPanel::new(rl, game_data, system_rows(&game_data.galaxy[focus], selected), selected);
+        highlighted_planet = match panel.hovered {
+            Some(row) => {panel.rows[row].0.planet()}
+            None => {
+                game_data.galaxy[focus]
+                    .system_data.clone().unwrap().closest_planet_to_mouse(rl, &camera)
             }
-            else {
+        };
+        if game_data.input.pressed(rl, Action::Select) {
+            if let Some(row) = panel.hovered {
+                let body = panel.rows[row].0;
+                if selected_planet.is_none() && body.planet().is_some() {
+                    camera.fovy -= 20.0;
+                }
+                if body == Body::Star {
+                    camera.fovy = max_fov;
+                }
+                selected_planet = body.planet();
+                selected_moon = body.moon();
+            }
+            else if !panel.contains(rl.get_mouse_position()) {
+                selected_planet = highlighted_planet;
+                selected_moon = None;
+                if selected_planet.is_none() {
+                    camera.fovy += 20.0;
+                }
+                else {
 
-                camera.fovy -= 20.0;
+                    camera.fovy -= 20.0;
+                }
             }
         }
 
+        // Stepping through the planets and moons, with the same bindings as the editor
+        let planets = &game_data.galaxy[focus].system_data.as_ref().unwrap().planets;
+        let num_planets = planets.len();
+        if num_planets > 0 && game_data.input.pressed(rl, Action::NextPlanet) {
+            if selected_planet.is_none() {
+                camera.fovy -= 20.0;
+            }
+            selected_planet = Some(selected_planet.map_or(0, |i| (i + 1) % num_planets));
+            selected_moon = None;
+        }
+        if num_planets > 0 && game_data.input.pressed(rl, Action::PreviousPlanet) {
+            if selected_planet.is_none() {
+                camera.fovy -= 20.0;
+            }
+            selected_planet = Some(selected_planet.map_or(num_planets - 1, |i| (i + num_planets - 1) % num_planets));
+            selected_moon = None;
+        }
+        let num_moons = selected_planet.map_or(0, |i| planets[i].moons.len());
+        if num_moons > 0 && game_data.input.pressed(rl, Action::NextMoon) {
+            selected_moon = Some(selected_moon.map_or(0, |i| (i + 1) % num_moons));
+        }
+        if num_moons > 0 && game_data.input.pressed(rl, Action::PreviousMoon) {
+            selected_moon = Some(selected_moon.map_or(num_moons - 1, |i| (i + num_moons - 1) % num_moons));
+        }
 
-        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) { 
-            match selected_planet {
-                None => {break;}
-                Some(_) => {selected_planet = None; camera.fovy = 120.0;}
+        if game_data.input.pressed(rl, Action::Back) { 
+            match (selected_planet, selected_moon) {
+                (None, _) => {game_data.input.consume(Action::Back); break;}
+                (Some(_), Some(_)) => {selected_moon = None}
+                (Some(_), None) => {selected_planet = None; camera.fovy = max_fov;}
             }
         }
-        let mut orbit_direction = 0.0;
-        let mut pitch_direction = 0.0;
-        if rl.is_key_down(KeyboardKey::KEY_LEFT) {orbit_direction -= 1.0}
-        if rl.is_key_down(KeyboardKey::KEY_RIGHT) {orbit_direction += 1.0}
-        if rl.is_key_down(KeyboardKey::KEY_UP) {pitch_direction += 1.0}
-        if rl.is_key_down(KeyboardKey::KEY_DOWN) {pitch_direction -= 1.0}
+        let orbit_direction = game_data.input.axis(rl, Action::OrbitRight, Action::OrbitLeft);
+        let pitch_direction = game_data.input.axis(rl, Action::PitchUp, Action::PitchDown);
         let dt = rl.get_frame_time();
-        orbit_angle += orbit_speed * dt * orbit_direction;
-        pitch_angle += pitch_speed * dt * pitch_direction;
-        camera.position.x = orbit_radius * orbit_angle.cos() * pitch_angle.sin();
-        camera.position.z = orbit_radius * orbit_angle.sin() * pitch_angle.sin();
-        camera.position.y = orbit_radius * pitch_angle.cos();
+        orbit_angle += settings.orbit_speed * dt * orbit_direction;
+        pitch_angle += settings.pitch_speed * dt * pitch_direction;
+        camera.position.x = settings.distance * orbit_angle.cos() * pitch_angle.sin();
+        camera.position.z = settings.distance * orbit_angle.sin() * pitch_angle.sin();
+        camera.position.y = settings.distance * pitch_angle.cos();
         camera.target = match selected_planet {
             None => {Vector3::zero()}
             Some(i) => {
-                let planet = &game_data.galaxy.systems[game_data.focused.unwrap()].system_data.clone().unwrap().planets[i];
+                let planet = &game_data.galaxy[focus].system_data.clone().unwrap().planets[i];
                 let planet_pos = point_on_3d_circle(
                     planet.orbit_normal, 
                     planet.orbit_radius as f32, 
-                    planet.orbit_completion as f32 * 2.0 * PI as f32).scale_by(50.0);
+                    planet.orbit_completion as f32 * 2.0 * PI as f32).scale_by(50.0).into();
                 camera.position += planet_pos;
                 planet_pos
 
             }
         };
-        camera.fovy -= 5.0 * rl.get_mouse_wheel_move();
-        if camera.fovy > 120.0 {camera.fovy = 120.0};
-        if camera.fovy < 10.0 {camera.fovy = 10.0};
-        game_data.galaxy.systems[game_data.focused.unwrap()].tick();
+        camera.fovy -= zoom_speed * game_data.input.scroll(rl, Action::ZoomIn, Action::ZoomOut);
+        if camera.fovy > max_fov {camera.fovy = max_fov};
+        if camera.fovy < min_fov {camera.fovy = min_fov};
+        if game_data.input.pressed(rl, Action::ExportSvg) {
+            let system = &game_data.galaxy[focus];
+            let path = file_generator::export_path(&game_data.save_path, &format!("{:X}.svg", system.name));
+            if let Some(svg) = svg::orrery_svg(system) {
+                export_svg(game_data, path, svg);
+            }
+        }
+        game_data.tick_notice(dt);
+        game_data.galaxy[focus].tick();
         goofy_orbits = {
             let mut orbits = Vec::new();
-            for (k, planet) in game_data.galaxy.systems[game_data.focused.unwrap()]
+            for (k, planet) in game_data.galaxy[focus]
                 .system_data.clone().unwrap().planets.iter().enumerate() {
                 let mut points = Vec::new();
                 let mut i: f64 = 0.0;
@@ -155,7 +443,19 @@ fn gameloop_star_system_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_
             orbits
         };
 
-        draw_star_system_view(rl, thread, &camera, &game_data, &goofy_orbits, (selected_planet, highlighted_planet));
+        if let Some(planet) = selected_planet {
+            scan_planet(game_data, focus, planet);
+        }
+        if let (true, Some(planet)) = (game_data.input.pressed(rl, Action::Confirm), selected_planet) {
+            game_data.focused_planet = Some(planet);
+            game_data.state = GameState::PlanetView;
+            return
+        }
+
+        // Laid out again so the panel shows what was picked this frame
+        let selected = Body::selected(selected_planet, selected_moon);
+        let panel = BodyPanel::new(rl, game_data, system_rows(&game_data.galaxy[focus], selected), selected);
+        draw_star_system_view(rl, thread, &camera, game_data, &goofy_orbits, (selected_planet, highlighted_planet), &panel);
          
     }
 
@@ -170,12 +470,16 @@ fn draw_star_system_view(
     thread: &RaylibThread, 
     camera: &Camera3D, 
     game_data: &GameData,
-    orbits: &Vec<Vec<(Vector3, u8)>>,
-    selected_and_highlighted: (Option<usize>, Option<usize>)
+    orbits: &Vec<Vec<(Vec3, u8)>>,
+    selected_and_highlighted: (Option<usize>, Option<usize>),
+    panel: &BodyPanel
     ) {
-    
+    let selected = panel.selected;
 
-    let sys_data = &game_data.galaxy.systems[game_data.focused.unwrap()].system_data.clone().unwrap();
+    let system = &game_data.galaxy[game_data.focused.unwrap()];
+    let sys_data = &system.system_data.clone().unwrap();
+    // Where to put each body's label once the 3D drawing is done
+    let mut labels: Vec<(Vec3, String, Color)> = Vec::new();
     let mut d = rl.begin_drawing(thread);
     
     //It's okay to unwrap these things because the only way to get to star_system_view is by having
@@ -186,8 +490,8 @@ fn draw_star_system_view(
         for star in &game_data.stars {
             d3.draw_point3D(star, Color::WHITE);
         }
-        d3.draw_sphere(Vector3::zero(), sys_data.star_mass.log10() as f32 / 20.0, Color::YELLOW);
-        d3.draw_sphere(Vector3::zero(), sys_data.star_mass.log10() as f32 / 20.0 + 0.5, Color::YELLOW.alpha(0.9));
+        d3.draw_sphere(Vector3::zero(), sys_data.star_display_radius(), Color::YELLOW);
+        d3.draw_sphere(Vector3::zero(), sys_data.star_display_radius() + 0.5, Color::YELLOW.alpha(0.9));
 
         for orbit in orbits {
             let orbit_line_color = Color::new(255, 255, 255, orbit[0].1);
@@ -209,28 +513,14 @@ fn draw_star_system_view(
             //d3.draw_circle_3D(Vector3::zero(), display_radius, planet.orbit_normal, 80.0, );
 
             // Parametric circle position in tilted plane
-            let mut moon_positions: Vec<(Vector3, Color)> = Vec::new(); 
+            let mut moon_positions: Vec<(Vec3, Color)> = Vec::new(); 
             let planet_pos = utils::point_on_3d_circle(planet.orbit_normal, display_radius, angle);
             for moon in &planet.moons {
                 let relative_moon_pos = utils::point_on_3d_circle(moon.orbit_normal, moon.orbital_radius as f32 * 50.0, moon.orbit_completion as f32 * 2.0 * PI as f32);
-                let moon_color: Color = match &moon.moon_type {
-                    MoonType::Asteroid => {Color::GRAY},
-                    MoonType::RoundDusty => {Color::LIGHTSLATEGRAY},
-                    MoonType::SubsurfaceOcean => {Color::SLATEBLUE}
-                };
-                moon_positions.push((planet_pos + relative_moon_pos, moon_color)); 
+                moon_positions.push((planet_pos + relative_moon_pos, moon.moon_type.color())); 
             } 
-            // Planet color
-            let planet_color = match planet.class {
-                PlanetClass::Volcanic => Color::ORANGE,
-                PlanetClass::MetalWorld => Color::GRAY,
-                PlanetClass::Terran => Color::GREEN,
-                PlanetClass::Desert => Color::BEIGE,
-                PlanetClass::OceanWorld => Color::BLUE,
-                PlanetClass::GasGiant => Color::PURPLE,
-                PlanetClass::IceGiant => Color::SKYBLUE,
-            };
-            let planet_radius = planet.mass.powf(1.0 / 8.444) as f32 / 4.0;
+            let planet_color = planet.class.color();
+            let planet_radius = planet.display_radius();
 
             let mut draw_moons = match selected_and_highlighted.1 {
                 None => {false}
@@ -245,82 +535,550 @@ fn draw_star_system_view(
                 }
             };
             if draw_moons {
-                for (pos, color) in moon_positions {
+                for (j, (pos, color)) in moon_positions.into_iter().enumerate() {
                     d3.draw_sphere(pos, 0.1,  color); 
+                    let moon_selected = selected == Body::Moon(i, j);
+                    if moon_selected {
+                        d3.draw_sphere(pos, 0.3, Color::WHITE.alpha(0.3));
+                    }
+                    if selected_and_highlighted.0 == Some(i) {
+                        labels.push((pos, format!("{}", j + 1), if moon_selected { Color::YELLOW } else { Color::new(200, 200, 200, 160) }));
+                    }
                 }
             }
+            let planet_selected = selected.planet() == Some(i);
+            labels.push((planet_pos, system.planet_designation(i), if planet_selected { Color::YELLOW } else { Color::new(200, 200, 200, 220) }));
             let planet_brightness = if draw_moons { 0.7 } else {0.2};
             d3.draw_sphere(planet_pos, planet_radius, planet_color); 
             d3.draw_sphere(planet_pos, planet_radius * 1.2, planet_color.alpha(0.7).brightness(planet_brightness).contrast(planet_brightness)); 
         }
 
     }
+    let small_font = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+    for (position, text, color) in labels {
+        let screen = d.get_world_to_screen(position, *camera);
+        d.draw_text(&text, screen.x as i32 + small_font / 2, screen.y as i32 - small_font, small_font, color);
+    }
+    draw_body_panel(&mut d, panel);
+    let font_size = hud_font(&d, game_data, game_data.config.hud.font_size);
+    draw_text_columns(&mut d, &body_details(system, selected), font_size, game_data.config.hud.max_lines, Color::new(200, 200, 200, 200));
+    if selected.planet().is_some() {
+        let hint = format!("{}: Close-Up", game_data.input.describe(Action::Confirm));
+        d.draw_text(&hint, small_font / 2, d.get_screen_height() - small_font * 3, small_font, Color::new(200, 200, 200, 160));
+    }
+    draw_pause_menu(&mut d, game_data);
+    draw_notice(&mut d, game_data);
+}
+
+/// How much bigger the planet view draws things than the star system view does.
+const CLOSE_UP_SCALE: f32 = 20.0;
+
+/// Where a moon is relative to its planet in the planet view.
+fn close_up_moon_position(moon: &Moon) -> Vec3 {
+    point_on_3d_circle(moon.orbit_normal, moon.orbital_radius as f32 * 50.0 * CLOSE_UP_SCALE, moon.orbit_completion as f32 * 2.0 * PI as f32)
+}
+
+fn gameloop_planet_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    let focus = game_data.focused.unwrap();
+    let planet_index = game_data.focused_planet.unwrap();
+    let settings = game_data.config.camera.system.clone();
+    let zoom_speed = game_data.config.camera.zoom_speed;
+    let (min_fov, max_fov) = settings.fov;
+    let mut camera = Camera3D::orthographic(Vector3::zero(), Vector3::zero(), Vector3::new(0.0, 1.0, 0.0), max_fov);
+    let mut orbit_angle = 0.0f32;
+    let mut pitch_angle = 1.2f32;
+    let mut selected_moon: Option<usize> = None;
+
+    while running(rl, game_data) {
+        game_data.input.update(rl);
+        sync(game_data);
+        update_pause(rl, thread, game_data);
+        if !system_viewable(game_data, focus) || planet_index >= game_data.galaxy[focus].system_data.as_ref().unwrap().planets.len() {
+            break
+        }
+        let num_moons = game_data.galaxy[focus].system_data.as_ref().unwrap().planets[planet_index].moons.len();
+        if selected_moon.is_some_and(|j| j >= num_moons) {
+            selected_moon = None;
+        }
+        let dt = rl.get_frame_time();
+        game_data.tick_notice(dt);
+        game_data.galaxy[focus].tick();
+
+        let orbit_direction = game_data.input.axis(rl, Action::OrbitRight, Action::OrbitLeft);
+        let pitch_direction = game_data.input.axis(rl, Action::PitchUp, Action::PitchDown);
+        orbit_angle += settings.orbit_speed * dt * orbit_direction;
+        pitch_angle = (pitch_angle + settings.pitch_speed * dt * pitch_direction).clamp(0.1, std::f32::consts::PI - 0.1);
+        camera.position.x = settings.distance * orbit_angle.cos() * pitch_angle.sin();
+        camera.position.z = settings.distance * orbit_angle.sin() * pitch_angle.sin();
+        camera.position.y = settings.distance * pitch_angle.cos();
+        camera.fovy = (camera.fovy - zoom_speed * game_data.input.scroll(rl, Action::ZoomIn, Action::ZoomOut)).clamp(min_fov, max_fov);
+
+        let system = &game_data.galaxy[focus];
+        let planet = &system.system_data.as_ref().unwrap().planets[planet_index];
+        let selected = Body::selected(Some(planet_index), selected_moon);
+        let panel = BodyPanel::new(rl, game_data, planet_rows(system, planet_index), selected);
+        let hovered_moon = match panel.hovered {
+            Some(row) => {panel.rows[row].0.moon()}
+            None => {
+                let mut picker = Picker::new(View::of_window(rl, &camera), rl.get_mouse_position());
+                for (j, moon) in planet.moons.iter().enumerate() {
+                    picker.sphere(j, close_up_moon_position(moon).into(), moon.display_radius() * CLOSE_UP_SCALE * 1.4);
+                }
+                picker.nearest()
+            }
+        };
+        if game_data.input.pressed(rl, Action::Select) && (panel.hovered.is_some() || !panel.contains(rl.get_mouse_position())) {
+            selected_moon = hovered_moon;
+        }
+        let num_moons = planet.moons.len();
+        if num_moons > 0 && game_data.input.pressed(rl, Action::NextMoon) {
+            selected_moon = Some(selected_moon.map_or(0, |i| (i + 1) % num_moons));
+        }
+        if num_moons > 0 && game_data.input.pressed(rl, Action::PreviousMoon) {
+            selected_moon = Some(selected_moon.map_or(num_moons - 1, |i| (i + num_moons - 1) % num_moons));
+        }
+        if game_data.input.pressed(rl, Action::Back) {
+            match selected_moon {
+                None => {
+                    game_data.input.consume(Action::Back);
+                    break
+                }
+                Some(_) => {selected_moon = None}
+            }
+        }
+
+        let selected = Body::selected(Some(planet_index), selected_moon);
+        let panel = BodyPanel::new(rl, game_data, planet_rows(&game_data.galaxy[focus], planet_index), selected);
+        draw_planet_view(rl, thread, &camera, game_data, &panel, hovered_moon);
+    }
+
+    // Back to the system with this planet still selected
+    game_data.focused_planet = Some(planet_index);
+    game_data.state = GameState::StarSystemView;
+}
+
+fn draw_planet_view(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    camera: &Camera3D,
+    game_data: &GameData,
+    panel: &BodyPanel,
+    hovered_moon: Option<usize>
+    ) {
+    let system = &game_data.galaxy[game_data.focused.unwrap()];
+    let planet_index = game_data.focused_planet.unwrap();
+    let planet = &system.system_data.as_ref().unwrap().planets[planet_index];
+    let planet_radius = planet.display_radius() * CLOSE_UP_SCALE;
+    let mut labels: Vec<(Vec3, String, Color)> = Vec::new();
+
+    let mut d = rl.begin_drawing(thread);
+    d.clear_background(Color::BLACK);
+    {
+        let mut d3 = d.begin_mode3D(camera);
+        for star in &game_data.stars {
+            d3.draw_point3D(star, Color::WHITE);
+        }
+        d3.draw_sphere(Vector3::zero(), planet_radius, planet.class.color());
+        d3.draw_sphere(Vector3::zero(), planet_radius * 1.05, planet.class.color().alpha(0.3));
+
+        // Rings are drawn as a band of circles around the equator
+        if let Some(rings) = &planet.rings {
+            let bands = 24;
+            for band in 0..=bands {
+                let radius = planet_radius * (rings.inner + (rings.outer - rings.inner) * band as f64 / bands as f64) as f32;
+                let alpha = 0.15 + 0.25 * ((band as f32 * 1.7).sin() * 0.5 + 0.5);
+                draw_circle_3d(&mut d3, Vec3::zero(), planet.orbit_normal, radius, Color::BEIGE.alpha(alpha));
+            }
+        }
+
+        for (j, moon) in planet.moons.iter().enumerate() {
+            let position = close_up_moon_position(moon);
+            let orbit_radius = moon.orbital_radius as f32 * 50.0 * CLOSE_UP_SCALE;
+            let selected = panel.selected == Body::Moon(planet_index, j);
+            let orbit_alpha = if selected || hovered_moon == Some(j) { 0.5 } else { 0.15 };
+            draw_circle_3d(&mut d3, Vec3::zero(), moon.orbit_normal, orbit_radius, Color::WHITE.alpha(orbit_alpha));
+            let radius = moon.display_radius() * CLOSE_UP_SCALE;
+            d3.draw_sphere(position, radius, moon.moon_type.color());
+            if selected || hovered_moon == Some(j) {
+                d3.draw_sphere(position, radius * 1.4, if selected { Color::YELLOW.alpha(0.3) } else { Color::POWDERBLUE.alpha(0.3) });
+            }
+            labels.push((position, system.moon_designation(planet_index, j), if selected { Color::YELLOW } else { Color::new(200, 200, 200, 200) }));
+        }
+    }
+
+    let small_font = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+    for (position, text, color) in labels {
+        let screen = d.get_world_to_screen(position, *camera);
+        d.draw_text(&text, screen.x as i32 + small_font / 2, screen.y as i32 - small_font, small_font, color);
+    }
+    draw_body_panel(&mut d, panel);
+    let font_size = hud_font(&d, game_data, game_data.config.hud.font_size);
+    draw_text_columns(&mut d, &body_details(system, panel.selected), font_size, game_data.config.hud.max_lines, Color::new(200, 200, 200, 200));
+    draw_pause_menu(&mut d, game_data);
+    draw_notice(&mut d, game_data);
+}
+
+/// A circle in 3D, as a loop of line segments.
+fn draw_circle_3d(d3: &mut impl RaylibDraw3D, center: Vec3, normal: Vec3, radius: f32, color: Color) {
+    let segments = 64;
+    let point = |k: i32| center + point_on_3d_circle(normal, radius, k as f32 / segments as f32 * std::f32::consts::TAU);
+    for k in 0..segments {
+        d3.draw_line_3D(point(k), point(k + 1), color);
+    }
+}
+
+/// Something in a star system that can be selected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Body {
+    Star,
+    Planet(usize),
+    /// A planet's index and the moon's index around it.
+    Moon(usize, usize),
+}
+
+impl Body {
+    fn selected(planet: Option<usize>, moon: Option<usize>) -> Body {
+        match (planet, moon) {
+            (None, _) => Body::Star,
+            (Some(planet), None) => Body::Planet(planet),
+            (Some(planet), Some(moon)) => Body::Moon(planet, moon),
+        }
+    }
+
+    fn planet(&self) -> Option<usize> {
+        match self {
+            Body::Star => None,
+            Body::Planet(planet) | Body::Moon(planet, _) => Some(*planet),
+        }
+    }
+
+    fn moon(&self) -> Option<usize> {
+        match self {
+            Body::Moon(_, moon) => Some(*moon),
+            _ => None,
+        }
+    }
+}
+
+/// The list of bodies down the right of the star system view. The selected planet's moons
+/// are listed under it. Laid out each frame so clicks and drawing agree on where rows are.
+struct BodyPanel {
+    rows: Vec<(Body, String)>,
+    selected: Body,
+    rect: Rectangle,
+    font_size: i32,
+    line_height: i32,
+    /// The row under the mouse.
+    hovered: Option<usize>,
+}
+
+impl BodyPanel {
+    fn new(rl: &RaylibHandle, game_data: &GameData, rows: Vec<(Body, String)>, selected: Body) -> BodyPanel {
+        let font_size = hud_font(rl, game_data, game_data.config.hud.small_font_size);
+        let padding = font_size / 2;
+        let line_height = font_size + font_size / 3;
+        let width = rows.iter().map(|(_, text)| rl.measure_text(text, font_size)).max().unwrap_or(0) + padding * 2;
+        let height = rows.len() as i32 * line_height + padding * 2;
+        let rect = Rectangle::new(
+            (rl.get_screen_width() - width - padding) as f32, padding as f32,
+            width as f32, height as f32);
+        let mouse = rl.get_mouse_position();
+        let hovered = if rect.check_collision_point_rec(mouse) {
+            let row = (mouse.y - rect.y) as i32 - padding;
+            (row >= 0).then_some((row / line_height) as usize).filter(|row| *row < rows.len())
+        }
+        else {
+            None
+        };
+        BodyPanel { rows, selected, rect, font_size, line_height, hovered }
+    }
+
+    fn contains(&self, point: Vector2) -> bool {
+        self.rect.check_collision_point_rec(point)
+    }
+}
+
+/// The star and planets, with the selected planet's moons under it.
+fn system_rows(system: &map::StarSystem, selected: Body) -> Vec<(Body, String)> {
+    let data = system.system_data.as_ref().unwrap();
+    let mut rows = vec![(Body::Star, format!("Star {:X}", system.name))];
+    for (i, planet) in data.planets.iter().enumerate() {
+        rows.push((Body::Planet(i), format!("{} {:?}", system.planet_designation(i), planet.class)));
+        if selected.planet() == Some(i) {
+            rows.extend(moon_rows(system, i));
+        }
+    }
+    rows
+}
+
+/// The planet of the planet view, with its moons under it.
+fn planet_rows(system: &map::StarSystem, planet: usize) -> Vec<(Body, String)> {
+    let class = system.system_data.as_ref().unwrap().planets[planet].class;
+    let mut rows = vec![(Body::Planet(planet), format!("{} {:?}", system.planet_designation(planet), class))];
+    rows.extend(moon_rows(system, planet));
+    rows
+}
+
+fn moon_rows(system: &map::StarSystem, planet: usize) -> Vec<(Body, String)> {
+    let moons = &system.system_data.as_ref().unwrap().planets[planet].moons;
+    moons.iter().enumerate()
+        .map(|(j, moon)| (Body::Moon(planet, j), format!("   {} {:?}", system.moon_designation(planet, j), moon.moon_type)))
+        .collect()
+}
+
+fn draw_body_panel(d: &mut RaylibDrawHandle, panel: &BodyPanel) {
+    let padding = panel.font_size / 2;
+    let (x, y) = (panel.rect.x as i32, panel.rect.y as i32);
+    d.draw_rectangle(x, y, panel.rect.width as i32, panel.rect.height as i32, Color::BLACK.alpha(0.6));
+    for (row, (body, text)) in panel.rows.iter().enumerate() {
+        let row_y = y + padding + row as i32 * panel.line_height;
+        if *body == panel.selected {
+            d.draw_rectangle(x, row_y - panel.font_size / 6, panel.rect.width as i32, panel.line_height, Color::POWDERBLUE.alpha(0.25));
+        }
+        else if panel.hovered == Some(row) {
+            d.draw_rectangle(x, row_y - panel.font_size / 6, panel.rect.width as i32, panel.line_height, Color::WHITE.alpha(0.1));
+        }
+        let color = match body {
+            Body::Star => {Color::YELLOW}
+            Body::Planet(_) | Body::Moon(..) => {Color::new(200, 200, 200, 230)}
+        };
+        d.draw_text(text, x + padding, row_y, panel.font_size, color);
+    }
+}
+
+/// Everything known about the selected body, for the star system view.
+fn body_details(system: &map::StarSystem, body: Body) -> String {
+    let data = system.system_data.as_ref().unwrap();
+    let mut string = String::new();
+    let notes = match body {
+        Body::Star => {
+            string += format!("Star of System {:X}\n", system.name).as_str();
+            string += format!("Mass: {:.2} solar masses\n", data.star_solar_masses()).as_str();
+            string += format!("Planets: {}\n", data.planets.len()).as_str();
+            string += format!("Habitability: {:.2}\n", data.habitability()).as_str();
+            if !system.tags.is_empty() {
+                string += format!("Tags: {}\n", system.tags.join(", ")).as_str();
+            }
+            &system.notes
+        }
+        Body::Planet(i) => {
+            let planet = &data.planets[i];
+            string += format!("Planet {}\n", system.planet_designation(i)).as_str();
+            string += format!("Class: {:?}\n", planet.class).as_str();
+            string += format!("Mass: {:.2} Earth masses\n", planet.mass).as_str();
+            string += format!("Orbital radius: {:.3} std.\n", planet.orbit_radius).as_str();
+            string += format!("Orbit progress: {:.0}%\n", planet.orbit_completion * 100.0).as_str();
+            string += format!("Moons: {}\n", planet.moons.len()).as_str();
+            if let Some(rings) = &planet.rings {
+                string += format!("Rings: {:.1} to {:.1} planet radii\n", rings.inner, rings.outer).as_str();
+            }
+            string += format!("Habitability: {:.2}\n", planet.class.habitability()).as_str();
+            &planet.notes
+        }
+        Body::Moon(i, j) => {
+            let moon = &data.planets[i].moons[j];
+            string += format!("Moon {}\n", system.moon_designation(i, j)).as_str();
+            string += format!("Type: {:?}\n", moon.moon_type).as_str();
+            string += format!("Mass: {:.5} Earth masses\n", moon.mass).as_str();
+            string += format!("Orbital radius: {:.4} std.\n", moon.orbital_radius).as_str();
+            string += format!("Orbit progress: {:.0}%\n", moon.orbit_completion * 100.0).as_str();
+            &moon.notes
+        }
+    };
+    if !notes.is_empty() {
+        string += format!("Notes: {}\n", notes).as_str();
+    }
+    string
 }
 
 
 fn gameloop_map_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    let mut orbit = game_data.map_orbit.clone();
     let mut camera = Camera3D::orthographic(
-        Vector3::new(0.0, 0.0, 150.0),
-        Vector3::new(0.0, 0.0, 0.0),
+        orbit.pan + orbit.direction().scale_by(game_data.config.camera.map.distance),
+        orbit.pan,
         Vector3::new(0.0, 1.0, 0.0),
-        120.0,
+        orbit.fov,
     );
-    let mut orbit_angle = match &game_data.orbit_angle {
-        None => {0f32},
-        Some(angle) => {*angle}
-    };
 
-    let orbit_speed = 0.1;        
-    let orbit_radius = 150.0;
+    let settings = game_data.config.camera.map.clone();
+    let zoom_speed = game_data.config.camera.zoom_speed;
+    let (min_fov, max_fov) = settings.fov;
     let mut selecting = false;
     let mut fully_zoomed_frames = 0;
-    while !rl.window_should_close() {
-        if game_data.focused == None {
-            camera.fovy -= 5.0 * rl.get_mouse_wheel_move();
-            if camera.fovy > 120.0 {camera.fovy = 120.0};
-            if camera.fovy < 50.0 {camera.fovy = 50.0};
+    let mut overlay: Option<MapOverlay> = None;
+    while running(rl, game_data) {
+        game_data.input.update(rl);
+        sync(game_data);
+        update_pause(rl, thread, game_data);
+        let close_overlay = match &mut overlay {
+            None => {false}
+            Some(MapOverlay::Search(search_box)) => {update_search_box(rl, game_data, search_box)}
+            Some(MapOverlay::Filters(panel)) => {update_filter_panel(rl, game_data, panel)}
+            Some(MapOverlay::NameParty(field)) => {update_party_field(rl, game_data, field)}
+        };
+        if close_overlay {
+            overlay = None;
+            game_data.input.set_suspended(false);
+            // The Enter or Escape that closed it shouldn't also enter or leave a system
+            game_data.input.consume(Action::Confirm);
+            game_data.input.consume(Action::Back);
+        }
+        let input = &game_data.input;
+        if game_data.focused.is_none() {
+            orbit.fov -= zoom_speed * input.scroll(rl, Action::ZoomIn, Action::ZoomOut);
+            orbit.fov = orbit.fov.clamp(min_fov, max_fov);
         }
         
         let dt = rl.get_frame_time();
-        orbit_angle += orbit_speed * dt;
 
-        camera.position.x = orbit_radius * orbit_angle.cos();
-        camera.position.z = orbit_radius * orbit_angle.sin();
-        match &game_data.focused {
-            None => { camera.target = Vector3::zero(); },
-            Some(focus) => {camera.target = game_data.galaxy.systems[*focus].position;}
+        // Turning and panning. Mouse drags are scaled so dragging across the window
+        // turns half a revolution, and pans keep the galaxy under the mouse.
+        let forward = orbit.direction().scale_by(-1.0);
+        let right = forward.cross(Vector3::new(0.0, 1.0, 0.0)).normalized();
+        let up = right.cross(forward);
+        let mouse = rl.get_mouse_delta();
+        let screen_height = rl.get_screen_height().max(1) as f32;
+        if orbit.auto_rotate {
+            orbit.yaw += settings.orbit_speed * dt;
+        }
+        orbit.yaw += settings.turn_speed * dt * input.axis(rl, Action::OrbitRight, Action::OrbitLeft);
+        orbit.pitch += settings.turn_speed * dt * input.axis(rl, Action::PitchUp, Action::PitchDown);
+        if input.strength(rl, Action::Pan) > 0.0 {
+            let units_per_pixel = camera.fovy / screen_height;
+            orbit.pan += (up.scale_by(mouse.y) - right.scale_by(mouse.x)).scale_by(units_per_pixel);
+        }
+        else if input.strength(rl, Action::Rotate) > 0.0 {
+            orbit.yaw -= mouse.x / screen_height * std::f32::consts::PI;
+            orbit.pitch += mouse.y / screen_height * std::f32::consts::PI;
+        }
+        let pan_direction = right.scale_by(input.axis(rl, Action::PanRight, Action::PanLeft))
+            + up.scale_by(input.axis(rl, Action::PanUp, Action::PanDown));
+        orbit.pan += pan_direction.scale_by(camera.fovy * 0.5 * dt);
+        orbit.yaw = orbit.yaw.rem_euclid(std::f32::consts::TAU);
+        orbit.pitch = orbit.pitch.clamp(-settings.max_pitch, settings.max_pitch);
+        if input.pressed(rl, Action::ResetCamera) {
+            orbit = MapOrbit { auto_rotate: orbit.auto_rotate, ..MapOrbit::new(max_fov) };
+        }
+        if input.pressed(rl, Action::ToggleAutoRotate) {
+            orbit.auto_rotate = !orbit.auto_rotate;
+            game_data.notify(format!("Auto-rotate {}", if orbit.auto_rotate { "on" } else { "off" }));
+        }
+        game_data.tick_notice(dt);
+
+        // The camera eases towards where it should be rather than jumping there
+        let (target, fov) = match &game_data.focused {
+            None => {(orbit.pan, orbit.fov)}
+            Some(focus) => {(game_data.galaxy[*focus].position.into(), min_fov)}
+        };
+        let blend = 1.0 - (-settings.smoothing * dt).exp();
+        camera.target = camera.target.lerp(target, blend);
+        if !selecting {
+            camera.fovy += (fov - camera.fovy) * blend;
         }
+        camera.position = camera.target + orbit.direction().scale_by(settings.distance);
         game_data.galaxy.wiggle(dt);
 
-        game_data.hovered = game_data.galaxy.closest_system_to_mouse(rl, &camera);
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+        game_data.hovered = game_data.galaxy.closest_system_to_mouse(rl, &camera, game_data.perspective);
+        if game_data.input.pressed(rl, Action::Select) {
             match game_data.hovered {
                 None => {},
-                Some(i) => {
-                    match &game_data.galaxy.systems[i].system_data {
-                        Some(_) => {}
-                        None => {game_data.galaxy.systems[i].system_data = Some(StarSystemData::new())}
-                    }
-                    game_data.focused = Some(i);
-                    camera.fovy = 50.0; 
-                }
+                Some(i) => {focus_system(game_data, i)}
             }
         }
-        if rl.is_key_released(KeyboardKey::KEY_ESCAPE) {
+        if game_data.input.pressed(rl, Action::Back) {
             match game_data.focused {
-                None => {
-                },
+                None => {open_pause(game_data)},
                 Some(_) => {
                     game_data.focused = None; 
-                    camera.fovy = 120.0; 
                     selecting = false;
                     fully_zoomed_frames = 0;
                 }
             }
         }
-        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+        if !selecting && handle_undo_redo(rl, game_data) {
+            game_data.hovered = None;
+            if game_data.focused.is_some_and(|focus| !game_data.galaxy.contains(focus)) {
+                game_data.focused = None;
+            }
+        }
+        if game_data.input.pressed(rl, Action::ExportGraph) && !selecting {
+            export_graphs(game_data);
+        }
+        if game_data.input.pressed(rl, Action::ExportSvg) && !selecting {
+            let svg = svg::galaxy_svg(&game_data.galaxy, Projection::from_camera(&camera), true);
+            let path = file_generator::export_path(&game_data.save_path, "svg");
+            export_svg(game_data, path, svg);
+        }
+        if game_data.input.pressed(rl, Action::ExportPlayerCopy) && !selecting {
+            export_player_copy(game_data);
+        }
+        if game_data.input.pressed(rl, Action::ReloadSave) && !selecting && game_data.watcher.is_some() {
+            let modified = file_generator::modified(&game_data.save_path);
+            let changed = reload_save(game_data, modified);
+            if let Some(server) = &mut game_data.server {
+                server.changed(&changed);
+            }
+            sync_session(game_data, true);
+        }
+        if game_data.input.pressed(rl, Action::OpenEditor) && !selecting {
+            if game_data.read_only() {
+                game_data.notify("Only the host can edit a shared galaxy".to_string());
+            }
+            else {
+                game_data.state = GameState::Editor;
+                break
+            }
+        }
+        if game_data.input.pressed(rl, Action::OpenBindings) && !selecting {
+            game_data.state = GameState::Bindings;
+            break
+        }
+        if game_data.input.pressed(rl, Action::Search) && !selecting {
+            overlay = Some(MapOverlay::Search(SearchBox { field: TextField::new("Search", String::new()), results: Vec::new(), chosen: 0 }));
+            game_data.input.set_suspended(true);
+        }
+        if game_data.input.pressed(rl, Action::CycleHeatmap) {
+            game_data.heatmap = Metric::cycle(game_data.heatmap);
+            match game_data.heatmap {
+                None => {game_data.notify("Showing normal colours".to_string())}
+                Some(metric) => {game_data.notify(format!("Colouring systems by {}", metric.label()))}
+            }
+        }
+        if game_data.input.pressed(rl, Action::SetHome) {
+            match game_data.hovered.or(game_data.focused) {
+                None => {game_data.notify("Hover over or select a system to make it home".to_string())}
+                Some(id) => {
+                    game_data.home = Some(id);
+                    game_data.notify(format!("System {:X} is now home", game_data.galaxy[id].name));
+                }
+            }
+        }
+        if game_data.input.pressed(rl, Action::PlotRoute) {
+            plot_route(game_data);
+        }
+        if game_data.input.pressed(rl, Action::CyclePerspective) {
+            cycle_perspective(game_data);
+        }
+        if game_data.input.pressed(rl, Action::AddParty) && !selecting {
+            if game_data.read_only() {
+                game_data.notify("Only the host can add parties".to_string());
+            }
+            else {
+                overlay = Some(MapOverlay::NameParty(TextField::new("Party Name", String::new())));
+                game_data.input.set_suspended(true);
+            }
+        }
+        if game_data.input.pressed(rl, Action::OpenFilters) && !selecting {
+            overlay = Some(MapOverlay::Filters(FilterPanel { selected: 0, naming: None }));
+            game_data.input.set_suspended(true);
+        }
+        if game_data.input.pressed(rl, Action::Confirm) {
             match game_data.focused {
                 None => {},
+                Some(focus) if !system_viewable(game_data, focus) => {
+                    game_data.notify(format!("System {:X} hasn't been explored yet", game_data.galaxy[focus].name));
+                }
                 Some(_) => {
                     selecting = true;
                 }
@@ -339,119 +1097,1886 @@ fn gameloop_map_view(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &m
                 }
             }
         }
-        draw_map_view(rl, thread, &camera, &game_data, true);
+        refresh_map_cache(game_data);
+        draw_map_view(rl, thread, &camera, game_data, true, overlay.as_ref());
     }
+    game_data.input.set_suspended(false);
+    game_data.map_orbit = orbit;
 }
 
+/// Focuses the map on a system, scanning it first if it hasn't been.
+/// Players in a shared session can only look at what the host has scanned.
+fn focus_system(game_data: &mut GameData, id: SystemId) {
+    if game_data.galaxy[id].system_data.is_none() && !game_data.read_only() {
+        let edit = Edit::SetSystemData { id, before: None, after: Some(StarSystemData::new()), scans: Vec::new() };
+        game_data.history.apply(&mut game_data.galaxy, edit);
+    }
+    game_data.focused = Some(id);
+}
 
+/// Records the party scanning a planet the first time they look at it.
+fn scan_planet(game_data: &mut GameData, id: SystemId, planet: usize) {
+    let party = game_data.party;
+    if game_data.read_only() || game_data.galaxy.parties()[party].has_scanned(id, planet) {
+        return
+    }
+    let edit = Edit::SetScanned { party, id, planet, before: None, after: Some(party::now()) };
+    game_data.history.apply(&mut game_data.galaxy, edit);
+}
 
-
-
-fn draw_map_view(rl: &mut RaylibHandle, thread: &RaylibThread, camera: &Camera3D, game_data: & GameData, hud_text: bool) {
-
-    let mut d = rl.begin_drawing(thread);
-    d.clear_background(Color::BLACK);
-    {
-        let mut d3 = d.begin_mode3D(camera);
-        for star in &game_data.stars {
-            d3.draw_point3D(star, Color::WHITE);
+/// Steps the map from everyone's knowledge through each party's in turn. Systems
+/// entered and planets looked at count for the party last shown.
+fn cycle_perspective(game_data: &mut GameData) {
+    let parties = game_data.galaxy.parties().len();
+    game_data.perspective = match game_data.perspective {
+        Perspective::Everyone => {Perspective::Party(0)}
+        Perspective::Party(i) if i + 1 < parties => {Perspective::Party(i + 1)}
+        Perspective::Party(_) => {Perspective::Everyone}
+    };
+    match game_data.perspective {
+        Perspective::Everyone => {game_data.notify("Showing what every party knows".to_string())}
+        Perspective::Party(i) => {
+            game_data.party = i;
+            game_data.notify(format!("Showing what {} know", game_data.galaxy.parties()[i].name));
         }
-        let mut skipped_systems: Vec<usize> = Vec::new();
-        for (i, system) in game_data.galaxy.systems.iter().enumerate() {
-            let (size, mut color) = match game_data.hovered {
-                Some(val) => {
-                    if val == i { (0.9, Color::POWDERBLUE) }
-                    else { (0.5, Color::new(55, 55, 55, 255)) }
-                },
-                None => { 
-                    if let None = system.system_data {
-                        (0.5, Color::new(90, 90, 90, 255)) 
-                    }
-                    else {
-                        if system.explored == false {
-                            (0.5, Color::new(60, 60, 80, 255))
-                        }
-                        else {
-                            (1.0, Color::new(130, 110, 150, 255))
-                        }
-                    
-                    }
-                }
-            };
-            let mut highlight_all_connections = false;
-            let mut connection_color = Color::new(255, 255, 255, 40);
-            match game_data.focused {
-                None => {}
-                Some(focus) => {
-                    if i == focus {
-                        color = Color::YELLOW; 
-                        highlight_all_connections = true; 
-                        connection_color = Color::new(255, 255, 0, 100);
-                        skipped_systems.push(i);
-                    }
-                }
-            }
-            match game_data.hovered {
-                None => {}
-                Some(hovered) => {
-                    if i == hovered {
-                        highlight_all_connections = true; 
-                        connection_color = Color::new(150, 150, 255, 100);
-                        skipped_systems.push(i);
-                    }
-                }
+    }
+}
+
+/// Returns true once the party name field should close.
+fn update_party_field(rl: &mut RaylibHandle, game_data: &mut GameData, field: &mut TextField) -> bool {
+    match field.update(rl) {
+        None | Some(TextFieldEvent::Changed) => {false}
+        Some(TextFieldEvent::Cancel) => {true}
+        Some(TextFieldEvent::Submit) => {
+            let name = field.text.trim().to_string();
+            if name.is_empty() {
+                return true
             }
-            d3.draw_sphere(system.position, size, color.alpha(camera.fovy / 50.0));
-            d3.draw_sphere(system.position, size * 1.2, color.alpha(0.5));
-            // Draw connections
-            for &conn_idx in &system.connections {
-                if (conn_idx > i || highlight_all_connections) && !skipped_systems.contains(&conn_idx) {
-                    let conn = &game_data.galaxy.systems[conn_idx];
-                    d3.draw_line_3D(
-                        system.position,
-                        conn.position,
-                        connection_color
-                    );
-                }
+            if game_data.galaxy.parties().iter().any(|party| party.name == name) {
+                game_data.notify(format!("There's already a party called {}", name));
+                return false
             }
+            let index = game_data.galaxy.parties().len();
+            game_data.history.apply(&mut game_data.galaxy, Edit::AddParty { index, party: Party::new(&name) });
+            game_data.party = index;
+            game_data.perspective = Perspective::Party(index);
+            game_data.notify(format!("Added {}, showing what they know", name));
+            true
         }
-
     }
+}
 
-    if !hud_text {return}
-    match game_data.hovered {
+/// The text shown for a hovered or focused system: what's known about it, and which
+/// parties have been there from the map's perspective.
+fn hover_text(game_data: &GameData, id: SystemId) -> String {
+    let system = &game_data.galaxy[id];
+    let parties = game_data.galaxy.parties();
+    match game_data.perspective {
+        Perspective::Party(i) if !parties[i].has_explored(id) => {
+            format!("System {:X}:\n{} haven't explored this system.", system.name, parties[i].name)
+        }
+        Perspective::Party(i) => {
+            let party = &parties[i];
+            let mut text = system.get_hover_string();
+            text += &format!("---\nExplored by {} on {}.\n", party.name, party::format_date(party.explored[&id]));
+            let planets = system.system_data.as_ref().map_or(0, |data| data.planets.len());
+            let unscanned: Vec<String> = (0..planets).filter(|planet| !party.has_scanned(id, *planet))
+                .map(|planet| system.planet_designation(planet))
+                .collect();
+            if !unscanned.is_empty() {
+                text += &format!("Not scanned: {}\n", unscanned.join(", "));
+            }
+            text
+        }
+        Perspective::Everyone => {
+            let mut text = system.get_hover_string();
+            let explorers: Vec<String> = parties.iter()
+                .filter_map(|party| party.explored.get(&id).map(|at| format!("{} ({})", party.name, party::format_date(*at))))
+                .collect();
+            if !explorers.is_empty() {
+                text += &format!("---\nExplored by {}\n", explorers.join(", "));
+            }
+            text
+        }
+    }
+}
+
+/// Whether a system can be opened, which needs it scanned.
+fn system_viewable(game_data: &GameData, id: SystemId) -> bool {
+    game_data.galaxy.get(id).is_some_and(|system| system.system_data.is_some())
+}
+
+/// Plots the route with the fewest lanes from home to the hovered or focused system,
+/// or clears it when neither is.
+fn plot_route(game_data: &mut GameData) {
+    let (home, end) = match (game_data.home, game_data.hovered.or(game_data.focused)) {
+        (_, None) => {
+            game_data.route.clear();
+            game_data.notify("Route cleared".to_string());
+            return
+        }
+        (None, Some(_)) => {
+            game_data.notify(format!("Set a home system with {} to plot a route from", game_data.input.describe(Action::SetHome)));
+            return
+        }
+        (Some(home), Some(end)) => {(home, end)}
+    };
+    let name = game_data.galaxy[end].name;
+    match game_data.galaxy.shortest_path(home, end) {
+        None => {game_data.notify(format!("No lanes lead from home to System {:X}", name))}
+        Some(route) => {
+            game_data.notify(format!("Route to System {:X}: {} jumps", name, route.len() - 1));
+            game_data.route = route;
+        }
+    }
+}
+
+/// Keeps the JSON API and the shared session in step with the galaxy. Runs every frame
+/// whichever view is open.
+fn sync(game_data: &mut GameData) {
+    let requests = game_data.server.as_ref().map(Server::requests).unwrap_or_default();
+    for request in requests {
+        let response = answer_api(game_data, &request.command);
+        request.respond(response);
+    }
+    let mut changed = game_data.history.take_changed();
+    changed.extend(watch_save(game_data));
+    if !changed.is_empty() {
+        game_data.map_cache = None;
+    }
+    if let Some(server) = &mut game_data.server {
+        server.changed(&changed);
+        server.focus_changed(game_data.focused);
+    }
+    sync_session(game_data, !changed.is_empty());
+    // Undoing a new party, or a host's update, can take the chosen party away
+    let parties = game_data.galaxy.parties().len();
+    if game_data.party >= parties || matches!(game_data.perspective, Perspective::Party(i) if i >= parties) {
+        game_data.party = 0;
+        game_data.perspective = Perspective::Everyone;
+    }
+}
+
+/// Sends players what changed, or takes in what the host sent.
+fn sync_session(game_data: &mut GameData, changed: bool) {
+    match &mut game_data.session {
+        None => {}
+        Some(Session::Host(host)) => {
+            let joined = host.update(&game_data.galaxy, changed, game_data.focused, &game_data.route);
+            if joined > 0 {
+                let players = host.players();
+                game_data.notify(format!("A player joined ({} connected)", players));
+            }
+        }
+        Some(Session::Player(player)) => {
+            let was_connected = player.connected();
+            let messages = player.messages();
+            if was_connected && !player.connected() {
+                game_data.notify("Lost the connection to the host".to_string());
+            }
+            if !messages.is_empty() {
+                game_data.map_cache = None;
+            }
+            for message in messages {
+                match message {
+                    Message::Galaxy(galaxy) => {game_data.galaxy = galaxy}
+                    Message::Upsert(systems) => {session::upsert_systems(&mut game_data.galaxy, systems)}
+                    Message::Remove(ids) => {session::remove_systems(&mut game_data.galaxy, &ids)}
+                    Message::Focus(id) => {
+                        // The other views are built around the system they were opened on
+                        if matches!(game_data.state, GameState::MapView) {
+                            game_data.focused = id;
+                        }
+                    }
+                    Message::Route(route) => {game_data.route = route}
+                    Message::Parties(parties) => {*game_data.galaxy.parties_mut() = parties}
+                }
+            }
+            forget_missing(game_data);
+        }
+    }
+}
+
+/// Lets go of systems that aren't in the galaxy any more, after it was replaced or changed
+/// underneath the views. The star and planet views leave by themselves when theirs goes.
+fn forget_missing(game_data: &mut GameData) {
+    let galaxy = &game_data.galaxy;
+    if game_data.hovered.is_some_and(|id| !galaxy.contains(id)) {
+        game_data.hovered = None;
+    }
+    if game_data.home.is_some_and(|id| !galaxy.contains(id)) {
+        game_data.home = None;
+    }
+    if matches!(game_data.state, GameState::MapView) && game_data.focused.is_some_and(|id| !galaxy.contains(id)) {
+        game_data.focused = None;
+    }
+    game_data.route.retain(|id| galaxy.contains(*id));
+}
+
+/// Reloads the save when something else changed it, unless that would throw away edits
+/// made here. Returns the systems that may have changed.
+fn watch_save(game_data: &mut GameData) -> Vec<SystemId> {
+    // The editor holds on to planets and moons by index, so changes wait until it's closed
+    if matches!(game_data.state, GameState::Editor) {
+        return Vec::new()
+    }
+    let modified = match game_data.watcher.as_mut().and_then(SaveWatcher::poll) {
+        None => {return Vec::new()}
+        Some(modified) => {modified}
+    };
+    if Some(game_data.history.edits()) != game_data.saved_edits {
+        let reload = game_data.input.describe(Action::ReloadSave);
+        game_data.notify(format!("{} changed on disk. {} loads it, losing changes made here", game_data.save_path, reload));
+        return Vec::new()
+    }
+    reload_save(game_data, Some(modified))
+}
+
+/// Replaces the galaxy with what's in the save, keeping the camera and whatever is still
+/// there of the selection. Undo starts over, since the edits were to the old galaxy.
+fn reload_save(game_data: &mut GameData, modified: Option<std::time::SystemTime>) -> Vec<SystemId> {
+    let galaxy = match file_generator::load_file(&game_data.save_path) {
+        None => {
+            game_data.notify(format!("{} changed on disk but couldn't be read, keeping what's open", game_data.save_path));
+            return Vec::new()
+        }
+        Some(galaxy) => {galaxy}
+    };
+    let mut changed: Vec<SystemId> = game_data.galaxy.systems().iter().map(|system| system.id()).collect();
+    changed.extend(galaxy.systems().iter().map(|system| system.id()).filter(|id| !game_data.galaxy.contains(*id)));
+    game_data.galaxy = galaxy;
+    game_data.history = History::default();
+    game_data.saved_edits = Some(game_data.history.edits());
+    game_data.map_cache = None;
+    if let Some(watcher) = &mut game_data.watcher {
+        watcher.caught_up(modified);
+    }
+    forget_missing(game_data);
+    game_data.notify(format!("Reloaded {}", game_data.save_path));
+    changed
+}
+
+/// Asks what to do when the save was changed by something else since rim loaded it, before
+/// saving over it. Returns where to save, or None to leave the save as it is.
+fn confirm_overwrite(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &GameData) -> Option<String> {
+    let copy_path = file_generator::export_path(&game_data.save_path, "mine.rim");
+    let lines = [
+        format!("{} was changed by something else since rim loaded it.", game_data.save_path),
+        "Saving now would write over those changes.".to_string(),
+        String::new(),
+        "Y: Save over it".to_string(),
+        format!("C: Keep it, and save this galaxy to {}", copy_path),
+        "N: Keep it, and don't save this galaxy".to_string(),
+    ];
+    let keys = [KeyboardKey::KEY_Y, KeyboardKey::KEY_C, KeyboardKey::KEY_N];
+    match ask(rl, thread, game_data, "SAVE CHANGED ON DISK", &lines, &keys) {
+        Some(KeyboardKey::KEY_Y) => {Some(game_data.save_path.clone())}
+        Some(KeyboardKey::KEY_C) => {Some(copy_path)}
+        _ => {None}
+    }
+}
+
+fn answer_api(game_data: &mut GameData, command: &Command) -> Response {
+    let galaxy = &game_data.galaxy;
+    match command {
+        Command::ListSystems => {
+            Response::ok(galaxy.systems().iter().map(|system| server::system_summary(galaxy, system)).collect())
+        }
+        Command::GetSystem(id) => {
+            match galaxy.get(*id) {
+                None => {Response::no_system(*id)}
+                Some(system) => {Response::ok(server::system_details(galaxy, system))}
+            }
+        }
+        Command::GetFocus => {
+            Response::ok(serde_json::json!({ "id": game_data.focused.map(|id| id.0) }))
+        }
+        Command::SetFocus(_) | Command::SetExplored(..) | Command::AddNote(..) if game_data.read_only() => {
+            Response::error(409, "Only the host can change a shared galaxy")
+        }
+        Command::SetFocus(id) => {
+            // The other views are built around the system they were opened on
+            if !matches!(game_data.state, GameState::MapView) {
+                return Response::error(409, "The focus can only be moved while the map is open")
+            }
+            match id {
+                Some(id) if !galaxy.contains(*id) => {return Response::no_system(*id)}
+                Some(id) => {focus_system(game_data, *id)}
+                None => {game_data.focused = None}
+            }
+            Response::ok(serde_json::json!({ "id": game_data.focused.map(|id| id.0) }))
+        }
+        Command::SetExplored(id, explored, party) => {
+            if !galaxy.contains(*id) {
+                return Response::no_system(*id)
+            }
+            let party = match party {
+                None => {game_data.party}
+                Some(name) => {
+                    match galaxy.parties().iter().position(|party| party.name == *name) {
+                        None => {return Response::error(404, &format!("No party named {}", name))}
+                        Some(party) => {party}
+                    }
+                }
+            };
+            let before = galaxy.parties()[party].explored.get(id).copied();
+            if before.is_some() != *explored {
+                let after = explored.then(party::now);
+                game_data.history.apply(&mut game_data.galaxy, Edit::SetExplored { party, id: *id, before, after });
+            }
+            Response::ok(server::system_details(&game_data.galaxy, &game_data.galaxy[*id]))
+        }
+        Command::AddNote(id, text) => {
+            let before = match galaxy.get(*id) {
+                None => {return Response::no_system(*id)}
+                Some(system) => {system.notes.clone()}
+            };
+            let after = if before.is_empty() { text.clone() } else { format!("{}\n{}", before, text) };
+            game_data.history.apply(&mut game_data.galaxy, Edit::SetNotes { id: *id, before, after });
+            Response::ok(server::system_details(&game_data.galaxy, &game_data.galaxy[*id]))
+        }
+    }
+}
+
+/// A panel over the map that has the keyboard while it's open.
+enum MapOverlay {
+    Search(SearchBox),
+    Filters(FilterPanel),
+    NameParty(TextField),
+}
+
+/// The map's search box and what it's found so far.
+struct SearchBox {
+    field: TextField,
+    results: Vec<SystemId>,
+    /// The result Enter jumps to, moved with Up and Down.
+    chosen: usize,
+}
+
+/// Returns true once the search box should close.
+fn update_search_box(rl: &mut RaylibHandle, game_data: &mut GameData, search_box: &mut SearchBox) -> bool {
+    let event = search_box.field.update(rl);
+    let num_results = search_box.results.len();
+    if num_results > 0 && rl.is_key_pressed(KeyboardKey::KEY_DOWN) {
+        search_box.chosen = (search_box.chosen + 1) % num_results;
+    }
+    if num_results > 0 && rl.is_key_pressed(KeyboardKey::KEY_UP) {
+        search_box.chosen = (search_box.chosen + num_results - 1) % num_results;
+    }
+    match event {
+        None => {false}
+        Some(TextFieldEvent::Changed) => {
+            search_box.results = search::search(&game_data.galaxy, &search_box.field.text);
+            search_box.chosen = 0;
+            false
+        }
+        Some(TextFieldEvent::Submit) => {
+            // Focusing eases the camera over to the system like clicking on it does
+            if let Some(&id) = search_box.results.get(search_box.chosen).filter(|id| game_data.galaxy.contains(**id)) {
+                focus_system(game_data, id);
+            }
+            true
+        }
+        Some(TextFieldEvent::Cancel) => {true}
+    }
+}
+
+const SEARCH_HELP: &str = "Type a name, tag, planet class or words from the notes   Up/Down: Choose   Enter: Go To System   Esc: Close";
+
+fn draw_search_box(d: &mut RaylibDrawHandle, game_data: &GameData, search_box: &SearchBox) {
+    let font_size = hud_font(d, game_data, game_data.config.hud.small_font_size);
+    let width = (d.get_screen_width() / 2).max(font_size * 20);
+    let x = (d.get_screen_width() - width) / 2;
+    let mut y = font_size;
+    let text_color = Color::new(200, 200, 200, 255);
+    y += search_box.field.draw(d, x, y, width, font_size) + font_size / 2;
+    d.draw_text(SEARCH_HELP, x, y, font_size * 3 / 4, text_color.alpha(0.6));
+    y += font_size * 3 / 2;
+
+    if search_box.results.is_empty() && !search_box.field.text.trim().is_empty() {
+        d.draw_text("No matching systems.", x, y, font_size, text_color.alpha(0.7));
+    }
+    let line_height = font_size * 3 / 2;
+    for (row, id) in search_box.results.iter().enumerate() {
+        // Players' results can be taken out of view by the host while the box is open
+        let system = match game_data.galaxy.get(*id) {
+            None => {continue}
+            Some(system) => {system}
+        };
+        if row == search_box.chosen {
+            d.draw_rectangle(x, y - font_size / 4, width, line_height, Color::POWDERBLUE.alpha(0.2));
+        }
+        d.draw_text(&search_result_line(system), x + font_size / 3, y, font_size, text_color);
+        y += line_height;
+    }
+}
+
+/// One row of the search results: the name, tags and planet classes.
+fn search_result_line(system: &map::StarSystem) -> String {
+    let mut line = format!("System {:X}", system.name);
+    if !system.tags.is_empty() {
+        line += format!("  [{}]", system.tags.join(", ")).as_str();
+    }
+    if let Some(data) = &system.system_data {
+        let classes: Vec<String> = data.planets.iter().map(|planet| format!("{:?}", planet.class)).collect();
+        if !classes.is_empty() {
+            line += format!("  {}", classes.join(", ")).as_str();
+        }
+    }
+    line
+}
+
+
+
+
+
+fn gameloop_editor(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    let mut camera = Camera3D::orthographic(
+        Vector3::new(0.0, 0.0, game_data.config.camera.editor.distance),
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        game_data.config.camera.editor.fov.1,
+    );
+    // The editor picks up where the map camera was looking from. Its pitch is measured from
+    // straight up rather than from the equator.
+    let mut orbit_angle = game_data.map_orbit.yaw;
+    let mut pitch_angle = std::f32::consts::FRAC_PI_2 - game_data.map_orbit.pitch;
+    let settings = game_data.config.camera.editor.clone();
+    let zoom_speed = game_data.config.camera.zoom_speed;
+    let (min_fov, max_fov) = settings.fov;
+
+    let mut selected_planet: Option<usize> = None;
+    let mut selected_moon: Option<usize> = None;
+    let mut dragging = false;
+    let mut drag_start = Vec3::zero();
+    // The lane under the mouse, when there's no system under it
+    let mut hovered_lane: Option<(SystemId, SystemId)> = None;
+    // Notes or tags being typed for the selected system, with which of the two it is
+    let mut text_field: Option<(Action, TextField)> = None;
+
+    // Systems rest at their origin while being edited so they don't slide out from under the mouse.
+    for system in game_data.galaxy.systems_mut() {
+        system.set_origin(system.origin());
+    }
+
+    while running(rl, game_data) {
+        game_data.input.update(rl);
+        sync(game_data);
+        update_pause(rl, thread, game_data);
+        let orbit_direction = game_data.input.axis(rl, Action::OrbitRight, Action::OrbitLeft);
+        let pitch_direction = game_data.input.axis(rl, Action::PitchDown, Action::PitchUp);
+        let dt = rl.get_frame_time();
+        game_data.tick_notice(dt);
+        orbit_angle += settings.orbit_speed * dt * orbit_direction;
+        pitch_angle = (pitch_angle + settings.pitch_speed * dt * pitch_direction).clamp(0.1, std::f32::consts::PI - 0.1);
+        camera.position.x = settings.distance * orbit_angle.cos() * pitch_angle.sin();
+        camera.position.z = settings.distance * orbit_angle.sin() * pitch_angle.sin();
+        camera.position.y = settings.distance * pitch_angle.cos();
+        camera.fovy -= zoom_speed * game_data.input.scroll(rl, Action::ZoomIn, Action::ZoomOut);
+        if camera.fovy > max_fov {camera.fovy = max_fov};
+        if camera.fovy < min_fov {camera.fovy = min_fov};
+
+        if let Some((action, field)) = &mut text_field {
+            match field.update(rl) {
+                Some(TextFieldEvent::Submit) => {
+                    if let Some(focus) = game_data.focused.filter(|focus| game_data.galaxy.contains(*focus)) {
+                        let system = &game_data.galaxy[focus];
+                        let text = field.text.trim().to_string();
+                        // Notes go on the selected planet or moon if there is one, otherwise the system
+                        let edit = match (*action, selected_planet, &system.system_data) {
+                            (Action::EditNotes, Some(planet), Some(data)) => {
+                                let mut after = data.clone();
+                                *body_notes(&mut after, planet, selected_moon) = text;
+                                Edit::SetSystemData { id: focus, before: Some(data.clone()), after: Some(after), scans: Vec::new() }
+                            }
+                            (Action::EditNotes, ..) => {
+                                Edit::SetNotes { id: focus, before: system.notes.clone(), after: text }
+                            }
+                            _ => {
+                                let tags = text.split(',').map(str::trim).filter(|tag| !tag.is_empty()).map(String::from).collect();
+                                Edit::SetTags { id: focus, before: system.tags.clone(), after: tags }
+                            }
+                        };
+                        game_data.history.apply(&mut game_data.galaxy, edit);
+                    }
+                    text_field = None;
+                }
+                Some(TextFieldEvent::Cancel) => {text_field = None}
+                Some(TextFieldEvent::Changed) | None => {}
+            }
+            if text_field.is_none() {
+                game_data.input.set_suspended(false);
+                game_data.input.consume(Action::Back);
+            }
+        }
+
+        if game_data.input.pressed(rl, Action::Back) {
+            game_data.input.consume(Action::Back);
+            break
+        }
+
+        let ray = rl.get_screen_to_world_ray(rl.get_mouse_position(), camera);
+        let view_direction = (camera.target - camera.position).normalized();
+        if !dragging {
+            game_data.hovered = game_data.galaxy.closest_system_to_mouse(rl, &camera, game_data.perspective);
+            hovered_lane = match game_data.hovered {
+                Some(_) => {None}
+                None => {game_data.galaxy.closest_lane_to_mouse(rl, &camera)}
+            };
+        }
+
+        // Selecting, dragging and linking systems
+        if let (true, Some(focus), Some(hovered)) = (game_data.input.pressed(rl, Action::ToggleLane), game_data.focused, game_data.hovered) {
+            let edit = if game_data.galaxy.is_connected(focus, hovered) {
+                Edit::Disconnect { a: focus, b: hovered }
+            }
+            else {
+                Edit::Connect { a: focus, b: hovered }
+            };
+            game_data.history.apply(&mut game_data.galaxy, edit);
+        }
+        else if let (true, Some((a, b))) = (game_data.input.pressed(rl, Action::ToggleLane), hovered_lane) {
+            // Pointing straight at a lane cuts it without selecting either end
+            game_data.history.apply(&mut game_data.galaxy, Edit::Disconnect { a, b });
+            hovered_lane = None;
+        }
+        if game_data.input.pressed(rl, Action::Select) {
+            if game_data.focused != game_data.hovered {
+                selected_planet = None;
+                selected_moon = None;
+            }
+            game_data.focused = game_data.hovered;
+            dragging = game_data.hovered.is_some();
+            if let Some(focus) = game_data.focused {
+                drag_start = game_data.galaxy[focus].origin();
+            }
+        }
+        if game_data.input.released(rl, Action::Select) {
+            if let (true, Some(focus)) = (dragging, game_data.focused) {
+                let to = game_data.galaxy[focus].origin();
+                if to != drag_start {
+                    game_data.history.record(Edit::MoveSystem { id: focus, from: drag_start, to });
+                }
+            }
+            dragging = false;
+        }
+        if let (true, Some(focus)) = (dragging, game_data.focused) {
+            // Slide the system across the plane facing the camera at its current depth
+            let origin = game_data.galaxy[focus].origin();
+            if let Some(point) = picking::ray_plane_intersect(ray.position, ray.direction, origin.into(), view_direction) {
+                game_data.galaxy.move_system(focus, point.into());
+            }
+        }
+
+        // Adding and deleting systems
+        if game_data.input.pressed(rl, Action::AddSystem) {
+            let radius = game_data.galaxy.radius();
+            let point = picking::ray_sphere_hit(ray.position, ray.direction, Vector3::zero(), radius)
+                .or_else(|| picking::ray_plane_intersect(ray.position, ray.direction, Vector3::zero(), view_direction));
+            if let Some(point) = point {
+                let index = game_data.galaxy.len();
+                let system = game_data.galaxy.new_system(point.into());
+                let id = system.id();
+                game_data.history.apply(&mut game_data.galaxy, Edit::AddSystem { index, system });
+                game_data.focused = Some(id);
+                selected_planet = None;
+                selected_moon = None;
+            }
+        }
+        if let (true, Some(focus)) = (game_data.input.pressed(rl, Action::DeleteSystem), game_data.focused) {
+            let index = game_data.galaxy.index_of(focus).unwrap();
+            let system = game_data.galaxy[focus].clone();
+            game_data.history.apply(&mut game_data.galaxy, Edit::RemoveSystem { index, system });
+            game_data.focused = None;
+            game_data.hovered = None;
+            hovered_lane = None;
+            selected_planet = None;
+            selected_moon = None;
+            dragging = false;
+        }
+        if let Some(focus) = game_data.focused.filter(|_| !dragging) {
+            let system = &game_data.galaxy[focus];
+            if game_data.input.pressed(rl, Action::EditNotes) {
+                let field = match (selected_planet, system.system_data.clone()) {
+                    (Some(planet), Some(mut data)) => {
+                        let label = if selected_moon.is_some() { "Moon Notes" } else { "Planet Notes" };
+                        TextField::new(label, body_notes(&mut data, planet, selected_moon).clone())
+                    }
+                    _ => {TextField::new("Notes", system.notes.clone())}
+                };
+                text_field = Some((Action::EditNotes, field));
+            }
+            else if game_data.input.pressed(rl, Action::EditTags) {
+                text_field = Some((Action::EditTags, TextField::new("Tags (comma separated)", system.tags.join(", "))));
+            }
+            if text_field.is_some() {
+                game_data.input.set_suspended(true);
+            }
+        }
+
+        // Planet and moon editing on the selected system. Changes are made to a copy of the
+        // system data, which replaces the original through the history if anything changed.
+        if let Some(focus) = game_data.focused {
+            let before = game_data.galaxy[focus].system_data.clone();
+            let mut after = before.clone();
+            let mut changed = false;
+            // Where each planet went, so parties' scans can follow them
+            let mut moved: Vec<Option<usize>> = (0..before.as_ref().map_or(0, |data| data.planets.len())).map(Some).collect();
+            if game_data.input.pressed(rl, Action::AddPlanet) {
+                let data = after.get_or_insert_with(StarSystemData::new);
+                let added = data.add_planet(PlanetClass::Terran);
+                history::move_planet(&mut moved, None, Some(added));
+                selected_planet = Some(added);
+                selected_moon = None;
+                changed = true;
+            }
+            if let Some(data) = &mut after {
+                let num_planets = data.planets.len();
+                if num_planets > 0 && game_data.input.pressed(rl, Action::NextPlanet) {
+                    selected_planet = Some(selected_planet.map_or(0, |i| (i + 1) % num_planets));
+                    selected_moon = None;
+                }
+                if num_planets > 0 && game_data.input.pressed(rl, Action::PreviousPlanet) {
+                    selected_planet = Some(selected_planet.map_or(num_planets - 1, |i| (i + num_planets - 1) % num_planets));
+                    selected_moon = None;
+                }
+                if let Some(planet_index) = selected_planet {
+                    if game_data.input.pressed(rl, Action::RetypePlanet) {
+                        let class = data.planets[planet_index].class.next();
+                        let retyped = data.set_planet_class(planet_index, class);
+                        history::move_planet(&mut moved, Some(planet_index), Some(retyped));
+                        selected_planet = Some(retyped);
+                        selected_moon = None;
+                        changed = true;
+                    }
+                    else if game_data.input.pressed(rl, Action::RemovePlanet) {
+                        data.remove_planet(planet_index);
+                        history::move_planet(&mut moved, Some(planet_index), None);
+                        selected_planet = None;
+                        selected_moon = None;
+                        changed = true;
+                    }
+                }
+                if let Some(planet) = selected_planet.map(|i| &mut data.planets[i]) {
+                    if game_data.input.pressed(rl, Action::PlanetMassUp) {
+                        planet.set_mass(planet.mass * 1.25);
+                        changed = true;
+                    }
+                    if game_data.input.pressed(rl, Action::PlanetMassDown) {
+                        planet.set_mass(planet.mass / 1.25);
+                        changed = true;
+                    }
+                    if game_data.input.pressed(rl, Action::AddMoon) {
+                        selected_moon = Some(planet.add_moon(MoonType::Asteroid));
+                        changed = true;
+                    }
+                    let num_moons = planet.moons.len();
+                    if num_moons > 0 && game_data.input.pressed(rl, Action::NextMoon) {
+                        selected_moon = Some(selected_moon.map_or(0, |i| (i + 1) % num_moons));
+                    }
+                    if num_moons > 0 && game_data.input.pressed(rl, Action::PreviousMoon) {
+                        selected_moon = Some(selected_moon.map_or(num_moons - 1, |i| (i + num_moons - 1) % num_moons));
+                    }
+                    if let Some(moon_index) = selected_moon {
+                        if game_data.input.pressed(rl, Action::RetypeMoon) {
+                            let moon = &mut planet.moons[moon_index];
+                            moon.set_type(moon.moon_type.next());
+                            changed = true;
+                        }
+                        else if game_data.input.pressed(rl, Action::RemoveMoon) {
+                            planet.remove_moon(moon_index);
+                            selected_moon = None;
+                            changed = true;
+                        }
+                    }
+                }
+            }
+            if changed {
+                let scans = history::renumber_scans(&game_data.galaxy, focus, &moved);
+                game_data.history.apply(&mut game_data.galaxy, Edit::SetSystemData { id: focus, before, after, scans });
+            }
+        }
+
+        if !dragging && handle_undo_redo(rl, game_data) {
+            game_data.hovered = None;
+            hovered_lane = None;
+            if game_data.focused.is_some_and(|focus| !game_data.galaxy.contains(focus)) {
+                game_data.focused = None;
+            }
+            let planets = game_data.focused
+                .and_then(|focus| game_data.galaxy[focus].system_data.as_ref())
+                .map(|data| &data.planets);
+            let planet = selected_planet.zip(planets).and_then(|(i, planets)| planets.get(i));
+            if planet.is_none() {
+                selected_planet = None;
+            }
+            if selected_moon.zip(planet).is_none_or(|(i, planet)| i >= planet.moons.len()) {
+                selected_moon = None;
+            }
+        }
+
+        draw_editor_view(rl, thread, &camera, game_data, (selected_planet, selected_moon), hovered_lane, text_field.as_ref().map(|(_, field)| field));
+    }
+    game_data.input.set_suspended(false);
+
+    game_data.map_orbit.yaw = orbit_angle;
+    game_data.map_orbit.pitch = std::f32::consts::FRAC_PI_2 - pitch_angle;
+    game_data.hovered = None;
+    game_data.focused = None;
+    game_data.state = GameState::MapView;
+}
+
+fn draw_editor_view(
+    rl: &mut RaylibHandle,
+    thread: &RaylibThread,
+    camera: &Camera3D,
+    game_data: &GameData,
+    selected_planet_and_moon: (Option<usize>, Option<usize>),
+    hovered_lane: Option<(SystemId, SystemId)>,
+    text_field: Option<&TextField>
+    ) {
+    let mut d = rl.begin_drawing(thread);
+    d.clear_background(Color::BLACK);
+    {
+        let mut d3 = d.begin_mode3D(camera);
+        for star in &game_data.stars {
+            d3.draw_point3D(star, Color::WHITE);
+        }
+        draw_systems(&mut d3, camera, game_data, None, None);
+        if let Some((a, b)) = hovered_lane
+            && let (Some(a), Some(b)) = (game_data.galaxy.get(a), game_data.galaxy.get(b)) {
+            d3.draw_line_3D(a.position, b.position, Color::ORANGERED);
+        }
+    }
+
+    let font_size = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+    d.draw_text(&editor_help(&game_data.input), font_size / 2, font_size / 2, font_size, Color::new(200, 200, 200, 200));
+    d.draw_text(&format!("Undo: {}   Redo: {}", game_data.history.undo_len(), game_data.history.redo_len()),
+        font_size / 2, d.get_screen_height() - font_size * 3 / 2, font_size, Color::new(200, 200, 200, 200));
+    let string = match game_data.focused {
+        None => {"No System Selected.".to_string()}
+        Some(focus) => {
+            editor_selection_string(&game_data.galaxy[focus], selected_planet_and_moon)
+        }
+    };
+    draw_text_right(&mut d, &string, font_size, Color::new(200, 200, 200, 200));
+    if let Some(field) = text_field {
+        let width = d.get_screen_width() - font_size;
+        let y = d.get_screen_height() - font_size * 5;
+        field.draw(&mut d, font_size / 2, y, width, font_size);
+    }
+    draw_pause_menu(&mut d, game_data);
+    draw_notice(&mut d, game_data);
+}
+
+fn editor_selection_string(system: &map::StarSystem, selected_planet_and_moon: (Option<usize>, Option<usize>)) -> String {
+    let mut string = format!("System {:X}:\n", system.name);
+    string += format!("{} Connections.\n", system.connections.len()).as_str();
+    string += format!("Tags: {}\n", if system.tags.is_empty() { "None".to_string() } else { system.tags.join(", ") }).as_str();
+    if !system.notes.is_empty() {
+        string += format!("Notes: {}\n", system.notes).as_str();
+    }
+    let data = match &system.system_data {
+        None => {
+            string += "Not Scanned.\n";
+            return string
+        }
+        Some(data) => {data}
+    };
+    for (i, planet) in data.planets.iter().enumerate() {
+        let planet_selected = selected_planet_and_moon.0 == Some(i);
+        string += format!("{} Planet {}: {:?}, {:.2} Earth masses, {:.3} std.\n",
+            if planet_selected {">"} else {" "},
+            utils::num_to_letter(i as u8).unwrap_or('?').to_ascii_uppercase(),
+            planet.class, planet.mass, planet.orbit_radius).as_str();
+        if !planet_selected {
+            continue
+        }
+        if !planet.notes.is_empty() {
+            string += format!("   Notes: {}\n", planet.notes).as_str();
+        }
+        for (j, moon) in planet.moons.iter().enumerate() {
+            let moon_selected = selected_planet_and_moon.1 == Some(j);
+            string += format!("   {} Moon {}: {:?}\n",
+                if moon_selected {">"} else {" "},
+                j + 1, moon.moon_type).as_str();
+            if moon_selected && !moon.notes.is_empty() {
+                string += format!("      Notes: {}\n", moon.notes).as_str();
+            }
+        }
+    }
+    string
+}
+
+/// The notes of a planet, or of one of its moons.
+fn body_notes(data: &mut StarSystemData, planet: usize, moon: Option<usize>) -> &mut String {
+    let planet = &mut data.planets[planet];
+    match moon {
+        None => {&mut planet.notes}
+        Some(moon) => {&mut planet.moons[moon].notes}
+    }
+}
+
+/// The actions listed in the editor's help text, a line at a time.
+const EDITOR_HELP: [&[Action]; 13] = [
+    &[Action::Select],
+    &[Action::ToggleLane],
+    &[Action::AddSystem, Action::DeleteSystem],
+    &[Action::EditNotes, Action::EditTags],
+    &[Action::AddPlanet, Action::RemovePlanet],
+    &[Action::NextPlanet, Action::PreviousPlanet],
+    &[Action::RetypePlanet, Action::PlanetMassUp, Action::PlanetMassDown],
+    &[Action::AddMoon, Action::RemoveMoon],
+    &[Action::NextMoon, Action::PreviousMoon, Action::RetypeMoon],
+    &[Action::Undo, Action::Redo],
+    &[Action::OrbitLeft, Action::OrbitRight],
+    &[Action::PitchUp, Action::PitchDown],
+    &[Action::Back],
+];
+
+fn editor_help(input: &Input) -> String {
+    let mut help = "EDITOR".to_string();
+    for line in EDITOR_HELP {
+        let entries: Vec<String> = line.iter().map(|action| format!("{}: {}", input.describe(*action), action.label())).collect();
+        help += "\n";
+        help += &entries.join("   ");
+    }
+    help
+}
+
+fn gameloop_bindings(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    bindings_screen(rl, thread, game_data);
+    game_data.state = GameState::MapView;
+}
+
+/// Lists every action with what it's bound to, and lets the player change them.
+/// Getting around this screen is fixed to the arrow keys, Enter and Escape (or the d-pad,
+/// A and B) so a bad binding can't lock anyone out of fixing it.
+fn bindings_screen(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    let defaults = config::default_bindings();
+    let mut selected = 0;
+    let mut capturing = false;
+    let mut changed = false;
+    while running(rl, game_data) {
+        game_data.input.update(rl);
+        sync(game_data);
+        game_data.tick_notice(rl.get_frame_time());
+        let action = Action::ALL[selected];
+        if capturing {
+            if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+                capturing = false;
+            }
+            else if let Some(chord) = game_data.input.capture(rl) {
+                let chords = game_data.input.bindings.entry(action).or_default();
+                if !chords.contains(&chord) {
+                    chords.push(chord);
+                    changed = true;
+                }
+                capturing = false;
+            }
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_ESCAPE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT) {
+            break
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+            selected = (selected + Action::ALL.len() - 1) % Action::ALL.len();
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+            selected = (selected + 1) % Action::ALL.len();
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+            capturing = true;
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_BACKSPACE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT)
+            || rl.is_key_pressed(KeyboardKey::KEY_DELETE) {
+            game_data.input.bindings.insert(action, Vec::new());
+            changed = true;
+        }
+        else if menu_pressed(rl, KeyboardKey::KEY_R, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP) {
+            game_data.input.bindings.insert(action, defaults.get(&action).cloned().unwrap_or_default());
+            changed = true;
+        }
+        draw_bindings(rl, thread, game_data, selected, capturing);
+    }
+
+    // Whatever left this screen shouldn't also count as Back on the map
+    game_data.input.consume(Action::Back);
+    if changed {
+        game_data.config.bindings = game_data.input.bindings.clone();
+        match &game_data.config.path {
+            None => {game_data.notify("Nowhere to save bindings to, they only last until Rim closes".to_string())}
+            Some(path) => {
+                match config::save_section(path, "bindings", &game_data.config.bindings) {
+                    Ok(()) => {game_data.notify(format!("Saved bindings to {}", path.display()))}
+                    Err(err) => {game_data.notify(err)}
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn menu_pressed(rl: &RaylibHandle, key: KeyboardKey, button: GamepadButton) -> bool {
+    rl.is_key_pressed(key) || (rl.is_gamepad_available(0) && rl.is_gamepad_button_pressed(0, button))
+}
+
+const BINDINGS_HELP: &str = "Up/Down: Choose   Enter: Add Binding   Backspace: Clear   R: Reset To Default   Esc: Back";
+
+fn draw_bindings(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &GameData, selected: usize, capturing: bool) {
+    let mut d = rl.begin_drawing(thread);
+    d.clear_background(Color::BLACK);
+    let font_size = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+    let margin = font_size;
+    let line_height = font_size * 3 / 2;
+    let text_color = Color::new(200, 200, 200, 255);
+    d.draw_text("BINDINGS", margin, margin, font_size * 2, text_color);
+    d.draw_text(BINDINGS_HELP, margin, margin + font_size * 5 / 2, font_size, text_color.alpha(0.7));
+
+    // Scroll so the selected action stays on screen
+    let top = margin + font_size * 5;
+    let rows = ((d.get_screen_height() - top - margin * 3) / line_height).max(1) as usize;
+    let first = selected.saturating_sub(rows / 2).min(Action::ALL.len().saturating_sub(rows));
+    let label_width = Action::ALL.iter().map(|action| d.measure_text(action.label(), font_size)).max().unwrap_or(0);
+    for (row, action) in Action::ALL.iter().enumerate().skip(first).take(rows) {
+        let y = top + (row - first) as i32 * line_height;
+        let bound = if row == selected && capturing {
+            "Press a key, button or stick... (Esc cancels)".to_string()
+        }
+        else {
+            game_data.input.describe(*action)
+        };
+        if row == selected {
+            d.draw_rectangle(margin / 2, y - font_size / 4, d.get_screen_width() - margin, line_height, Color::POWDERBLUE.alpha(0.2));
+        }
+        d.draw_text(action.label(), margin, y, font_size, text_color);
+        d.draw_text(&bound, margin * 2 + label_width, y, font_size, text_color);
+    }
+    draw_notice(&mut d, game_data);
+}
+
+/// The pause menu, open over whichever view the player was in. The view keeps running
+/// underneath with its input suspended, so its camera is as it was left when the menu closes.
+struct PauseMenu {
+    page: PausePage,
+    selected: usize,
+    /// Whether the window settings were changed, to save them on leaving the settings page.
+    settings_changed: bool,
+}
+
+enum PausePage {
+    Main,
+    Settings,
+    /// Typing where to save the galaxy to.
+    SaveAs(TextField),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PauseRow {
+    Resume,
+    Save,
+    SaveAs,
+    Settings,
+    Controls,
+    ExportGraph,
+    ExportSvg,
+    ExportPlayerCopy,
+    Quit,
+}
+
+impl PauseRow {
+    const ALL: [PauseRow; 9] = [
+        PauseRow::Resume, PauseRow::Save, PauseRow::SaveAs, PauseRow::Settings, PauseRow::Controls,
+        PauseRow::ExportGraph, PauseRow::ExportSvg, PauseRow::ExportPlayerCopy, PauseRow::Quit,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            PauseRow::Resume => "Resume",
+            PauseRow::Save => "Save",
+            PauseRow::SaveAs => "Save As...",
+            PauseRow::Settings => "Settings...",
+            PauseRow::Controls => "Controls...",
+            PauseRow::ExportGraph => "Export Lane Graph",
+            PauseRow::ExportSvg => "Export Map SVG",
+            PauseRow::ExportPlayerCopy => "Export Player Copy",
+            PauseRow::Quit => "Quit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SettingsRow {
+    WindowMode,
+    TargetFps,
+    UiScale,
+    HiDpi,
+    Back,
+}
+
+impl SettingsRow {
+    const ALL: [SettingsRow; 5] = [SettingsRow::WindowMode, SettingsRow::TargetFps, SettingsRow::UiScale, SettingsRow::HiDpi, SettingsRow::Back];
+
+    fn label(&self) -> &'static str {
+        match self {
+            SettingsRow::WindowMode => "Window Mode",
+            SettingsRow::TargetFps => "Frame Rate",
+            SettingsRow::UiScale => "UI Scale",
+            SettingsRow::HiDpi => "HiDPI Scaling",
+            SettingsRow::Back => "Back",
+        }
+    }
+
+    fn value(&self, window: &WindowConfig) -> String {
+        match self {
+            SettingsRow::WindowMode => {format!("{:?}", window.mode)}
+            SettingsRow::TargetFps if window.target_fps == 0 => {"Unlimited".to_string()}
+            SettingsRow::TargetFps => {format!("{} fps", window.target_fps)}
+            SettingsRow::UiScale => {format!("{:.2}x", window.ui_scale)}
+            SettingsRow::HiDpi => {if window.hidpi { "On" } else { "Off" }.to_string()}
+            SettingsRow::Back => {String::new()}
+        }
+    }
+}
+
+const WINDOW_MODES: [WindowMode; 3] = [WindowMode::Windowed, WindowMode::Borderless, WindowMode::Fullscreen];
+/// 0 is unlimited.
+const FPS_STEPS: [u32; 6] = [30, 60, 120, 144, 240, 0];
+const UI_SCALE_STEPS: [f32; 11] = [0.5, 0.75, 1.0, 1.25, 1.5, 1.75, 2.0, 2.25, 2.5, 2.75, 3.0];
+
+const PAUSE_HELP: &str = "Up/Down: Choose   Enter: Select   Esc: Resume";
+const SETTINGS_HELP: &str = "Up/Down: Choose   Left/Right: Change   Esc: Back";
+
+fn open_pause(game_data: &mut GameData) {
+    game_data.pause = Some(PauseMenu { page: PausePage::Main, selected: 0, settings_changed: false });
+    game_data.input.set_suspended(true);
+}
+
+/// Opens the pause menu on Pause, and runs it while it's open. Every view calls this once
+/// a frame, after `sync`. Like the bindings screen it's driven by fixed keys and the d-pad.
+fn update_pause(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData) {
+    let mut pause = match game_data.pause.take() {
+        None => {
+            if game_data.input.pressed(rl, Action::Pause) {
+                open_pause(game_data);
+            }
+            return
+        }
+        Some(pause) => {pause}
+    };
+    let close = match pause.page {
+        PausePage::Main => {update_pause_main(rl, thread, game_data, &mut pause)}
+        PausePage::Settings => {
+            update_pause_settings(rl, game_data, &mut pause);
+            false
+        }
+        PausePage::SaveAs(_) => {
+            update_save_as(rl, thread, game_data, &mut pause);
+            false
+        }
+    };
+    if close {
+        game_data.input.set_suspended(false);
+        // The Escape or Enter that closed it shouldn't also leave the view or pick something
+        game_data.input.consume(Action::Confirm);
+        game_data.input.consume(Action::Back);
+    }
+    else {
+        game_data.pause = Some(pause);
+    }
+}
+
+/// Returns true once the menu should close.
+fn update_pause_main(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData, pause: &mut PauseMenu) -> bool {
+    let rows = PauseRow::ALL.len();
+    if menu_pressed(rl, KeyboardKey::KEY_ESCAPE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT) {
+        return true
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+        pause.selected = (pause.selected + rows - 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+        pause.selected = (pause.selected + 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        match PauseRow::ALL[pause.selected] {
+            PauseRow::Resume => {return true}
+            PauseRow::Save => {
+                let path = game_data.save_path.clone();
+                save_from_pause(rl, thread, game_data, path);
+            }
+            PauseRow::SaveAs => {
+                if can_save(game_data) {
+                    pause.page = PausePage::SaveAs(TextField::new("Save As", game_data.save_path.clone()));
+                }
+            }
+            PauseRow::Settings => {
+                pause.page = PausePage::Settings;
+                pause.selected = 0;
+            }
+            PauseRow::Controls => {bindings_screen(rl, thread, game_data)}
+            PauseRow::ExportGraph => {export_graphs(game_data)}
+            PauseRow::ExportSvg => {
+                let svg = svg::galaxy_svg(&game_data.galaxy, Projection::TopDown, true);
+                let path = file_generator::export_path(&game_data.save_path, "svg");
+                export_svg(game_data, path, svg);
+            }
+            PauseRow::ExportPlayerCopy => {export_player_copy(game_data)}
+            PauseRow::Quit => {
+                game_data.quit = true;
+                return true
+            }
+        }
+    }
+    false
+}
+
+fn update_pause_settings(rl: &mut RaylibHandle, game_data: &mut GameData, pause: &mut PauseMenu) {
+    let rows = SettingsRow::ALL.len();
+    let row = SettingsRow::ALL[pause.selected];
+    let mut step = 0;
+    if menu_pressed(rl, KeyboardKey::KEY_ESCAPE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT) {
+        leave_settings(game_data, pause);
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+        pause.selected = (pause.selected + rows - 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+        pause.selected = (pause.selected + 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_RIGHT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT)
+        || menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        step = 1;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_LEFT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT) {
+        step = -1;
+    }
+    if step == 0 {
+        return
+    }
+
+    let window = &mut game_data.config.window;
+    match row {
+        SettingsRow::WindowMode => {
+            let mode = step_choice(window.mode, &WINDOW_MODES, step);
+            set_window_mode(rl, window, mode);
+        }
+        SettingsRow::TargetFps => {
+            window.target_fps = step_choice(window.target_fps, &FPS_STEPS, step);
+            rl.set_target_fps(window.target_fps);
+        }
+        SettingsRow::UiScale => {window.ui_scale = step_choice(window.ui_scale, &UI_SCALE_STEPS, step)}
+        SettingsRow::HiDpi => {window.hidpi = !window.hidpi}
+        SettingsRow::Back => {
+            leave_settings(game_data, pause);
+            return
+        }
+    }
+    pause.settings_changed = true;
+}
+
+/// Goes back to the main page, saving the window settings to the config if they changed.
+fn leave_settings(game_data: &mut GameData, pause: &mut PauseMenu) {
+    pause.page = PausePage::Main;
+    pause.selected = PauseRow::ALL.iter().position(|row| *row == PauseRow::Settings).unwrap_or(0);
+    if !pause.settings_changed {
+        return
+    }
+    pause.settings_changed = false;
+    match &game_data.config.path {
+        None => {game_data.notify("Nowhere to save settings to, they only last until Rim closes".to_string())}
+        Some(path) => {
+            match config::save_section(path, "window", &game_data.config.window) {
+                Ok(()) => {game_data.notify(format!("Saved settings to {}", path.display()))}
+                Err(err) => {game_data.notify(err)}
+            }
+        }
+    }
+}
+
+/// Moves to the next (1) or previous (-1) of `options`, wrapping around. A value that isn't
+/// one of them, like a UI scale typed into the config, moves to the first.
+fn step_choice<T: Copy + PartialEq>(current: T, options: &[T], step: isize) -> T {
+    match options.iter().position(|option| *option == current) {
+        None => {options[0]}
+        Some(i) => {options[(i as isize + step).rem_euclid(options.len() as isize) as usize]}
+    }
+}
+
+fn update_save_as(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData, pause: &mut PauseMenu) {
+    let field = match &mut pause.page {
+        PausePage::SaveAs(field) => {field}
+        _ => {return}
+    };
+    match field.update(rl) {
+        Some(TextFieldEvent::Submit) => {
+            let mut path = field.text.trim().to_string();
+            if path.is_empty() {
+                pause.page = PausePage::Main;
+                return
+            }
+            if std::path::Path::new(&path).extension().is_none() {
+                path.push_str(".rim");
+            }
+            if path != game_data.save_path && std::path::Path::new(&path).exists() {
+                game_data.notify(format!("{} already exists, pick another name", path));
+                return
+            }
+            pause.page = PausePage::Main;
+            save_from_pause(rl, thread, game_data, path);
+        }
+        Some(TextFieldEvent::Cancel) => {pause.page = PausePage::Main}
+        Some(TextFieldEvent::Changed) | None => {}
+    }
+}
+
+/// A joined session's galaxy lives with the host, so players have nothing to save.
+fn can_save(game_data: &mut GameData) -> bool {
+    let player = matches!(game_data.session, Some(Session::Player(_)));
+    if player {
+        game_data.notify("The host keeps this galaxy, so there's nothing to save here".to_string());
+    }
+    !player
+}
+
+/// Saves the galaxy without waiting for rim to close, to the save or somewhere new. Saving
+/// somewhere new makes that the save from then on.
+fn save_from_pause(rl: &mut RaylibHandle, thread: &RaylibThread, game_data: &mut GameData, path: String) {
+    if !can_save(game_data) {
+        return
+    }
+    let newer_on_disk = game_data.watcher.as_ref().is_some_and(SaveWatcher::newer_on_disk);
+    let path = if path == game_data.save_path && newer_on_disk {
+        match confirm_overwrite(rl, thread, game_data) {
+            None => {return}
+            Some(path) => {path}
+        }
+    }
+    else {
+        path
+    };
+    if let Err(err) = file_generator::try_save(&path, &game_data.galaxy) {
+        game_data.notify(format!("Could not save to {}: {}", path, err));
+        return
+    }
+    // The journal remembers which save it goes with, so it has to be written again too
+    let journal = if game_data.config.journal { file_generator::save_journal(&path, &game_data.history) } else { Ok(()) };
+    if path == game_data.save_path {
+        if let Some(watcher) = &mut game_data.watcher {
+            watcher.caught_up(file_generator::modified(&path));
+        }
+    }
+    else {
+        game_data.watcher = Some(SaveWatcher::new(&path));
+        game_data.save_path = path;
+    }
+    game_data.saved_edits = Some(game_data.history.edits());
+    menu::remember_save(&game_data.save_path);
+    match journal {
+        Ok(()) => {game_data.notify(format!("Saved to {}", game_data.save_path))}
+        Err(err) => {game_data.notify(format!("Saved to {}, but could not write its journal: {}", game_data.save_path, err))}
+    }
+}
+
+fn draw_pause_menu(d: &mut RaylibDrawHandle, game_data: &GameData) {
+    let pause = match &game_data.pause {
+        None => {return}
+        Some(pause) => {pause}
+    };
+    let font_size = hud_font(d, game_data, game_data.config.hud.small_font_size);
+    let margin = font_size;
+    let line_height = font_size * 3 / 2;
+    let text_color = Color::new(200, 200, 200, 255);
+    let (title, help, rows): (&str, &str, Vec<(&str, String)>) = match &pause.page {
+        PausePage::Settings => {
+            let rows = SettingsRow::ALL.iter().map(|row| (row.label(), row.value(&game_data.config.window))).collect();
+            ("SETTINGS", SETTINGS_HELP, rows)
+        }
+        PausePage::Main | PausePage::SaveAs(_) => {
+            ("PAUSED", PAUSE_HELP, PauseRow::ALL.iter().map(|row| (row.label(), String::new())).collect())
+        }
+    };
+
+    let label_width = rows.iter().map(|(label, _)| d.measure_text(label, font_size)).max().unwrap_or(0);
+    let value_width = rows.iter().map(|(_, value)| d.measure_text(value, font_size)).max().unwrap_or(0);
+    let width = (label_width + value_width + margin * 3).max(d.measure_text(help, font_size * 3 / 4) + margin * 2).max(font_size * 20);
+    let saving_as = matches!(pause.page, PausePage::SaveAs(_));
+    let height = margin * 4 + rows.len() as i32 * line_height + if saving_as { line_height * 2 } else { 0 };
+    let (screen_width, screen_height) = (d.get_screen_width(), d.get_screen_height());
+    d.draw_rectangle(0, 0, screen_width, screen_height, Color::BLACK.alpha(0.5));
+    let x = ((screen_width - width) / 2).max(0);
+    let top = ((screen_height - height) / 2).max(0);
+    d.draw_rectangle(x, top, width, height, Color::BLACK.alpha(0.8));
+
+    d.draw_text(title, x + margin / 2, top + margin / 2, font_size * 3 / 2, text_color);
+    d.draw_text(help, x + margin / 2, top + margin / 2 + font_size * 2, font_size * 3 / 4, text_color.alpha(0.6));
+    let mut y = top + margin * 7 / 2;
+    for (row, (label, value)) in rows.iter().enumerate() {
+        if row == pause.selected {
+            d.draw_rectangle(x, y - font_size / 4, width, line_height, Color::POWDERBLUE.alpha(0.2));
+        }
+        d.draw_text(label, x + margin / 2, y, font_size, text_color);
+        d.draw_text(value, x + margin * 3 / 2 + label_width, y, font_size, text_color);
+        y += line_height;
+    }
+    if let PausePage::SaveAs(field) = &pause.page {
+        field.draw(d, x + margin / 2, y + font_size / 2, width - margin, font_size);
+    }
+}
+
+/// The map's filter panel. Like the bindings screen it's driven by fixed keys and the d-pad.
+struct FilterPanel {
+    selected: usize,
+    /// The name being typed for a new preset.
+    naming: Option<TextField>,
+}
+
+/// Rows for each criterion come first, then these two, then the saved presets.
+const FILTER_CRITERIA: usize = 6;
+const FILTER_CLEAR_ROW: usize = FILTER_CRITERIA;
+const FILTER_SAVE_ROW: usize = FILTER_CRITERIA + 1;
+
+/// Steps through star masses from small red dwarfs to giants, in solar masses.
+const STAR_MASS_STEPS: [f64; 8] = [0.2, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0, 40.0];
+
+const FILTER_HELP: &str = "Up/Down: Choose   Left/Right: Change   Backspace: Clear   Enter: Load/Save Preset   Delete: Delete Preset   Esc: Close";
+
+/// Returns true once the panel should close.
+fn update_filter_panel(rl: &mut RaylibHandle, game_data: &mut GameData, panel: &mut FilterPanel) -> bool {
+    if let Some(field) = &mut panel.naming {
+        match field.update(rl) {
+            Some(TextFieldEvent::Submit) => {
+                let name = field.text.trim().to_string();
+                panel.naming = None;
+                if !name.is_empty() {
+                    game_data.config.filters.insert(name.clone(), game_data.filter.clone());
+                    save_filters(game_data, format!("Saved filter preset {}", name));
+                }
+            }
+            Some(TextFieldEvent::Cancel) => {panel.naming = None}
+            Some(TextFieldEvent::Changed) | None => {}
+        }
+        return false
+    }
+
+    let presets: Vec<String> = game_data.config.filters.keys().cloned().collect();
+    let rows = FILTER_SAVE_ROW + 1 + presets.len();
+    panel.selected = panel.selected.min(rows - 1);
+    let preset = panel.selected.checked_sub(FILTER_SAVE_ROW + 1).map(|i| &presets[i]);
+    if menu_pressed(rl, KeyboardKey::KEY_ESCAPE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT) {
+        return true
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+        panel.selected = (panel.selected + rows - 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+        panel.selected = (panel.selected + 1) % rows;
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_RIGHT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT) {
+        step_filter_row(&mut game_data.filter, panel.selected, 1);
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_LEFT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT) {
+        step_filter_row(&mut game_data.filter, panel.selected, -1);
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_BACKSPACE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT) {
+        step_filter_row(&mut game_data.filter, panel.selected, 0);
+    }
+    else if let (true, Some(name)) = (rl.is_key_pressed(KeyboardKey::KEY_DELETE), preset) {
+        game_data.config.filters.remove(name);
+        save_filters(game_data, format!("Deleted filter preset {}", name));
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        match (panel.selected, preset) {
+            (FILTER_CLEAR_ROW, _) => {game_data.filter = Filter::default()}
+            (FILTER_SAVE_ROW, _) => {panel.naming = Some(TextField::new("Preset Name", String::new()))}
+            (_, Some(name)) => {game_data.filter = game_data.config.filters[name].clone()}
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Moves one criterion to its next (1) or previous (-1) setting, or turns it off (0).
+fn step_filter_row(filter: &mut Filter, row: usize, step: isize) {
+    match row {
+        0 => {filter.planet_class = step_setting(filter.planet_class, &PlanetClass::ALL, step)}
+        1 => {filter.more_planets_than = step_setting(filter.more_planets_than, &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9], step)}
+        2 => {filter.min_star_mass = step_setting(filter.min_star_mass, &STAR_MASS_STEPS, step)}
+        3 => {filter.max_star_mass = step_setting(filter.max_star_mass, &STAR_MASS_STEPS, step)}
+        4 => {filter.moon_type = step_setting(filter.moon_type, &MoonType::ALL, step)}
+        5 => {filter.unexplored_within = step_setting(filter.unexplored_within, &[1, 2, 3, 4, 5], step)}
+        _ => {}
+    }
+}
+
+/// Steps a setting through off and then each of `options`, wrapping around. Settings that
+/// aren't one of the options, say from a hand written preset, step as if they were off.
+fn step_setting<T: Copy + PartialEq>(current: Option<T>, options: &[T], step: isize) -> Option<T> {
+    if step == 0 {
+        return None
+    }
+    let len = options.len() as isize + 1;
+    let index = current.and_then(|value| options.iter().position(|option| *option == value)).map_or(0, |i| i as isize + 1);
+    match (index + step).rem_euclid(len) {
+        0 => None,
+        i => Some(options[i as usize - 1]),
+    }
+}
+
+fn filter_row_text(filter: &Filter, row: usize) -> (&'static str, String) {
+    fn show<T>(value: Option<T>, text: impl Fn(T) -> String) -> String {
+        value.map_or("Any".to_string(), text)
+    }
+    match row {
+        0 => {("Has Planet Class", show(filter.planet_class, |class| format!("{:?}", class)))}
+        1 => {("More Planets Than", show(filter.more_planets_than, |count| count.to_string()))}
+        2 => {("Star Mass At Least", show(filter.min_star_mass, |mass| format!("{} solar masses", mass)))}
+        3 => {("Star Mass At Most", show(filter.max_star_mass, |mass| format!("{} solar masses", mass)))}
+        4 => {("Has Moon Type", show(filter.moon_type, |moon_type| format!("{:?}", moon_type)))}
+        _ => {("Unexplored Within", show(filter.unexplored_within, |hops| format!("{} hops of an explored system", hops)))}
+    }
+}
+
+fn save_filters(game_data: &mut GameData, done: String) {
+    match &game_data.config.path {
+        None => {game_data.notify("Nowhere to save presets to, they only last until Rim closes".to_string())}
+        Some(path) => {
+            match config::save_section(path, "filters", &game_data.config.filters) {
+                Ok(()) => {game_data.notify(done)}
+                Err(err) => {game_data.notify(err)}
+            }
+        }
+    }
+}
+
+fn draw_filter_panel(d: &mut RaylibDrawHandle, game_data: &GameData, panel: &FilterPanel, num_matches: usize) {
+    let font_size = hud_font(d, game_data, game_data.config.hud.small_font_size);
+    let margin = font_size;
+    let line_height = font_size * 3 / 2;
+    let text_color = Color::new(200, 200, 200, 255);
+    let presets: Vec<&String> = game_data.config.filters.keys().collect();
+    let mut rows: Vec<(String, String)> = (0..FILTER_CRITERIA)
+        .map(|row| {
+            let (label, value) = filter_row_text(&game_data.filter, row);
+            (label.to_string(), value)
+        })
+        .collect();
+    rows.push(("Clear All".to_string(), String::new()));
+    rows.push(("Save As Preset...".to_string(), String::new()));
+    rows.extend(presets.iter().map(|name| ("Preset".to_string(), name.to_string())));
+
+    let label_width = rows.iter().map(|(label, _)| d.measure_text(label, font_size)).max().unwrap_or(0);
+    let value_width = rows.iter().map(|(_, value)| d.measure_text(value, font_size)).max().unwrap_or(0);
+    let width = (label_width + value_width + margin * 3).max(d.measure_text(FILTER_HELP, font_size * 3 / 4) + margin * 2);
+    let height = margin * 5 + rows.len() as i32 * line_height + if panel.naming.is_some() { line_height * 2 } else { 0 };
+    d.draw_rectangle(margin / 2, margin / 2, width, height, Color::BLACK.alpha(0.7));
+
+    let status = if game_data.filter.is_empty() { "FILTERS".to_string() } else { format!("FILTERS ({} systems)", num_matches) };
+    d.draw_text(&status, margin, margin, font_size * 3 / 2, text_color);
+    d.draw_text(FILTER_HELP, margin, margin + font_size * 2, font_size * 3 / 4, text_color.alpha(0.6));
+    let mut y = margin * 4;
+    for (row, (label, value)) in rows.iter().enumerate() {
+        if row == panel.selected {
+            d.draw_rectangle(margin / 2, y - font_size / 4, width, line_height, Color::POWDERBLUE.alpha(0.2));
+        }
+        d.draw_text(label, margin, y, font_size, text_color);
+        d.draw_text(value, margin * 2 + label_width, y, font_size, text_color);
+        y += line_height;
+    }
+    if let Some(field) = &panel.naming {
+        field.draw(d, margin, y + font_size / 2, width - margin, font_size);
+    }
+}
+
+/// What the map highlights and colours systems by. Both go over the whole galaxy, so
+/// they're kept between frames and only worked out again when what they came from changes.
+struct MapCache {
+    filter: Filter,
+    perspective: Perspective,
+    metric: Option<Metric>,
+    home: Option<SystemId>,
+    /// None when the filter is empty.
+    matches: Option<HashSet<SystemId>>,
+    heatmap: Option<Heatmap>,
+}
+
+fn refresh_map_cache(game_data: &mut GameData) {
+    let fresh = game_data.map_cache.as_ref().is_some_and(|cache| {
+        cache.filter == game_data.filter && cache.perspective == game_data.perspective
+            && cache.metric == game_data.heatmap && cache.home == game_data.home
+    });
+    if fresh {
+        return
+    }
+    let (galaxy, filter) = (&game_data.galaxy, &game_data.filter);
+    game_data.map_cache = Some(MapCache {
+        filter: filter.clone(),
+        perspective: game_data.perspective,
+        metric: game_data.heatmap,
+        home: game_data.home,
+        matches: (!filter.is_empty()).then(|| filter.matching(galaxy, game_data.perspective)),
+        heatmap: game_data.heatmap.map(|metric| Heatmap::new(galaxy, metric, game_data.home)),
+    });
+}
+
+fn draw_map_view(rl: &mut RaylibHandle, thread: &RaylibThread, camera: &Camera3D, game_data: & GameData, hud_text: bool, overlay: Option<&MapOverlay>) {
+
+    let cache = game_data.map_cache.as_ref();
+    let matches = cache.and_then(|cache| cache.matches.as_ref());
+    let heatmap = cache.and_then(|cache| cache.heatmap.as_ref());
+    let mut d = rl.begin_drawing(thread);
+    d.clear_background(Color::BLACK);
+    {
+        let mut d3 = d.begin_mode3D(camera);
+        for star in &game_data.stars {
+            d3.draw_point3D(star, Color::WHITE);
+        }
+        draw_systems(&mut d3, camera, game_data, matches, heatmap);
+    }
+
+    // The pause menu stands in for the HUD
+    if game_data.pause.is_some() {
+        draw_pause_menu(&mut d, game_data);
+        draw_notice(&mut d, game_data);
+        return
+    }
+    if !hud_text {return}
+    draw_notice(&mut d, game_data);
+    match overlay {
+        None => {}
+        Some(MapOverlay::Search(search_box)) => {
+            draw_search_box(&mut d, game_data, search_box);
+            return
+        }
+        Some(MapOverlay::Filters(panel)) => {
+            draw_filter_panel(&mut d, game_data, panel, matches.map_or(0, HashSet::len));
+            return
+        }
+        Some(MapOverlay::NameParty(field)) => {
+            let font_size = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+            let width = (d.get_screen_width() / 3).max(font_size * 16);
+            let x = (d.get_screen_width() - width) / 2;
+            field.draw(&mut d, x, font_size, width, font_size);
+            return
+        }
+    }
+    if let Some(heatmap) = heatmap {
+        draw_heatmap_legend(&mut d, game_data, heatmap);
+    }
+    if let Some(matches) = matches {
+        let text = format!("Filter: {} ({} systems)", game_data.filter.describe(), matches.len());
+        let small_font = hud_font(&d, game_data, game_data.config.hud.small_font_size);
+        let width = d.measure_text(&text, small_font);
+        d.draw_text(&text, d.get_screen_width() - width - small_font / 2, d.get_screen_height() - small_font * 2, small_font, Color::SKYBLUE.alpha(0.8));
+    }
+    let font_size = hud_font(&d, game_data, game_data.config.hud.font_size);
+    match game_data.hovered {
         None => {
             let string = match game_data.focused {
                 Some(focus) => {
                     d.draw_rectangle(0, 0, d.get_screen_width(), d.get_screen_height(), Color::BLACK.alpha(0.2));
-                    game_data.galaxy.systems[focus].get_hover_string()
+                    hover_text(game_data, focus)
                 },
                 None => {"".to_string()}
             };
-            d.draw_text(&string.lines().take(MAX_LINES_MAP_HUD).collect::<Vec<_>>().join("\n")
-                , 15, 10, 30, Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.8));
-            d.draw_text(&string, d.get_screen_width() - 500, 30 - d.get_screen_height(), 30, Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.8))
+            draw_text_columns(&mut d, &string, font_size, game_data.config.hud.max_lines, Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.8));
         },
         Some(i) => {
             d.draw_rectangle(0, 0, d.get_screen_width(), d.get_screen_height(), Color::BLACK.alpha(0.1));
-            d.draw_text(&game_data.galaxy.systems[i].get_hover_string().lines().take(MAX_LINES_MAP_HUD).collect::<Vec<_>>().join("\n")
-                , 15, 10, 30, Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.5));
-            d.draw_text(&game_data.galaxy.systems[i].get_hover_string(), d.get_screen_width() - 500, 30
-                - d.get_screen_height(), 30, Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.5))
+            draw_text_columns(&mut d, &hover_text(game_data, i), font_size, game_data.config.hud.max_lines,
+                Color::new(200, 200, 200, (camera.fovy * 5.0) as u8).alpha(0.5));
         }
     }
 }
 
-const MAX_LINES_MAP_HUD: usize = 33;
+/// Draws the systems and lanes. Systems outside `highlighted` are dimmed and those in it ringed,
+/// unless it's None. A heatmap replaces the usual colours of everything but the hovered system.
+fn draw_systems(
+    d3: &mut impl RaylibDraw3D,
+    camera: &Camera3D,
+    game_data: &GameData,
+    highlighted: Option<&HashSet<SystemId>>,
+    heatmap: Option<&Heatmap>
+    ) {
+    let mut skipped_systems: Vec<SystemId> = Vec::new();
+    for system in game_data.galaxy.systems() {
+        let i = system.id();
+        let (size, mut color) = system.map_style(game_data.hovered, game_data.galaxy.is_explored(i, game_data.perspective));
+        if let Some(heatmap) = heatmap && game_data.hovered != Some(i) {
+            color = heatmap.color(i);
+        }
+        let mut highlight_all_connections = false;
+        let mut connection_color = Color::new(255, 255, 255, 40);
+        match game_data.focused {
+            Some(focus) if i == focus => {
+                color = Color::YELLOW; 
+                highlight_all_connections = true; 
+                connection_color = Color::new(255, 255, 0, 100);
+                skipped_systems.push(i);
+            }
+            _ => {}
+        }
+        match game_data.hovered {
+            Some(hovered) if i == hovered => {
+                highlight_all_connections = true; 
+                connection_color = Color::new(150, 150, 255, 100);
+                skipped_systems.push(i);
+            }
+            _ => {}
+        }
+        let mut fade = 1.0;
+        match highlighted {
+            None => {}
+            Some(highlighted) => {
+                if highlighted.contains(&i) {
+                    d3.draw_sphere(system.position, size * 2.0, Color::SKYBLUE.alpha(0.25));
+                }
+                else {
+                    fade = 0.15;
+                }
+            }
+        }
+        d3.draw_sphere(system.position, size, color.alpha(fade * camera.fovy / 50.0));
+        d3.draw_sphere(system.position, size * 1.2, color.alpha(fade * 0.5));
+        // Draw connections
+        for &conn_idx in &system.connections {
+            if (conn_idx > i || highlight_all_connections) && !skipped_systems.contains(&conn_idx) {
+                let conn = &game_data.galaxy[conn_idx];
+                d3.draw_line_3D(
+                    system.position,
+                    conn.position,
+                    connection_color
+                );
+            }
+        }
+    }
+    // The plotted route, over the lanes it follows
+    for pair in game_data.route.windows(2) {
+        if let (Some(a), Some(b)) = (game_data.galaxy.get(pair[0]), game_data.galaxy.get(pair[1]))
+            && a.connections.contains(&b.id()) {
+            d3.draw_line_3D(a.position, b.position, Color::ORANGE);
+        }
+    }
+}
+
+/// The heatmap's name, colour ramp and range in the bottom right corner, above the filter summary.
+fn draw_heatmap_legend(d: &mut RaylibDrawHandle, game_data: &GameData, heatmap: &Heatmap) {
+    let font_size = hud_font(d, game_data, game_data.config.hud.small_font_size);
+    let margin = font_size / 2;
+    let bar_width = font_size * 12;
+    let bar_height = font_size * 2 / 3;
+    let x = d.get_screen_width() - bar_width - margin;
+    let mut y = d.get_screen_height() - font_size * 8 - bar_height;
+    let text_color = Color::new(200, 200, 200, 220);
+    d.draw_rectangle(x - margin, y - margin, bar_width + margin * 2, font_size * 5 + bar_height + margin, Color::BLACK.alpha(0.6));
+    d.draw_text(heatmap.metric.label(), x, y, font_size, text_color);
+    y += font_size * 3 / 2;
+
+    if heatmap.is_empty() {
+        let hint = match heatmap.metric {
+            Metric::DistanceFromHome => {format!("Set a home system with {}", game_data.input.describe(Action::SetHome))}
+            _ => {"No systems scanned yet".to_string()}
+        };
+        d.draw_text(&hint, x, y, font_size, text_color);
+        return
+    }
+    const STEPS: i32 = 32;
+    for step in 0..STEPS {
+        let left = x + bar_width * step / STEPS;
+        let right = x + bar_width * (step + 1) / STEPS;
+        d.draw_rectangle(left, y, right - left, bar_height, heatmap::ramp(step as f32 / (STEPS - 1) as f32));
+    }
+    y += bar_height + 4;
+    let max = heatmap.metric.format(heatmap.max);
+    d.draw_text(&heatmap.metric.format(heatmap.min), x, y, font_size, text_color);
+    d.draw_text(&max, x + bar_width - d.measure_text(&max, font_size), y, font_size, text_color);
+    y += font_size * 3 / 2;
+    if let Some(hovered) = game_data.hovered {
+        let value = heatmap.value(hovered).map_or("None".to_string(), |value| heatmap.metric.format(value));
+        d.draw_text(&format!("System {:X}: {}", game_data.galaxy[hovered].name, value), x, y, font_size, text_color);
+    }
+}
+
+/// Undoes or redoes the last edit when asked to.
+/// Returns whether the galaxy changed, so callers can drop selections that no longer exist.
+fn handle_undo_redo(rl: &RaylibHandle, game_data: &mut GameData) -> bool {
+    if game_data.input.pressed(rl, Action::Redo) {
+        game_data.history.redo(&mut game_data.galaxy)
+    }
+    else if game_data.input.pressed(rl, Action::Undo) {
+        game_data.history.undo(&mut game_data.galaxy)
+    }
+    else {
+        false
+    }
+}
 
 
 struct GameData {
     state: GameState,
     galaxy: Galaxy,
-    hovered: Option<usize>,
-    focused: Option<usize>,
+    history: History,
+    hovered: Option<SystemId>,
+    focused: Option<SystemId>,
     stars: Vec<Vector3>,
-    orbit_angle: Option<f32>
+    map_orbit: MapOrbit,
+    save_path: String,
+    notice: Option<(String, f32)>,
+    config: Config,
+    input: Input,
+    /// Highlights systems on the map. Empty shows them all as usual.
+    filter: Filter,
+    /// What the map is coloured by, None for the usual colours.
+    heatmap: Option<Metric>,
+    /// The system distances are measured from on the lanes-from-home heatmap.
+    home: Option<SystemId>,
+    /// The planet shown in the planet view, within the focused system.
+    focused_planet: Option<usize>,
+    /// The JSON API, when it's turned on in the config.
+    server: Option<Server>,
+    /// The shared session this rim is hosting or has joined, if any.
+    session: Option<Session>,
+    /// Systems along the plotted route, starting from home. Empty when there isn't one.
+    route: Vec<SystemId>,
+    /// Whose knowledge the map shows.
+    perspective: Perspective,
+    /// The party, by index in `Galaxy::parties`, that systems entered and planets looked at count for.
+    party: usize,
+    /// Looks out for the save being changed by something else. None in a joined session.
+    watcher: Option<SaveWatcher>,
+    /// `History::edits` when the galaxy last matched the save, to tell if there's anything to lose
+    /// by reloading it. None when it hasn't matched it yet, like after restoring from a crash.
+    saved_edits: Option<usize>,
+    /// The map's filter matches and heatmap, None when the galaxy has changed since.
+    map_cache: Option<MapCache>,
+    /// The pause menu, when it's open over the current view.
+    pause: Option<PauseMenu>,
+    /// Set by Quit on the pause menu to end the game loop as if the window was closed.
+    quit: bool
+}
+
+/// Where the map camera is looking from. Kept in `GameData` so the view is the same
+/// after visiting a system or the editor.
+#[derive(Clone)]
+struct MapOrbit {
+    /// Angle around the galaxy's axis.
+    yaw: f32,
+    /// Angle above the equator, clamped short of the poles.
+    pitch: f32,
+    /// Where the camera looks when no system is focused.
+    pan: Vector3,
+    fov: f32,
+    auto_rotate: bool,
+}
+
+impl MapOrbit {
+    fn new(fov: f32) -> MapOrbit {
+        MapOrbit { yaw: 0.0, pitch: 0.0, pan: Vector3::zero(), fov, auto_rotate: true }
+    }
+
+    /// Unit vector from the camera's target to the camera.
+    fn direction(&self) -> Vector3 {
+        Vector3::new(self.yaw.cos() * self.pitch.cos(), self.pitch.sin(), self.yaw.sin() * self.pitch.cos())
+    }
+}
+
+impl GameData {
+    /// Players see a redacted galaxy, from a player copy or a host, but can't change it.
+    fn read_only(&self) -> bool {
+        self.galaxy.is_redacted() || matches!(self.session, Some(Session::Player(_)))
+    }
+
+    /// Shows a short message at the bottom of the screen for a few seconds.
+    fn notify(&mut self, text: String) {
+        self.notice = Some((text, NOTICE_SECONDS));
+    }
+
+    fn tick_notice(&mut self, dt: f32) {
+        if let Some((_, remaining)) = &mut self.notice {
+            *remaining -= dt;
+            if *remaining <= 0.0 {
+                self.notice = None;
+            }
+        }
+    }
+}
+
+const NOTICE_SECONDS: f32 = 4.0;
+
+fn draw_notice(d: &mut RaylibDrawHandle, game_data: &GameData) {
+    if let Some((text, remaining)) = &game_data.notice {
+        let alpha = remaining.min(1.0);
+        let font_size = hud_font(d, game_data, game_data.config.hud.small_font_size);
+        d.draw_text(text, font_size / 2, d.get_screen_height() - font_size * 2, font_size, Color::new(200, 200, 200, 255).alpha(alpha));
+    }
+}
+
+fn hud_font(rl: &RaylibHandle, game_data: &GameData, size: i32) -> i32 {
+    scaled_font(rl, &game_data.config.window, size)
+}
+
+/// Font size for HUD text, scaled up on HiDPI monitors and by the configured UI scale.
+pub(crate) fn scaled_font(rl: &RaylibHandle, window: &WindowConfig, size: i32) -> i32 {
+    let dpi = if window.hidpi { rl.get_window_scale_dpi().x.max(1.0) } else { 1.0 };
+    (size as f32 * dpi * window.ui_scale).round() as i32
+}
+
+/// Draws text from the top left, moving lines that don't fit the window height, or
+/// go past `max_lines` if it isn't 0, into extra columns to the right.
+fn draw_text_columns(d: &mut RaylibDrawHandle, text: &str, font_size: i32, max_lines: usize, color: Color) {
+    let margin = font_size / 2;
+    // draw_text leaves a couple of pixels between lines
+    let line_height = font_size + 2;
+    let mut lines_per_column = ((d.get_screen_height() - 2 * margin) / line_height).max(1) as usize;
+    if max_lines > 0 {
+        lines_per_column = lines_per_column.min(max_lines);
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut x = margin;
+    for column in lines.chunks(lines_per_column) {
+        if x >= d.get_screen_width() {
+            break
+        }
+        d.draw_text(&column.join("\n"), x, margin, font_size, color);
+        x += column.iter().map(|line| d.measure_text(line, font_size)).max().unwrap_or(0) + 2 * margin;
+    }
+}
+
+/// Draws text in the top right corner, far enough in that the longest line fits.
+fn draw_text_right(d: &mut RaylibDrawHandle, text: &str, font_size: i32, color: Color) {
+    let margin = font_size / 2;
+    let width = text.lines().map(|line| d.measure_text(line, font_size)).max().unwrap_or(0);
+    d.draw_text(text, (d.get_screen_width() - width - margin).max(margin), margin, font_size, color);
+}
+
+/// Writes the lane graph in every export format next to the save file.
+fn export_graphs(game_data: &mut GameData) {
+    let mut written = Vec::new();
+    for format in GraphFormat::ALL {
+        let path = file_generator::export_path(&game_data.save_path, format.extension());
+        match file_generator::export_graph(&path, &game_data.galaxy, format) {
+            Ok(()) => {written.push(path)}
+            Err(err) => {
+                game_data.notify(format!("Could not write {}: {}", path, err));
+                return
+            }
+        }
+    }
+    game_data.notify(format!("Exported lane graph to {}", written.join(", ")));
+}
+
+fn export_player_copy(game_data: &mut GameData) {
+    let path = file_generator::export_path(&game_data.save_path, "player.rim");
+    let hide_lanes = game_data.config.export.hide_unknown_lanes;
+    match file_generator::export_player_copy(&path, &game_data.galaxy, game_data.perspective, hide_lanes) {
+        Ok(()) => {game_data.notify(format!("Exported a player copy to {}", path))}
+        Err(err) => {game_data.notify(format!("Could not write {}: {}", path, err))}
+    }
+}
+
+fn export_svg(game_data: &mut GameData, path: String, svg: String) {
+    match file_generator::export_svg(&path, svg) {
+        Ok(()) => {game_data.notify(format!("Exported SVG to {}", path))}
+        Err(err) => {game_data.notify(format!("Could not write {}: {}", path, err))}
+    }
 }
 
 fn get_stars(num_stars: usize, starfield_radius: f32) -> Vec<Vector3> {
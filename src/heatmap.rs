@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use raylib::prelude::Color;
+
+use crate::map::{Galaxy, StarSystem, SystemId};
+
+/// Something about each system the map can be coloured by instead of its usual colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Metric {
+    Planets,
+    Moons,
+    StarMass,
+    Habitability,
+    /// Lanes between the system and the home system.
+    DistanceFromHome,
+    /// How many lanes the system has.
+    LaneDegree,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::Planets, Metric::Moons, Metric::StarMass, Metric::Habitability, Metric::DistanceFromHome, Metric::LaneDegree,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Metric::Planets => "Planets",
+            Metric::Moons => "Moons",
+            Metric::StarMass => "Star Mass (solar masses)",
+            Metric::Habitability => "Habitability",
+            Metric::DistanceFromHome => "Lanes From Home",
+            Metric::LaneDegree => "Lanes",
+        }
+    }
+
+    /// The metric after this one, going back to the normal colours (None) after the last.
+    pub fn cycle(current: Option<Metric>) -> Option<Metric> {
+        match current {
+            None => Some(Metric::ALL[0]),
+            Some(metric) => {
+                let i = Metric::ALL.iter().position(|m| *m == metric).unwrap();
+                Metric::ALL.get(i + 1).copied()
+            }
+        }
+    }
+
+    pub fn format(&self, value: f32) -> String {
+        match self {
+            Metric::StarMass | Metric::Habitability => format!("{:.2}", value),
+            _ => format!("{:.0}", value),
+        }
+    }
+
+    /// None for systems the metric doesn't apply to, like unscanned ones for planet counts.
+    fn value(&self, system: &StarSystem, home_hops: &HashMap<SystemId, usize>) -> Option<f32> {
+        let data = system.system_data.as_ref();
+        match self {
+            Metric::Planets => data.map(|data| data.planets.len() as f32),
+            Metric::Moons => data.map(|data| data.planets.iter().map(|planet| planet.moons.len()).sum::<usize>() as f32),
+            Metric::StarMass => data.map(|data| data.star_solar_masses() as f32),
+            Metric::Habitability => data.map(|data| data.habitability() as f32),
+            Metric::DistanceFromHome => home_hops.get(&system.id()).map(|hops| *hops as f32),
+            Metric::LaneDegree => Some(system.connections.len() as f32),
+        }
+    }
+}
+
+/// A metric worked out for every system, with the range it spans.
+pub(crate) struct Heatmap {
+    pub metric: Metric,
+    values: HashMap<SystemId, f32>,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// Colour for systems the metric has no value for.
+pub(crate) const NO_VALUE: Color = Color::new(45, 45, 45, 255);
+
+impl Heatmap {
+    /// `home` is only needed for `Metric::DistanceFromHome`.
+    pub fn new(galaxy: &Galaxy, metric: Metric, home: Option<SystemId>) -> Heatmap {
+        let home_hops = match (metric, home) {
+            (Metric::DistanceFromHome, Some(home)) if galaxy.contains(home) => {galaxy.hops_from([home])}
+            _ => {HashMap::new()}
+        };
+        let values: HashMap<SystemId, f32> = galaxy.systems().iter()
+            .filter_map(|system| metric.value(system, &home_hops).map(|value| (system.id(), value)))
+            .collect();
+        let min = values.values().copied().fold(f32::INFINITY, f32::min);
+        let max = values.values().copied().fold(f32::NEG_INFINITY, f32::max);
+        Heatmap { metric, values, min, max }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn value(&self, id: SystemId) -> Option<f32> {
+        self.values.get(&id).copied()
+    }
+
+    pub fn color(&self, id: SystemId) -> Color {
+        match self.value(id) {
+            None => {NO_VALUE}
+            Some(value) => {
+                let t = if self.max > self.min { (value - self.min) / (self.max - self.min) } else { 0.5 };
+                ramp(t)
+            }
+        }
+    }
+}
+
+/// The colour ramp heatmaps are drawn with, from deep blue at 0 through teal to yellow at 1.
+pub(crate) fn ramp(t: f32) -> Color {
+    const STOPS: [(f32, [f32; 3]); 4] = [
+        (0.0, [40.0, 30.0, 120.0]),
+        (0.35, [30.0, 120.0, 170.0]),
+        (0.7, [60.0, 190.0, 110.0]),
+        (1.0, [250.0, 230.0, 60.0]),
+    ];
+    let t = t.clamp(0.0, 1.0);
+    let upper = STOPS.iter().position(|(at, _)| *at >= t).unwrap_or(STOPS.len() - 1).max(1);
+    let (start, from) = STOPS[upper - 1];
+    let (end, to) = STOPS[upper];
+    let f = (t - start) / (end - start);
+    let channel = |i: usize| (from[i] + (to[i] - from[i]) * f).round() as u8;
+    Color::new(channel(0), channel(1), channel(2), 255)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rim::system::{MoonType, PlanetClass, StarSystemData, SOLAR_MASS};
+    use serde_json::json;
+
+    fn data(solar_masses: f64, planets: &[(PlanetClass, &[MoonType])]) -> StarSystemData {
+        let mut data = StarSystemData { star_mass: solar_masses * SOLAR_MASS, planets: Vec::new() };
+        for (class, moons) in planets {
+            let planet = data.add_planet(*class);
+            let planet = &mut data.planets[planet];
+            planet.moons.clear();
+            for moon in moons.iter() {
+                planet.add_moon(*moon);
+            }
+        }
+        data
+    }
+
+    /// Systems 0 to 3 joined in a line, 2 unscanned, and 4 unscanned off on its own.
+    fn galaxy() -> Galaxy {
+        let systems: [(Option<StarSystemData>, &[u64]); 5] = [
+            (Some(data(1.0, &[(PlanetClass::Terran, &[MoonType::SubsurfaceOcean, MoonType::Asteroid])])), &[1]),
+            (Some(data(2.0, &[(PlanetClass::GasGiant, &[]), (PlanetClass::Desert, &[])])), &[0, 2]),
+            (None, &[1, 3]),
+            (Some(data(0.5, &[])), &[2]),
+            (None, &[]),
+        ];
+        let systems: Vec<_> = systems.into_iter().enumerate().map(|(i, (data, connections))| json!({
+            "id": i, "position": [0.0, 0.0, 0.0], "origin": [0.0, 0.0, 0.0], "drift_direction": [0.0, 0.0, 0.0],
+            "connections": connections, "name": i, "system_data": data,
+        })).collect();
+        serde_json::from_value(json!({"version": 2, "systems": systems})).unwrap()
+    }
+
+    /// Colours by their channels, since comparing them directly goes through raylib.
+    fn rgba(color: Color) -> [u8; 4] {
+        [color.r, color.g, color.b, color.a]
+    }
+
+    fn values(heatmap: &Heatmap) -> Vec<Option<f32>> {
+        (0..5).map(|i| heatmap.value(SystemId(i))).collect()
+    }
+
+    #[test]
+    fn metrics_are_measured_per_system() {
+        let galaxy = galaxy();
+        let values = |metric| values(&Heatmap::new(&galaxy, metric, None));
+        assert_eq!(values(Metric::Planets), [Some(1.0), Some(2.0), None, Some(0.0), None]);
+        assert_eq!(values(Metric::Moons), [Some(2.0), Some(0.0), None, Some(0.0), None]);
+        assert_eq!(values(Metric::StarMass), [Some(1.0), Some(2.0), None, Some(0.5), None]);
+        assert_eq!(values(Metric::Habitability), [Some(1.25), Some(0.45), None, Some(0.0), None]);
+        assert_eq!(values(Metric::LaneDegree), [Some(1.0), Some(2.0), Some(2.0), Some(1.0), Some(0.0)]);
+    }
+
+    #[test]
+    fn distance_counts_lanes_from_home() {
+        let galaxy = galaxy();
+        let heatmap = Heatmap::new(&galaxy, Metric::DistanceFromHome, Some(SystemId(1)));
+        assert_eq!(values(&heatmap), [Some(1.0), Some(0.0), Some(1.0), Some(2.0), None]);
+        assert_eq!((heatmap.min, heatmap.max), (0.0, 2.0));
+        // Without a home there's nothing to measure from
+        assert!(Heatmap::new(&galaxy, Metric::DistanceFromHome, None).is_empty());
+        assert!(Heatmap::new(&galaxy, Metric::DistanceFromHome, Some(SystemId(9))).is_empty());
+    }
+
+    #[test]
+    fn colours_span_the_range() {
+        let galaxy = galaxy();
+        let heatmap = Heatmap::new(&galaxy, Metric::StarMass, None);
+        assert_eq!((heatmap.min, heatmap.max), (0.5, 2.0));
+        assert_eq!(rgba(heatmap.color(SystemId(3))), rgba(ramp(0.0)));
+        assert_eq!(rgba(heatmap.color(SystemId(1))), rgba(ramp(1.0)));
+        assert_eq!(rgba(heatmap.color(SystemId(0))), rgba(ramp(1.0 / 3.0)));
+        assert_eq!(rgba(heatmap.color(SystemId(2))), rgba(NO_VALUE));
+        // Everything the same is the middle of the ramp
+        let home = Heatmap::new(&galaxy, Metric::DistanceFromHome, Some(SystemId(4)));
+        assert_eq!((home.min, home.max), (0.0, 0.0));
+        assert_eq!(rgba(home.color(SystemId(4))), rgba(ramp(0.5)));
+    }
+
+    #[test]
+    fn ramp_runs_from_blue_to_yellow() {
+        assert_eq!(rgba(ramp(0.0)), rgba(Color::new(40, 30, 120, 255)));
+        assert_eq!(rgba(ramp(0.35)), rgba(Color::new(30, 120, 170, 255)));
+        assert_eq!(rgba(ramp(1.0)), rgba(Color::new(250, 230, 60, 255)));
+        assert_eq!(rgba(ramp(-1.0)), rgba(ramp(0.0)));
+        assert_eq!(rgba(ramp(2.0)), rgba(ramp(1.0)));
+    }
+
+    #[test]
+    fn metrics_cycle_back_to_normal_colours() {
+        let mut metric = None;
+        for expected in Metric::ALL {
+            metric = Metric::cycle(metric);
+            assert_eq!(metric, Some(expected));
+        }
+        assert_eq!(Metric::cycle(metric), None);
+    }
+}
@@ -0,0 +1,404 @@
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Serialize, Deserialize};
+
+use crate::map::{Galaxy, StarSystem, SystemId};
+use crate::party::Party;
+use crate::system::StarSystemData;
+use crate::vector::Vec3;
+
+/// A single reversible change to a `Galaxy`. Every mutation made by the user goes
+/// through one of these so it can be undone, redone and written to the journal.
+/// Edits that involve randomness store their result rather than how to produce it,
+/// so redoing them always gives back exactly what was undone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Edit {
+    /// `index` is where the system sits in `Galaxy::systems`, so undoing a removal puts
+    /// it back in the same place.
+    AddSystem { index: usize, system: StarSystem },
+    RemoveSystem { index: usize, system: StarSystem },
+    MoveSystem {
+        id: SystemId,
+        from: Vec3,
+        to: Vec3
+    },
+    Connect { a: SystemId, b: SystemId },
+    Disconnect { a: SystemId, b: SystemId },
+    /// `scans` moves parties' scans along with planets that were added, removed or reordered.
+    SetSystemData {
+        id: SystemId,
+        before: Option<StarSystemData>,
+        after: Option<StarSystemData>,
+        #[serde(default)]
+        scans: Vec<ScanRenumbering>
+    },
+    /// `party` is the party's index in `Galaxy::parties`. The times are when the party
+    /// explored the system, None for not having explored it.
+    SetExplored { party: usize, id: SystemId, before: Option<u64>, after: Option<u64> },
+    SetScanned { party: usize, id: SystemId, planet: usize, before: Option<u64>, after: Option<u64> },
+    AddParty { index: usize, party: Party },
+    SetNotes { id: SystemId, before: String, after: String },
+    SetTags { id: SystemId, before: Vec<String>, after: Vec<String> },
+}
+
+impl Edit {
+    pub fn apply(&self, galaxy: &mut Galaxy) {
+        match self {
+            Edit::AddSystem { index, system } => {galaxy.insert_system(*index, system.clone())}
+            Edit::RemoveSystem { system, .. } => {galaxy.remove_system(system.id());}
+            Edit::MoveSystem { id, to, .. } => {galaxy.move_system(*id, *to)}
+            Edit::Connect { a, b } => {galaxy.connect(*a, *b)}
+            Edit::Disconnect { a, b } => {galaxy.disconnect(*a, *b)}
+            Edit::SetSystemData { id, after, scans, .. } => {
+                galaxy[*id].system_data = after.clone();
+                for scan in scans {
+                    set_planet_scans(galaxy, scan.party, *id, &scan.after);
+                }
+            }
+            Edit::SetExplored { party, id, after, .. } => {set_explored(galaxy, *party, *id, *after)}
+            Edit::SetScanned { party, id, planet, after, .. } => {set_scanned(galaxy, *party, *id, *planet, *after)}
+            Edit::AddParty { index, party } => {galaxy.parties_mut().insert(*index, party.clone())}
+            Edit::SetNotes { id, after, .. } => {galaxy[*id].notes = after.clone()}
+            Edit::SetTags { id, after, .. } => {galaxy[*id].tags = after.clone()}
+        }
+    }
+
+    /// The systems the edit changes, for telling listeners what to refresh.
+    pub fn systems(&self) -> Vec<SystemId> {
+        match self {
+            Edit::AddSystem { system, .. } | Edit::RemoveSystem { system, .. } => {vec![system.id()]}
+            Edit::Connect { a, b } | Edit::Disconnect { a, b } => {vec![*a, *b]}
+            Edit::MoveSystem { id, .. } | Edit::SetSystemData { id, .. } | Edit::SetExplored { id, .. }
+                | Edit::SetScanned { id, .. } | Edit::SetNotes { id, .. } | Edit::SetTags { id, .. } => {vec![*id]}
+            Edit::AddParty { .. } => {Vec::new()}
+        }
+    }
+
+    pub fn revert(&self, galaxy: &mut Galaxy) {
+        match self {
+            Edit::AddSystem { system, .. } => {galaxy.remove_system(system.id());}
+            Edit::RemoveSystem { index, system } => {galaxy.insert_system(*index, system.clone())}
+            Edit::MoveSystem { id, from, .. } => {galaxy.move_system(*id, *from)}
+            Edit::Connect { a, b } => {galaxy.disconnect(*a, *b)}
+            Edit::Disconnect { a, b } => {galaxy.connect(*a, *b)}
+            Edit::SetSystemData { id, before, scans, .. } => {
+                galaxy[*id].system_data = before.clone();
+                for scan in scans {
+                    set_planet_scans(galaxy, scan.party, *id, &scan.before);
+                }
+            }
+            Edit::SetExplored { party, id, before, .. } => {set_explored(galaxy, *party, *id, *before)}
+            Edit::SetScanned { party, id, planet, before, .. } => {set_scanned(galaxy, *party, *id, *planet, *before)}
+            Edit::AddParty { index, .. } => {galaxy.parties_mut().remove(*index);}
+            Edit::SetNotes { id, before, .. } => {galaxy[*id].notes = before.clone()}
+            Edit::SetTags { id, before, .. } => {galaxy[*id].tags = before.clone()}
+        }
+    }
+}
+
+/// A party's scans of one system's planets, from before and after the planets were renumbered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanRenumbering {
+    party: usize,
+    before: BTreeMap<usize, u64>,
+    after: BTreeMap<usize, u64>,
+}
+
+/// Works out where each party's scans of a system go once its planets have moved. `moved` has,
+/// for each planet before, its index after, or None if it was removed.
+pub fn renumber_scans(galaxy: &Galaxy, id: SystemId, moved: &[Option<usize>]) -> Vec<ScanRenumbering> {
+    let mut scans = Vec::new();
+    for (party, planets) in galaxy.parties().iter().enumerate() {
+        let before = match planets.scanned.get(&id) {
+            None => {continue}
+            Some(before) => {before.clone()}
+        };
+        let after: BTreeMap<usize, u64> = before.iter()
+            .filter_map(|(planet, at)| moved.get(*planet).copied().flatten().map(|planet| (planet, *at)))
+            .collect();
+        if after != before {
+            scans.push(ScanRenumbering { party, before, after });
+        }
+    }
+    scans
+}
+
+/// Updates `moved`, the list `renumber_scans` takes, after a planet is taken out of `from` and
+/// put in at `to`, keeping the others in order. None for `from` is a new planet, and None for
+/// `to` a removed one.
+pub fn move_planet(moved: &mut [Option<usize>], from: Option<usize>, to: Option<usize>) {
+    for slot in moved.iter_mut() {
+        if let Some(index) = *slot {
+            if Some(index) == from {
+                *slot = to;
+                continue
+            }
+            let mut index = index;
+            if from.is_some_and(|from| index > from) {
+                index -= 1;
+            }
+            if to.is_some_and(|to| index >= to) {
+                index += 1;
+            }
+            *slot = Some(index);
+        }
+    }
+}
+
+fn set_planet_scans(galaxy: &mut Galaxy, party: usize, id: SystemId, planets: &BTreeMap<usize, u64>) {
+    let scanned = &mut galaxy.parties_mut()[party].scanned;
+    if planets.is_empty() {
+        scanned.remove(&id);
+    }
+    else {
+        scanned.insert(id, planets.clone());
+    }
+}
+
+fn set_explored(galaxy: &mut Galaxy, party: usize, id: SystemId, at: Option<u64>) {
+    let explored = &mut galaxy.parties_mut()[party].explored;
+    match at {
+        None => {explored.remove(&id);}
+        Some(at) => {explored.insert(id, at);}
+    }
+}
+
+fn set_scanned(galaxy: &mut Galaxy, party: usize, id: SystemId, planet: usize, at: Option<u64>) {
+    let scanned = &mut galaxy.parties_mut()[party].scanned;
+    match at {
+        None => {
+            if let Some(planets) = scanned.get_mut(&id) {
+                planets.remove(&planet);
+                if planets.is_empty() {
+                    scanned.remove(&id);
+                }
+            }
+        }
+        Some(at) => {scanned.entry(id).or_default().insert(planet, at);}
+    }
+}
+
+/// Undo and redo stacks for the edits made to a galaxy.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Systems changed by anything done, undone or redone since `take_changed` was last called.
+    #[serde(skip)]
+    changed: Vec<SystemId>,
+    /// How many edits have been done, undone or redone.
+    #[serde(skip)]
+    edits: usize,
+}
+
+impl History {
+    /// Applies an edit and records it, dropping anything that could have been redone.
+    pub fn apply(&mut self, galaxy: &mut Galaxy, edit: Edit) {
+        edit.apply(galaxy);
+        self.changed.extend(edit.systems());
+        self.edits += 1;
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    /// Records an edit whose effect is already in the galaxy, like the end of a drag.
+    pub fn record(&mut self, edit: Edit) {
+        self.changed.extend(edit.systems());
+        self.edits += 1;
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, galaxy: &mut Galaxy) -> bool {
+        match self.undo.pop() {
+            None => {false}
+            Some(edit) => {
+                edit.revert(galaxy);
+                self.changed.extend(edit.systems());
+                self.edits += 1;
+                self.redo.push(edit);
+                true
+            }
+        }
+    }
+
+    pub fn redo(&mut self, galaxy: &mut Galaxy) -> bool {
+        match self.redo.pop() {
+            None => {false}
+            Some(edit) => {
+                edit.apply(galaxy);
+                self.changed.extend(edit.systems());
+                self.edits += 1;
+                self.undo.push(edit);
+                true
+            }
+        }
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Counts up with every edit done, undone or redone, so comparing it with an earlier
+    /// count tells whether the galaxy has been touched since.
+    pub fn edits(&self) -> usize {
+        self.edits
+    }
+
+    /// The systems changed since the last call, each once, in the order they were first changed.
+    pub fn take_changed(&mut self) -> Vec<SystemId> {
+        let mut changed = std::mem::take(&mut self.changed);
+        let mut seen = HashSet::new();
+        changed.retain(|id| seen.insert(*id));
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Shape;
+
+    /// Every lane in the galaxy as a sorted pair of ends, as many times as either end lists it.
+    fn lanes(galaxy: &Galaxy) -> Vec<(SystemId, SystemId)> {
+        let mut lanes: Vec<(SystemId, SystemId)> = galaxy.systems().iter()
+            .flat_map(|sys| sys.connections.iter().map(|&conn| (sys.id().min(conn), sys.id().max(conn))))
+            .collect();
+        lanes.sort();
+        lanes
+    }
+
+    fn ids(galaxy: &Galaxy) -> Vec<SystemId> {
+        galaxy.systems().iter().map(StarSystem::id).collect()
+    }
+
+    /// Where each of `count` planets ends up after the moves, in order.
+    fn moves(count: usize, moves: &[(Option<usize>, Option<usize>)]) -> Vec<Option<usize>> {
+        let mut moved: Vec<Option<usize>> = (0..count).map(Some).collect();
+        for (from, to) in moves {
+            move_planet(&mut moved, *from, *to);
+        }
+        moved
+    }
+
+    #[test]
+    fn planets_move_over_for_new_ones() {
+        assert_eq!(moves(3, &[(None, Some(0))]), [Some(1), Some(2), Some(3)]);
+        assert_eq!(moves(3, &[(None, Some(1))]), [Some(0), Some(2), Some(3)]);
+        assert_eq!(moves(3, &[(None, Some(3))]), [Some(0), Some(1), Some(2)]);
+        assert!(moves(0, &[(None, Some(0))]).is_empty());
+    }
+
+    #[test]
+    fn planets_close_up_after_removals() {
+        assert_eq!(moves(3, &[(Some(0), None)]), [None, Some(0), Some(1)]);
+        assert_eq!(moves(3, &[(Some(1), None)]), [Some(0), None, Some(1)]);
+        assert_eq!(moves(3, &[(Some(2), None)]), [Some(0), Some(1), None]);
+        // The second removal is of what was planet 2, now at 1
+        assert_eq!(moves(3, &[(Some(0), None), (Some(1), None)]), [None, Some(0), None]);
+    }
+
+    #[test]
+    fn planets_move_past_each_other() {
+        assert_eq!(moves(4, &[(Some(0), Some(2))]), [Some(2), Some(0), Some(1), Some(3)]);
+        assert_eq!(moves(4, &[(Some(3), Some(1))]), [Some(0), Some(2), Some(3), Some(1)]);
+        assert_eq!(moves(4, &[(Some(2), Some(2))]), [Some(0), Some(1), Some(2), Some(3)]);
+        // A new planet, then the first one retyped to after it, then the new one removed
+        assert_eq!(moves(2, &[(None, Some(1)), (Some(0), Some(2)), (Some(0), None)]), [Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn scans_follow_their_planets() {
+        let mut galaxy = Galaxy::with_seed(9, 4, 1, 0.3, 50.0, Shape::Sphere);
+        let id = galaxy.systems()[0].id();
+        galaxy.parties_mut().push(Party::new("Second"));
+        galaxy.parties_mut()[0].scanned.insert(id, BTreeMap::from([(0, 10), (2, 20)]));
+        galaxy.parties_mut()[1].scanned.insert(id, BTreeMap::from([(1, 30)]));
+        let before: Vec<Party> = galaxy.parties().to_vec();
+
+        // Planet 0 is removed and a new one goes in at 1, after what was planet 1
+        let moved = moves(3, &[(Some(0), None), (None, Some(1))]);
+        assert_eq!(moved, [None, Some(0), Some(2)]);
+        let scans = renumber_scans(&galaxy, id, &moved);
+        let data = galaxy[id].system_data.clone();
+        let mut history = History::default();
+        history.apply(&mut galaxy, Edit::SetSystemData { id, before: data.clone(), after: data, scans });
+        assert_eq!(galaxy.parties()[0].scanned[&id], BTreeMap::from([(2, 20)]));
+        assert_eq!(galaxy.parties()[1].scanned[&id], BTreeMap::from([(0, 30)]));
+
+        assert!(history.undo(&mut galaxy));
+        assert_eq!(galaxy.parties(), &before[..]);
+        // Removing the only scanned planet leaves no scans of the system at all
+        let scans = renumber_scans(&galaxy, id, &moves(3, &[(Some(1), None)]));
+        history.apply(&mut galaxy, Edit::SetSystemData { id, before: None, after: None, scans });
+        assert!(!galaxy.parties()[1].scanned.contains_key(&id));
+        assert_eq!(galaxy.parties()[0].scanned[&id], BTreeMap::from([(0, 10), (1, 20)]));
+    }
+
+    #[test]
+    fn unmoved_scans_are_left_alone() {
+        let mut galaxy = Galaxy::with_seed(9, 4, 1, 0.3, 50.0, Shape::Sphere);
+        let id = galaxy.systems()[0].id();
+        galaxy.parties_mut()[0].scanned.insert(id, BTreeMap::from([(0, 10)]));
+        assert!(renumber_scans(&galaxy, id, &moves(2, &[(Some(1), None)])).is_empty());
+        assert!(renumber_scans(&galaxy, id, &moves(1, &[(None, Some(1))])).is_empty());
+        assert!(renumber_scans(&galaxy, galaxy.systems()[1].id(), &moves(2, &[(Some(0), None)])).is_empty());
+    }
+
+    #[test]
+    fn undoing_a_removal_puts_the_system_back() {
+        let mut galaxy = Galaxy::with_seed(7, 12, 3, 0.3, 50.0, Shape::Sphere);
+        let mut history = History::default();
+        let index = 5;
+        let system = galaxy.systems()[index].clone();
+        let id = system.id();
+        assert!(!system.connections.is_empty());
+        let (ids_before, lanes_before) = (ids(&galaxy), lanes(&galaxy));
+
+        history.apply(&mut galaxy, Edit::RemoveSystem { index, system });
+        assert!(!galaxy.contains(id));
+        assert_eq!(galaxy.len(), 11);
+        assert!(galaxy.systems().iter().all(|sys| !sys.connections.contains(&id)));
+        // Everything after it moved down one and can still be found
+        for (i, sys) in galaxy.systems().iter().enumerate() {
+            assert_eq!(galaxy.index_of(sys.id()), Some(i));
+        }
+
+        assert!(history.undo(&mut galaxy));
+        assert_eq!(galaxy.index_of(id), Some(index));
+        assert_eq!(galaxy[id].id(), id);
+        assert_eq!(ids(&galaxy), ids_before);
+        assert_eq!(lanes(&galaxy), lanes_before);
+        for &conn in &galaxy[id].connections {
+            assert!(galaxy.is_connected(conn, id));
+        }
+    }
+
+    #[test]
+    fn removals_redo_and_undo_again() {
+        let mut galaxy = Galaxy::with_seed(8, 12, 3, 0.3, 50.0, Shape::Sphere);
+        let mut history = History::default();
+        let system = galaxy.systems()[0].clone();
+        let id = system.id();
+        let (ids_before, lanes_before) = (ids(&galaxy), lanes(&galaxy));
+
+        history.apply(&mut galaxy, Edit::RemoveSystem { index: 0, system });
+        let (ids_removed, lanes_removed) = (ids(&galaxy), lanes(&galaxy));
+        assert!(history.undo(&mut galaxy));
+        assert!(history.redo(&mut galaxy));
+        assert!(!galaxy.contains(id));
+        assert_eq!(ids(&galaxy), ids_removed);
+        assert_eq!(lanes(&galaxy), lanes_removed);
+        assert!(!history.redo(&mut galaxy));
+
+        assert!(history.undo(&mut galaxy));
+        assert_eq!(galaxy.index_of(id), Some(0));
+        assert_eq!(ids(&galaxy), ids_before);
+        assert_eq!(lanes(&galaxy), lanes_before);
+        assert_eq!((history.undo_len(), history.redo_len()), (0, 1));
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use raylib::prelude::*;
+use serde::{Serialize, Deserialize};
+
+/// Something the player can do, independent of what they press to do it.
+/// The snake_case names are what the `[bindings]` config table uses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Action {
+    Select,
+    Back,
+    Confirm,
+    ZoomIn,
+    ZoomOut,
+    OrbitLeft,
+    OrbitRight,
+    PitchUp,
+    PitchDown,
+    Undo,
+    Redo,
+    ExportGraph,
+    ExportSvg,
+    ExportPlayerCopy,
+    ReloadSave,
+    OpenEditor,
+    OpenBindings,
+    Pause,
+    Search,
+    OpenFilters,
+    CycleHeatmap,
+    SetHome,
+    PlotRoute,
+    CyclePerspective,
+    AddParty,
+    Rotate,
+    Pan,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ToggleAutoRotate,
+    ResetCamera,
+    ToggleLane,
+    AddSystem,
+    DeleteSystem,
+    EditNotes,
+    EditTags,
+    AddPlanet,
+    NextPlanet,
+    PreviousPlanet,
+    RetypePlanet,
+    PlanetMassUp,
+    PlanetMassDown,
+    RemovePlanet,
+    AddMoon,
+    NextMoon,
+    PreviousMoon,
+    RetypeMoon,
+    RemoveMoon,
+}
+
+impl Action {
+    pub const ALL: [Action; 50] = [
+        Action::Select, Action::Back, Action::Confirm, Action::ZoomIn, Action::ZoomOut,
+        Action::OrbitLeft, Action::OrbitRight, Action::PitchUp, Action::PitchDown,
+        Action::Rotate, Action::Pan, Action::PanLeft, Action::PanRight, Action::PanUp, Action::PanDown,
+        Action::ToggleAutoRotate, Action::ResetCamera,
+        Action::Undo, Action::Redo, Action::ExportGraph, Action::ExportSvg, Action::ExportPlayerCopy, Action::ReloadSave, Action::OpenEditor, Action::OpenBindings, Action::Pause,
+        Action::Search, Action::OpenFilters, Action::CycleHeatmap, Action::SetHome, Action::PlotRoute, Action::CyclePerspective, Action::AddParty, Action::ToggleLane, Action::AddSystem, Action::DeleteSystem, Action::EditNotes, Action::EditTags,
+        Action::AddPlanet, Action::NextPlanet, Action::PreviousPlanet, Action::RetypePlanet,
+        Action::PlanetMassUp, Action::PlanetMassDown, Action::RemovePlanet,
+        Action::AddMoon, Action::NextMoon, Action::PreviousMoon, Action::RetypeMoon, Action::RemoveMoon,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Select => "Select",
+            Action::Back => "Back",
+            Action::Confirm => "Enter System Or Planet",
+            Action::ZoomIn => "Zoom In",
+            Action::ZoomOut => "Zoom Out",
+            Action::OrbitLeft => "Orbit Left",
+            Action::OrbitRight => "Orbit Right",
+            Action::PitchUp => "Pitch Up",
+            Action::PitchDown => "Pitch Down",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::ExportGraph => "Export Lane Graph",
+            Action::ExportSvg => "Export SVG",
+            Action::ExportPlayerCopy => "Export Player Copy",
+            Action::ReloadSave => "Reload Save From Disk",
+            Action::OpenEditor => "Open Editor",
+            Action::OpenBindings => "Open Bindings",
+            Action::Pause => "Pause Menu",
+            Action::Search => "Search Systems",
+            Action::OpenFilters => "Filter Systems",
+            Action::CycleHeatmap => "Change Map Colours",
+            Action::SetHome => "Set Home System",
+            Action::PlotRoute => "Plot Route From Home",
+            Action::CyclePerspective => "Change Party",
+            Action::AddParty => "New Party",
+            Action::Rotate => "Drag To Rotate",
+            Action::Pan => "Drag To Pan",
+            Action::PanLeft => "Pan Left",
+            Action::PanRight => "Pan Right",
+            Action::PanUp => "Pan Up",
+            Action::PanDown => "Pan Down",
+            Action::ToggleAutoRotate => "Toggle Auto-Rotate",
+            Action::ResetCamera => "Reset Camera",
+            Action::ToggleLane => "Toggle Lane",
+            Action::AddSystem => "New System",
+            Action::DeleteSystem => "Delete System",
+            Action::EditNotes => "Edit Notes",
+            Action::EditTags => "Edit Tags",
+            Action::AddPlanet => "Add Planet",
+            Action::NextPlanet => "Next Planet",
+            Action::PreviousPlanet => "Previous Planet",
+            Action::RetypePlanet => "Retype Planet",
+            Action::PlanetMassUp => "Planet Mass Up",
+            Action::PlanetMassDown => "Planet Mass Down",
+            Action::RemovePlanet => "Remove Planet",
+            Action::AddMoon => "Add Moon",
+            Action::NextMoon => "Next Moon",
+            Action::PreviousMoon => "Previous Moon",
+            Action::RetypeMoon => "Retype Moon",
+            Action::RemoveMoon => "Remove Moon",
+        }
+    }
+}
+
+/// A single physical input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Source {
+    Key(KeyboardKey),
+    Mouse(MouseButton),
+    /// true for scrolling up.
+    Wheel(bool),
+    PadButton(GamepadButton),
+    /// A stick pushed one way along an axis. true for the positive direction.
+    PadAxis(GamepadAxis, bool),
+}
+
+/// An input with the modifiers that have to be held with it, written like `Ctrl+Shift+Z`.
+/// Stored in the config as that string.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) struct Chord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub source: Source,
+}
+
+pub(crate) type Bindings = BTreeMap<Action, Vec<Chord>>;
+
+/// Names for inputs that aren't a letter, digit or function key, as written in the config.
+const NAMED_SOURCES: [(&str, Source); 56] = [
+    ("Left", Source::Key(KeyboardKey::KEY_LEFT)),
+    ("Right", Source::Key(KeyboardKey::KEY_RIGHT)),
+    ("Up", Source::Key(KeyboardKey::KEY_UP)),
+    ("Down", Source::Key(KeyboardKey::KEY_DOWN)),
+    ("Escape", Source::Key(KeyboardKey::KEY_ESCAPE)),
+    ("Enter", Source::Key(KeyboardKey::KEY_ENTER)),
+    ("Space", Source::Key(KeyboardKey::KEY_SPACE)),
+    ("Tab", Source::Key(KeyboardKey::KEY_TAB)),
+    ("Backspace", Source::Key(KeyboardKey::KEY_BACKSPACE)),
+    ("Delete", Source::Key(KeyboardKey::KEY_DELETE)),
+    ("Insert", Source::Key(KeyboardKey::KEY_INSERT)),
+    ("Home", Source::Key(KeyboardKey::KEY_HOME)),
+    ("End", Source::Key(KeyboardKey::KEY_END)),
+    ("PageUp", Source::Key(KeyboardKey::KEY_PAGE_UP)),
+    ("PageDown", Source::Key(KeyboardKey::KEY_PAGE_DOWN)),
+    ("Minus", Source::Key(KeyboardKey::KEY_MINUS)),
+    ("Equal", Source::Key(KeyboardKey::KEY_EQUAL)),
+    ("LeftBracket", Source::Key(KeyboardKey::KEY_LEFT_BRACKET)),
+    ("RightBracket", Source::Key(KeyboardKey::KEY_RIGHT_BRACKET)),
+    ("Comma", Source::Key(KeyboardKey::KEY_COMMA)),
+    ("Period", Source::Key(KeyboardKey::KEY_PERIOD)),
+    ("Slash", Source::Key(KeyboardKey::KEY_SLASH)),
+    ("Backslash", Source::Key(KeyboardKey::KEY_BACKSLASH)),
+    ("Semicolon", Source::Key(KeyboardKey::KEY_SEMICOLON)),
+    ("Apostrophe", Source::Key(KeyboardKey::KEY_APOSTROPHE)),
+    ("Grave", Source::Key(KeyboardKey::KEY_GRAVE)),
+    ("MouseLeft", Source::Mouse(MouseButton::MOUSE_BUTTON_LEFT)),
+    ("MouseRight", Source::Mouse(MouseButton::MOUSE_BUTTON_RIGHT)),
+    ("MouseMiddle", Source::Mouse(MouseButton::MOUSE_BUTTON_MIDDLE)),
+    ("WheelUp", Source::Wheel(true)),
+    ("WheelDown", Source::Wheel(false)),
+    ("PadUp", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP)),
+    ("PadDown", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN)),
+    ("PadLeft", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT)),
+    ("PadRight", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT)),
+    ("PadA", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN)),
+    ("PadB", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT)),
+    ("PadX", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_LEFT)),
+    ("PadY", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP)),
+    ("PadLB", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_1)),
+    ("PadLT", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_TRIGGER_2)),
+    ("PadRB", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_1)),
+    ("PadRT", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_TRIGGER_2)),
+    ("PadBack", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_MIDDLE_LEFT)),
+    ("PadStart", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_MIDDLE_RIGHT)),
+    ("PadHome", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_MIDDLE)),
+    ("PadLeftStick", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_LEFT_THUMB)),
+    ("PadRightStick", Source::PadButton(GamepadButton::GAMEPAD_BUTTON_RIGHT_THUMB)),
+    ("LeftStickLeft", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_LEFT_X, false)),
+    ("LeftStickRight", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_LEFT_X, true)),
+    ("LeftStickUp", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_LEFT_Y, false)),
+    ("LeftStickDown", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_LEFT_Y, true)),
+    ("RightStickLeft", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_RIGHT_X, false)),
+    ("RightStickRight", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_RIGHT_X, true)),
+    ("RightStickUp", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_RIGHT_Y, false)),
+    ("RightStickDown", Source::PadAxis(GamepadAxis::GAMEPAD_AXIS_RIGHT_Y, true)),
+];
+
+
+impl Source {
+    fn from_name(name: &str) -> Option<Source> {
+        if let Some((_, source)) = NAMED_SOURCES.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            return Some(*source)
+        }
+        let upper = name.to_ascii_uppercase();
+        let mut chars = upper.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphanumeric() => {key_from_i32(c as i32).map(Source::Key)}
+            (Some('F'), Some(_)) => {
+                let number: i32 = upper[1..].parse().ok().filter(|n| (1..=12).contains(n))?;
+                key_from_i32(KeyboardKey::KEY_F1 as i32 + number - 1).map(Source::Key)
+            }
+            // Keys without a name of their own are written by their raylib key code
+            (Some('K'), Some('E')) => {key_from_i32(upper.strip_prefix("KEY")?.parse().ok()?).map(Source::Key)}
+            _ => {None}
+        }
+    }
+
+    fn name(&self) -> String {
+        if let Some((name, _)) = NAMED_SOURCES.iter().find(|(_, source)| source == self) {
+            return name.to_string()
+        }
+        match self {
+            Source::Key(key) => {
+                let code = *key as i32;
+                let f1 = KeyboardKey::KEY_F1 as i32;
+                if (f1..f1 + 12).contains(&code) {
+                    format!("F{}", code - f1 + 1)
+                }
+                else {
+                    char::from_u32(code as u32).filter(char::is_ascii_alphanumeric)
+                        .map_or(format!("Key{}", code), |c| c.to_string())
+                }
+            }
+            Source::Mouse(button) => {format!("Mouse{}", *button as i32)}
+            Source::PadButton(button) => {format!("PadButton{}", *button as i32)}
+            Source::Wheel(_) | Source::PadAxis(..) => {unreachable!("every wheel and stick direction is named")}
+        }
+    }
+}
+
+impl TryFrom<String> for Chord {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Chord, String> {
+        let mut chord = Chord { ctrl: false, shift: false, source: Source::Wheel(true) };
+        let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+        let name = parts.pop().filter(|name| !name.is_empty()).ok_or(format!("Empty binding in {:?}", text))?;
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => {chord.ctrl = true}
+                "shift" => {chord.shift = true}
+                _ => {return Err(format!("Unknown modifier {:?} in {:?}, expected Ctrl or Shift", modifier, text))}
+            }
+        }
+        chord.source = Source::from_name(name).ok_or(format!("Unknown key or button {:?}", name))?;
+        Ok(chord)
+    }
+}
+
+impl From<Chord> for String {
+    fn from(chord: Chord) -> String {
+        chord.to_string()
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.source.name())
+    }
+}
+
+const GAMEPAD: i32 = 0;
+/// How far a stick has to move before it counts as pushed.
+const DEADZONE: f32 = 0.25;
+/// Holding a button bound to a wheel action scrolls this many notches a second.
+const HELD_NOTCHES_PER_SECOND: f32 = 10.0;
+const AXES: [GamepadAxis; 4] = [
+    GamepadAxis::GAMEPAD_AXIS_LEFT_X,
+    GamepadAxis::GAMEPAD_AXIS_LEFT_Y,
+    GamepadAxis::GAMEPAD_AXIS_RIGHT_X,
+    GamepadAxis::GAMEPAD_AXIS_RIGHT_Y,
+];
+
+/// Reads actions from the keyboard, mouse and first gamepad through the player's bindings.
+/// `update` has to be called once a frame so sticks can be "pressed" like buttons.
+pub(crate) struct Input {
+    pub bindings: Bindings,
+    axes: [f32; 4],
+    last_axes: [f32; 4],
+    /// Actions that are ignored until everything bound to them is let go, so the
+    /// press that leaves one view doesn't also count in the next.
+    consumed: Vec<Action>,
+    /// Set while a text field or menu has the keyboard, so keys meant for it don't trigger actions.
+    suspended: bool,
+}
+
+impl Input {
+    pub fn new(bindings: Bindings) -> Input {
+        Input { bindings, axes: [0.0; 4], last_axes: [0.0; 4], consumed: Vec::new(), suspended: false }
+    }
+
+    pub fn update(&mut self, rl: &RaylibHandle) {
+        self.last_axes = self.axes;
+        for (i, axis) in AXES.iter().enumerate() {
+            self.axes[i] = if rl.is_gamepad_available(GAMEPAD) { rl.get_gamepad_axis_movement(GAMEPAD, *axis) } else { 0.0 };
+        }
+        let bindings = &self.bindings;
+        let axes = self.axes;
+        self.consumed.retain(|action| {
+            bindings.get(action).is_some_and(|chords| chords.iter().any(|chord| source_strength(rl, &axes, chord.source) > 0.0))
+        });
+    }
+
+    /// Ignores an action until it's released.
+    pub fn consume(&mut self, action: Action) {
+        if !self.consumed.contains(&action) {
+            self.consumed.push(action);
+        }
+    }
+
+    /// Turns every action off while a text field or menu is open, and back on after.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    fn chords(&self, action: Action) -> &[Chord] {
+        if self.suspended || self.consumed.contains(&action) {
+            return &[]
+        }
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// True on the frame one of the action's inputs goes down with exactly its modifiers held,
+    /// so Z and Ctrl+Z can be bound to different things.
+    pub fn pressed(&self, rl: &RaylibHandle, action: Action) -> bool {
+        let (ctrl, shift) = modifiers(rl);
+        self.chords(action).iter().any(|chord| {
+            chord.ctrl == ctrl && chord.shift == shift && match chord.source {
+                Source::Key(key) => {rl.is_key_pressed(key)}
+                Source::Mouse(button) => {rl.is_mouse_button_pressed(button)}
+                Source::Wheel(_) => {source_strength(rl, &self.axes, chord.source) > 0.0}
+                Source::PadButton(button) => {rl.is_gamepad_available(GAMEPAD) && rl.is_gamepad_button_pressed(GAMEPAD, button)}
+                Source::PadAxis(..) => {
+                    source_strength(rl, &self.axes, chord.source) > 0.5 && source_strength(rl, &self.last_axes, chord.source) <= 0.5
+                }
+            }
+        })
+    }
+
+    /// Modifiers don't matter when letting go, so pressing Shift mid-drag doesn't stop it ending.
+    pub fn released(&self, rl: &RaylibHandle, action: Action) -> bool {
+        self.chords(action).iter().any(|chord| {
+            match chord.source {
+                Source::Key(key) => {rl.is_key_released(key)}
+                Source::Mouse(button) => {rl.is_mouse_button_released(button)}
+                Source::Wheel(_) => {false}
+                Source::PadButton(button) => {rl.is_gamepad_available(GAMEPAD) && rl.is_gamepad_button_released(GAMEPAD, button)}
+                Source::PadAxis(..) => {
+                    source_strength(rl, &self.axes, chord.source) <= 0.5 && source_strength(rl, &self.last_axes, chord.source) > 0.5
+                }
+            }
+        })
+    }
+
+    /// How hard the action is held, from 0 to 1. Sticks give partial values. The chord's modifiers
+    /// have to be down but others can be too, so holding Shift doesn't stop the camera turning.
+    pub fn strength(&self, rl: &RaylibHandle, action: Action) -> f32 {
+        let (ctrl, shift) = modifiers(rl);
+        self.chords(action).iter()
+            .filter(|chord| !matches!(chord.source, Source::Wheel(_)))
+            .filter(|chord| (ctrl || !chord.ctrl) && (shift || !chord.shift))
+            .map(|chord| source_strength(rl, &self.axes, chord.source))
+            .fold(0.0, f32::max)
+    }
+
+    /// `positive` minus `negative`, for things steered both ways like orbiting.
+    pub fn axis(&self, rl: &RaylibHandle, positive: Action, negative: Action) -> f32 {
+        self.strength(rl, positive) - self.strength(rl, negative)
+    }
+
+    /// Like `axis` but in mouse wheel notches this frame, with held buttons and sticks
+    /// scrolling smoothly.
+    pub fn scroll(&self, rl: &RaylibHandle, positive: Action, negative: Action) -> f32 {
+        let wheel = |action| {
+            self.chords(action).iter()
+                .filter(|chord| matches!(chord.source, Source::Wheel(_)))
+                .map(|chord| source_strength(rl, &self.axes, chord.source))
+                .fold(0.0, f32::max)
+        };
+        wheel(positive) - wheel(negative) + self.axis(rl, positive, negative) * HELD_NOTCHES_PER_SECOND * rl.get_frame_time()
+    }
+
+    /// What's bound to an action, for help text.
+    pub fn describe(&self, action: Action) -> String {
+        match self.bindings.get(&action) {
+            None => {"Unbound".to_string()}
+            Some(chords) if chords.is_empty() => {"Unbound".to_string()}
+            Some(chords) => {chords.iter().map(Chord::to_string).collect::<Vec<_>>().join(" / ")}
+        }
+    }
+
+    /// The first input pressed this frame along with the modifiers held, for rebinding.
+    /// Modifier keys on their own are skipped so they can be held for a chord.
+    pub fn capture(&self, rl: &mut RaylibHandle) -> Option<Chord> {
+        let (ctrl, shift) = modifiers(rl);
+        let chord = |source| Some(Chord { ctrl, shift, source });
+        while let Some(key) = rl.get_key_pressed() {
+            if !MODIFIER_KEYS.contains(&key) {
+                return chord(Source::Key(key))
+            }
+        }
+        for button in [MouseButton::MOUSE_BUTTON_LEFT, MouseButton::MOUSE_BUTTON_RIGHT, MouseButton::MOUSE_BUTTON_MIDDLE] {
+            if rl.is_mouse_button_pressed(button) {
+                return chord(Source::Mouse(button))
+            }
+        }
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel != 0.0 {
+            return chord(Source::Wheel(wheel > 0.0))
+        }
+        if let Some(button) = rl.get_gamepad_button_pressed() {
+            return chord(Source::PadButton(button))
+        }
+        for (i, axis) in AXES.iter().enumerate() {
+            for positive in [true, false] {
+                let sign = if positive { 1.0 } else { -1.0 };
+                if self.axes[i] * sign > 0.5 && self.last_axes[i] * sign <= 0.5 {
+                    return chord(Source::PadAxis(*axis, positive))
+                }
+            }
+        }
+        None
+    }
+}
+
+const MODIFIER_KEYS: [KeyboardKey; 4] = [
+    KeyboardKey::KEY_LEFT_CONTROL, KeyboardKey::KEY_RIGHT_CONTROL,
+    KeyboardKey::KEY_LEFT_SHIFT, KeyboardKey::KEY_RIGHT_SHIFT,
+];
+
+fn modifiers(rl: &RaylibHandle) -> (bool, bool) {
+    let ctrl = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL) || rl.is_key_down(KeyboardKey::KEY_RIGHT_CONTROL);
+    let shift = rl.is_key_down(KeyboardKey::KEY_LEFT_SHIFT) || rl.is_key_down(KeyboardKey::KEY_RIGHT_SHIFT);
+    (ctrl, shift)
+}
+
+fn source_strength(rl: &RaylibHandle, axes: &[f32; 4], source: Source) -> f32 {
+    let held = |down: bool| if down { 1.0 } else { 0.0 };
+    match source {
+        Source::Key(key) => {held(rl.is_key_down(key))}
+        Source::Mouse(button) => {held(rl.is_mouse_button_down(button))}
+        Source::Wheel(up) => {
+            let wheel = rl.get_mouse_wheel_move();
+            if up { wheel.max(0.0) } else { (-wheel).max(0.0) }
+        }
+        Source::PadButton(button) => {held(rl.is_gamepad_available(GAMEPAD) && rl.is_gamepad_button_down(GAMEPAD, button))}
+        Source::PadAxis(axis, positive) => {
+            let i = AXES.iter().position(|a| *a == axis).unwrap_or(0);
+            let value = if positive { axes[i] } else { -axes[i] };
+            if value > DEADZONE { (value - DEADZONE) / (1.0 - DEADZONE) } else { 0.0 }
+        }
+    }
+}
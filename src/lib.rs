@@ -0,0 +1,26 @@
+//! Rim's galaxy model: star systems joined by lanes, their planets and moons, how
+//! they're generated, and reading and writing `.rim` files.
+//!
+//! The viewer and editor are built with the `gui` feature, which is on by default.
+//! Tools that only need the data can leave it off and skip raylib entirely:
+//!
+//! ```toml
+//! rim = { path = "../rim", default-features = false }
+//! ```
+
+pub mod map;
+pub mod system;
+pub mod utils;
+pub mod vector;
+pub mod file_generator;
+pub mod history;
+pub mod export;
+pub mod party;
+#[cfg(feature = "gui")]
+pub mod picking;
+
+pub use map::{Galaxy, Shape, StarSystem, SystemId};
+pub use party::{Party, Perspective};
+pub use system::{Moon, MoonType, Planet, PlanetClass, Rings, StarSystemData};
+pub use vector::Vec3;
+pub use file_generator::{load_file, save};
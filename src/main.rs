@@ -1,31 +1,323 @@
+// Everything but the commands that just read or write files needs a window
+#[cfg(feature = "gui")]
 mod gameloop;
-mod map;
-mod utils;
-mod system;
-mod file_generator;
+mod svg;
+#[cfg(feature = "gui")]
+mod config;
+#[cfg(feature = "gui")]
+mod input;
+#[cfg(feature = "gui")]
+mod filter;
+#[cfg(feature = "gui")]
+mod heatmap;
+#[cfg(feature = "gui")]
+mod search;
+#[cfg(feature = "gui")]
+mod ui;
+#[cfg(feature = "gui")]
+mod server;
+#[cfg(feature = "gui")]
+mod session;
+#[cfg(feature = "gui")]
+mod watch;
+#[cfg(feature = "gui")]
+mod menu;
 
+use rim::{map, file_generator, export, party, vector};
+#[cfg(feature = "gui")]
+use rim::{utils, system, history, picking};
+
+#[cfg(feature = "gui")]
 use std::fs::exists;
 use std::env;
+#[cfg(feature = "gui")]
 use std::path::Path;
+use std::process;
+
+#[cfg(feature = "gui")]
+use config::Config;
+use export::GraphFormat;
+#[cfg(feature = "gui")]
+use history::History;
+use map::Galaxy;
+#[cfg(feature = "gui")]
+use session::{Host, Player, Session};
+use svg::Projection;
+
+/// The documented config defaults, which `rim config` prints. User config files are laid
+/// over this, so every setting has its default written down in exactly one place.
+const DEFAULT_CONFIG: &str = include_str!("default_config.toml");
 
 fn main() {
 
     let args: Vec<String> = env::args().collect();
-    
-    if args.len() == 2 {
-        if exists(Path::new(&args[1])).unwrap() == true {
-            let galaxy = file_generator::load_file(&args[1]);
-            let save = gameloop::start_gameloop(galaxy);
-            file_generator::save(&args[1], save);
+    let flags: Vec<String> = args.iter().filter(|arg| arg.starts_with("--")).cloned().collect();
+    let args: Vec<String> = args.into_iter().filter(|arg| !arg.starts_with("--")).collect();
+
+    match args.get(1).map(String::as_str) {
+        Some("export") => {
+            export_command(&args[2..]);
+            return
+        }
+        Some("svg") => {
+            svg_command(&args[2..], &flags);
+            return
+        }
+        Some("redact") => {
+            redact_command(&args[2..], &flags);
+            return
+        }
+        #[cfg(feature = "gui")]
+        Some("join") => {
+            join_command(&args[2..], &flags);
+            return
+        }
+        Some("config") => {
+            // Prints the documented defaults, ready to be saved as a config file
+            print!("{}", DEFAULT_CONFIG);
+            return
+        }
+        _ => {}
+    }
+
+    open_viewer(&args, &flags);
+}
+
+#[cfg(not(feature = "gui"))]
+fn open_viewer(_args: &[String], _flags: &[String]) {
+    eprintln!("This rim was built without the gui feature, so it can only export, svg, redact and config");
+    process::exit(1);
+}
+
+/// Opens the galaxy given on the command line, or the title menu if there isn't one, and
+/// saves it when the window is closed.
+#[cfg(feature = "gui")]
+fn open_viewer(args: &[String], flags: &[String]) {
+    let config = load_config_or_exit(flags);
+    // --lan lets players on other computers join, not just other rims on this one
+    let lan = flags.iter().any(|flag| flag == "--lan");
+    let session = host_port(flags).map(|port| match Host::start(port, lan) {
+        Ok(host) => {Session::Host(host)}
+        Err(err) => {
+            eprintln!("Could not host on port {}: {}", port, err);
+            process::exit(1);
+        }
+    });
+    let (mut rl, thread) = gameloop::open_window(&config.window);
+    // Without a save to open, the title menu picks one or makes a new galaxy
+    let (path, galaxy) = if args.len() == 2 {
+        if exists(Path::new(&args[1])).unwrap() {
+            (args[1].clone(), file_generator::load_file(&args[1]))
         }
         else {
-            let save = gameloop::start_gameloop(None);
-            file_generator::save(&args[1], save);
+            (args[1].clone(), None)
         }
     }
     else {
-        let save = gameloop::start_gameloop(None);
-        file_generator::save(&("default.rim".to_string()), save);
+        match menu::title_menu(&mut rl, &thread, &config) {
+            None => {return}
+            Some((path, galaxy)) => {(path, Some(galaxy))}
+        }
+    };
+
+    // --journal keeps the undo history in a file next to the save between sessions
+    let journal = config.journal;
+    let history = match (journal, &galaxy) {
+        (true, Some(_)) => {
+            match file_generator::load_journal(&path) {
+                Ok(history) => {history.unwrap_or_default()}
+                Err(err) => {
+                    eprintln!("{}, starting without the undo history", err);
+                    History::default()
+                }
+            }
+        }
+        _ => {History::default()}
+    };
+    let (save, history, save_to) = gameloop::start_gameloop(&mut rl, &thread, galaxy, history, path, config, session);
+    if let Some(path) = save_to {
+        if let Err(err) = file_generator::try_save(&path, &save) {
+            eprintln!("Could not save to {}: {}", path, err);
+            process::exit(1);
+        }
+        if journal && let Err(err) = file_generator::save_journal(&path, &history) {
+            eprintln!("Could not write the journal for {}: {}", path, err);
+        }
+        menu::remember_save(&path);
+    }
+}
+
+/// `--host` or `--host=<port>` shares the galaxy with players who join it, on this computer
+/// or with `--lan` on the network.
+#[cfg(feature = "gui")]
+fn host_port(flags: &[String]) -> Option<u16> {
+    let flag = flags.iter().find(|flag| *flag == "--host" || flag.starts_with("--host="))?;
+    match flag.strip_prefix("--host=") {
+        None => {Some(session::DEFAULT_PORT)}
+        Some(port) => {
+            match port.parse() {
+                Ok(port) => {Some(port)}
+                Err(_) => {
+                    eprintln!("Bad port {}", port);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
+
+/// `rim join <host>[:port]` shows a galaxy someone is hosting with `--host`. Nothing is
+/// saved, the host keeps the galaxy.
+#[cfg(feature = "gui")]
+fn join_command(args: &[String], flags: &[String]) {
+    let address = match args.first() {
+        Some(address) => address,
+        None => {
+            eprintln!("Usage: rim join <host>[:port]");
+            process::exit(1);
+        }
+    };
+    let config = load_config_or_exit(flags);
+    let (player, galaxy) = match Player::join(address) {
+        Ok(joined) => joined,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+    let (mut rl, thread) = gameloop::open_window(&config.window);
+    // Exports go next to where a save would be
+    let session = Some(Session::Player(player));
+    gameloop::start_gameloop(&mut rl, &thread, Some(galaxy), History::default(), "shared.rim".to_string(), config, session);
+}
+
+#[cfg(feature = "gui")]
+fn load_config_or_exit(flags: &[String]) -> Config {
+    match Config::load(flags) {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// `rim export <format> <save.rim> [output]` writes the lane graph without opening a window.
+fn export_command(args: &[String]) {
+    let (format, input) = match (args.first().and_then(|name| GraphFormat::from_name(name)), args.get(1)) {
+        (Some(format), Some(input)) => (format, input),
+        _ => {
+            eprintln!("Usage: rim export <dot|graphml|geojson> <save.rim> [output]");
+            process::exit(1);
+        }
+    };
+    let galaxy = load_or_exit(input);
+    let output = args.get(2).cloned().unwrap_or_else(|| file_generator::export_path(input, format.extension()));
+    if let Err(err) = file_generator::export_graph(&output, &galaxy, format) {
+        eprintln!("Could not write {}: {}", output, err);
+        process::exit(1);
+    }
+}
+
+/// `rim redact <save.rim> [output] [--hide-lanes] [--party=<name>]` writes a copy for players,
+/// without what they haven't explored or the GM's notes. It opens read-only. With a party
+/// only what that party has explored is kept.
+fn redact_command(args: &[String], flags: &[String]) {
+    let input = match args.first() {
+        Some(input) => input,
+        None => {
+            eprintln!("Usage: rim redact <save.rim> [output] [--hide-lanes] [--party=<name>]");
+            process::exit(1);
+        }
+    };
+    let galaxy = load_or_exit(input);
+    let hide_lanes = flags.iter().any(|flag| flag == "--hide-lanes");
+    let perspective = match flags.iter().find_map(|flag| flag.strip_prefix("--party=")) {
+        None => {party::Perspective::Everyone}
+        Some(name) => {
+            match galaxy.parties().iter().position(|party| party.name == name) {
+                Some(index) => {party::Perspective::Party(index)}
+                None => {
+                    eprintln!("{} has no party named {}", input, name);
+                    process::exit(1);
+                }
+            }
+        }
+    };
+    let output = args.get(1).cloned().unwrap_or_else(|| file_generator::export_path(input, "player.rim"));
+    if let Err(err) = file_generator::export_player_copy(&output, &galaxy, perspective, hide_lanes) {
+        eprintln!("Could not write {}: {}", output, err);
+        process::exit(1);
+    }
+}
+
+/// `rim svg map <save.rim> [output] [--projection=top|equirect] [--no-labels]` draws the galaxy,
+/// `rim svg system <save.rim> <name> [output]` draws the orrery of one system, by its hex name.
+fn svg_command(args: &[String], flags: &[String]) {
+    const USAGE: &str = "Usage: rim svg map <save.rim> [output] [--projection=top|equirect] [--no-labels]\n       rim svg system <save.rim> <name> [output]";
+    let (kind, input) = match (args.first(), args.get(1)) {
+        (Some(kind), Some(input)) => (kind.as_str(), input),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    let galaxy = load_or_exit(input);
+    let (output, svg) = match kind {
+        "map" => {
+            let projection = match flags.iter().find_map(|flag| flag.strip_prefix("--projection=")) {
+                None => {Projection::TopDown}
+                Some(name) => {
+                    match Projection::from_name(name) {
+                        Some(projection) => {projection}
+                        None => {
+                            eprintln!("Unknown projection {}, expected top or equirect", name);
+                            process::exit(1);
+                        }
+                    }
+                }
+            };
+            let labels = !flags.iter().any(|flag| flag == "--no-labels");
+            let output = args.get(2).cloned().unwrap_or_else(|| file_generator::export_path(input, "svg"));
+            (output, svg::galaxy_svg(&galaxy, projection, labels))
+        }
+        "system" => {
+            let name = args.get(2).and_then(|name| usize::from_str_radix(name, 16).ok());
+            let system = match name.and_then(|name| galaxy.systems().iter().find(|system| system.name == name)) {
+                Some(system) => {system}
+                None => {
+                    eprintln!("No system named {} in {}", args.get(2).map(String::as_str).unwrap_or(""), input);
+                    process::exit(1);
+                }
+            };
+            let svg = match svg::orrery_svg(system) {
+                Some(svg) => {svg}
+                None => {
+                    eprintln!("System {:X} hasn't been scanned yet", system.name);
+                    process::exit(1);
+                }
+            };
+            let output = args.get(3).cloned()
+                .unwrap_or_else(|| file_generator::export_path(input, &format!("{:X}.svg", system.name)));
+            (output, svg)
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = file_generator::export_svg(&output, svg) {
+        eprintln!("Could not write {}: {}", output, err);
+        process::exit(1);
+    }
+}
+
+fn load_or_exit(input: &String) -> Galaxy {
+    match file_generator::load_file(input) {
+        Some(galaxy) => galaxy,
+        None => {
+            eprintln!("Could not read a galaxy from {}", input);
+            process::exit(1);
+        }
     }
-    
 }
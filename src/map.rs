@@ -2,24 +2,47 @@
 use crate::utils;
 use crate::utils::*;
 use crate::system::*;
-use rand::Rng;
-use raylib::prelude::*;
+use crate::vector::Vec3;
+use crate::party::{self, Party, Perspective};
+#[cfg(feature = "gui")]
+use crate::picking::{Picker, View};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+#[cfg(feature = "gui")]
+use raylib::prelude::{Camera3D, Color, RaylibHandle};
 use std::f64::consts::PI;
 
 use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::collections::hash_map::Entry;
+use std::ops::{Index, IndexMut};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Stable identifier for a system. Unlike its position in `Galaxy::systems` it never
+/// changes when other systems are added or removed, and is never reused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SystemId(pub u64);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StarSystem {
-    #[serde(with = "vector3_serde")]
-    pub position: Vector3,
-    #[serde(with = "vector3_serde")]
-    origin: Vector3,
-    #[serde(with = "vector3_serde")]
-    pub drift_direction: Vector3,
-    pub connections: Vec<usize>,
+    #[serde(default)]
+    id: SystemId,
+    pub position: Vec3,
+    origin: Vec3,
+    pub drift_direction: Vec3,
+    pub connections: Vec<SystemId>,
     pub name: usize,
     pub system_data: Option<StarSystemData>,
-    pub explored: bool
+    /// Whether the system had been explored, from saves made before parties. What's been
+    /// explored is kept in `Galaxy::parties` now.
+    #[serde(default, rename = "explored", skip_serializing)]
+    legacy_explored: bool,
+    /// Free text the GM keeps about the system. Lines starting with `GM:` are kept
+    /// out of what players get.
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub tags: Vec<String>
 }
 
 const SPRING_STRENGTH: f32 = 0.001;
@@ -34,6 +57,67 @@ impl StarSystem {
 
         self.position += self.drift_direction.scale_by(dt * 60.0);
     }
+    pub fn id(&self) -> SystemId {
+        self.id
+    }
+    pub fn origin(&self) -> Vec3 {
+        self.origin
+    }
+    /// Moves the system's resting point, snapping it there instead of letting it drift over.
+    pub fn set_origin(&mut self, origin: Vec3) {
+        self.origin = origin;
+        self.position = origin;
+        self.drift_direction = Vec3::zero();
+    }
+    /// Size and colour the system is drawn with on the galaxy map, where `explored` is whether
+    /// it's been explored from the map's perspective. While another system is hovered
+    /// everything else is dimmed.
+    #[cfg(feature = "gui")]
+    pub fn map_style(&self, hovered: Option<SystemId>, explored: bool) -> (f32, Color) {
+        let (size, [r, g, b]) = self.map_style_rgb(hovered, explored);
+        (size, Color::new(r, g, b, 255))
+    }
+    /// `map_style` with the colour as red, green and blue, for drawing the map without raylib.
+    pub fn map_style_rgb(&self, hovered: Option<SystemId>, explored: bool) -> (f32, [u8; 3]) {
+        match hovered {
+            Some(val) => {
+                if val == self.id { (0.9, [176, 224, 230]) }
+                else { (0.5, [55, 55, 55]) }
+            },
+            None => {
+                match &self.system_data {
+                    None => {(0.5, [90, 90, 90])}
+                    Some(_) if !explored => {(0.5, [60, 60, 80])}
+                    Some(_) => {(1.0, [130, 110, 150])}
+                }
+            }
+        }
+    }
+    /// The name of the system's `planet`th planet, like 3FA2-B.
+    pub fn planet_designation(&self, planet: usize) -> String {
+        format!("{:X}-{}", self.name, utils::num_to_letter(planet as u8).unwrap_or('?').to_ascii_uppercase())
+    }
+    /// The name of a moon, numbered from 1 after its planet's designation, like 3FA2-B2.
+    pub fn moon_designation(&self, planet: usize, moon: usize) -> String {
+        format!("{}{}", self.planet_designation(planet), moon + 1)
+    }
+    /// Takes out what players shouldn't know: everything but where the system is if it
+    /// hasn't been `explored`, and the GM's lines of notes if it has.
+    pub fn redact(&mut self, explored: bool) {
+        if !explored {
+            self.system_data = None;
+            self.notes.clear();
+            self.tags.clear();
+            return
+        }
+        self.notes = players_notes(&self.notes);
+        for planet in self.system_data.iter_mut().flat_map(|data| data.planets.iter_mut()) {
+            planet.notes = players_notes(&planet.notes);
+            for moon in &mut planet.moons {
+                moon.notes = players_notes(&moon.notes);
+            }
+        }
+    }
     pub fn get_hover_string(&self) -> String {
         match &self.system_data {
             None => {
@@ -41,11 +125,16 @@ impl StarSystem {
             }
             Some(data) => {
                 let mut hover_string: String = format!("System {:X}:\n", self.name);
+                if !self.tags.is_empty() {
+                    hover_string += format!("Tags: {}\n", self.tags.join(", ")).as_str();
+                }
+                if !self.notes.is_empty() {
+                    hover_string += format!("Notes: {}\n", self.notes).as_str();
+                }
                 hover_string += format!("Number of Planets: {}\n", data.planets.len()).as_str();
                 for (i, planet) in data.planets.iter().enumerate() {
                     hover_string += "---\n";
-                    hover_string += format!("Planet {:X}-{}\n", self.name, 
-                        utils::num_to_letter(i as u8).unwrap().to_ascii_uppercase()).as_str();
+                    hover_string += format!("Planet {}\n", self.planet_designation(i)).as_str();
                     hover_string += format!("   {:?} Planet.\n", planet.class).as_str();
                     hover_string += format!("   Orbital radius: {:.3} std.\n", planet.orbit_radius).as_str();
                     hover_string += format!("   {} Moons.\n", planet.moons.len()).as_str();
@@ -79,18 +168,161 @@ impl StarSystem {
     }
 }
 
+/// Save format version. Version 0 saves predate `SystemId` and store connections
+/// as indices into `systems`. Saves before version 2 mark systems explored instead of
+/// having parties.
+const GALAXY_VERSION: u32 = 2;
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "SavedGalaxy")]
 pub struct Galaxy {
-    pub systems: Vec<StarSystem>,
+    version: u32,
+    systems: Vec<StarSystem>,
+    next_id: u64,
+    /// The groups of players exploring the galaxy. There's always at least one.
+    parties: Vec<Party>,
+    /// Set on copies made for players, which the viewer opens read-only.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    redacted: bool,
+    #[serde(skip)]
+    index: HashMap<SystemId, usize>,
+}
+
+/// A galaxy as it is laid out on disk, before migration and indexing.
+#[derive(Deserialize)]
+struct SavedGalaxy {
+    #[serde(default)]
+    version: u32,
+    systems: Vec<StarSystem>,
+    #[serde(default)]
+    next_id: u64,
+    #[serde(default)]
+    parties: Vec<Party>,
+    #[serde(default)]
+    redacted: bool,
+}
+
+impl From<SavedGalaxy> for Galaxy {
+    fn from(saved: SavedGalaxy) -> Galaxy {
+        let mut systems = saved.systems;
+        if saved.version == 0 {
+            // Old connections are indices, so giving every system its index as its id
+            // makes them valid ids without touching them.
+            for (i, sys) in systems.iter_mut().enumerate() {
+                sys.id = SystemId(i as u64);
+            }
+        }
+        let mut parties = saved.parties;
+        if saved.version < 2 {
+            // Everything explored so far was explored by the one group there was
+            // and they'd seen every planet in the systems they explored
+            let mut players = Party::new(party::DEFAULT_PARTY);
+            let explored: Vec<&StarSystem> = systems.iter().filter(|sys| sys.legacy_explored).collect();
+            players.explored = explored.iter().map(|sys| (sys.id, 0)).collect();
+            players.scanned = explored.iter()
+                .filter_map(|sys| sys.system_data.as_ref().filter(|data| !data.planets.is_empty()).map(|data| (sys.id, (0..data.planets.len()).map(|planet| (planet, 0)).collect())))
+                .collect();
+            parties = vec![players];
+        }
+        let next_id = systems.iter().map(|sys| sys.id.0 + 1).max().unwrap_or(0).max(saved.next_id);
+        let mut galaxy = Galaxy::from_systems(systems, parties, next_id);
+        galaxy.drop_broken_lanes();
+        galaxy.redacted = saved.redacted;
+        galaxy
+    }
+}
+
+impl Index<SystemId> for Galaxy {
+    type Output = StarSystem;
+    fn index(&self, id: SystemId) -> &StarSystem {
+        &self.systems[self.index[&id]]
+    }
+}
+
+impl IndexMut<SystemId> for Galaxy {
+    fn index_mut(&mut self, id: SystemId) -> &mut StarSystem {
+        let i = self.index[&id];
+        &mut self.systems[i]
+    }
+}
+
+/// What systems are spread over when a galaxy is generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    /// The surface of a sphere, which is what rim has always made.
+    #[default]
+    Sphere,
+    /// A flat disc, a little thick so lanes don't all lie in one plane.
+    Disc,
+    /// A disc with its middle half missing.
+    Ring,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 3] = [Shape::Sphere, Shape::Disc, Shape::Ring];
+
+    pub fn from_name(name: &str) -> Option<Shape> {
+        match name.to_ascii_lowercase().as_str() {
+            "sphere" => Some(Shape::Sphere),
+            "disc" | "disk" => Some(Shape::Disc),
+            "ring" => Some(Shape::Ring),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Sphere => "sphere",
+            Shape::Disc => "disc",
+            Shape::Ring => "ring",
+        }
+    }
 }
+
 impl Galaxy {
     pub fn new(
         num_systems: usize,
         connections_per_system: usize,
         amplitude: f64,
         radius: f64,
+        shape: Shape,
+    ) -> Galaxy {
+        Galaxy::generate(&mut rand::thread_rng(), num_systems, connections_per_system, amplitude, radius, shape)
+    }
+    /// Like `Galaxy::new`, but the same seed always lays out the same galaxy.
+    pub fn with_seed(
+        seed: u64,
+        num_systems: usize,
+        connections_per_system: usize,
+        amplitude: f64,
+        radius: f64,
+        shape: Shape,
+    ) -> Galaxy {
+        Galaxy::generate(&mut StdRng::seed_from_u64(seed), num_systems, connections_per_system, amplitude, radius, shape)
+    }
+    /// Catches settings `Galaxy::new` can't make a galaxy from, naming the one that's wrong.
+    pub fn check_params(num_systems: usize, connections_per_system: usize, amplitude: f64, radius: f64) -> Result<(), String> {
+        if num_systems < 2 {
+            return Err("systems must be at least 2".to_string())
+        }
+        if connections_per_system >= num_systems {
+            return Err("connections_per_system must be less than systems".to_string())
+        }
+        // Written this way round so NaN fails too, and random ranges can't be infinite
+        if !(amplitude > 0.0 && radius > 0.0 && amplitude.is_finite() && radius.is_finite()) {
+            return Err("amplitude and radius must be above 0".to_string())
+        }
+        Ok(())
+    }
+    fn generate<R: Rng>(
+        rng: &mut R,
+        num_systems: usize,
+        connections_per_system: usize,
+        amplitude: f64,
+        radius: f64,
+        shape: Shape,
     ) -> Galaxy {
-        let mut rng = rand::thread_rng();
         let mut systems = Vec::with_capacity(num_systems);
 
         // Step 1: Generate points on a sphere (Fibonacci sphere)
@@ -98,33 +330,48 @@ impl Galaxy {
         let increment = PI * (3.0 - (5.0f64).sqrt());
 
         for i in 0..num_systems {
-            let y = ((i as f64) * offset) - 1.0 + (offset / 2.0);
-            let r = (1.0 - y * y).sqrt();
-            let phi = ((i as f64) % num_systems as f64) * increment;
+            let (x, y, z) = match shape {
+                Shape::Sphere => {
+                    let y = ((i as f64) * offset) - 1.0 + (offset / 2.0);
+                    let r = (1.0 - y * y).sqrt();
+                    let phi = ((i as f64) % num_systems as f64) * increment;
 
-            let mut x = phi.cos() * r;
-            let mut z = phi.sin() * r;
-            let mut y = y;
+                    let mut x = phi.cos() * r;
+                    let mut z = phi.sin() * r;
+                    let mut y = y;
 
-            // Step 2: Apply random offsets
-            x += rng.gen_range(-amplitude..amplitude);
-            y += rng.gen_range(-amplitude..amplitude);
-            z += rng.gen_range(-amplitude..amplitude);
+                    // Step 2: Apply random offsets
+                    x += rng.gen_range(-amplitude..amplitude);
+                    y += rng.gen_range(-amplitude..amplitude);
+                    z += rng.gen_range(-amplitude..amplitude);
 
-            // Normalize back to sphere surface
-            let length = (x * x + y * y + z * z).sqrt();
-            x = (x / length) * radius;
-            y = (y / length) * radius;
-            z = (z / length) * radius;
+                    // Normalize back to sphere surface
+                    let length = (x * x + y * y + z * z).sqrt();
+                    ((x / length) * radius, (y / length) * radius, (z / length) * radius)
+                }
+                Shape::Disc | Shape::Ring => {
+                    // The same spiral flattened into a disc. The offsets move systems around
+                    // and in or out, wrapping past the edges so they stay on the disc.
+                    let inner: f64 = if shape == Shape::Ring { 0.5 } else { 0.0 };
+                    let area = ((i as f64 + 0.5) / num_systems as f64 + rng.gen_range(-amplitude..amplitude)).rem_euclid(1.0);
+                    let r = (inner * inner + (1.0 - inner * inner) * area).sqrt();
+                    let phi = (i as f64) * increment + rng.gen_range(-amplitude..amplitude) * PI;
+                    let height = rng.gen_range(-0.05..0.05);
+                    (phi.cos() * r * radius, height * radius, phi.sin() * r * radius)
+                }
+            };
 
             systems.push(StarSystem {
-                position: Vector3::new(x as f32, y as f32, z as f32),
-                origin: Vector3::new(x as f32, y as f32, z as f32),
-                drift_direction: Vector3::zero(),
+                id: SystemId(i as u64),
+                position: Vec3::new(x as f32, y as f32, z as f32),
+                origin: Vec3::new(x as f32, y as f32, z as f32),
+                drift_direction: Vec3::zero(),
                 connections: Vec::new(),
-                system_data: Some(crate::file_generator::generate_system_data()),
-                name: crate::utils::hash_planet_id(i) as u32 as usize,
-                explored: false
+                system_data: Some(StarSystemData::generate(rng)),
+                name: unique_name(&systems, i),
+                legacy_explored: false,
+                notes: String::new(),
+                tags: Vec::new()
             });
         }
 
@@ -146,52 +393,576 @@ impl Galaxy {
             distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
             for &(j, _) in distances.iter().take(connections_per_system) {
-                if !systems[i].connections.contains(&j) {
-                    systems[i].connections.push(j);
+                let (id_i, id_j) = (systems[i].id, systems[j].id);
+                if !systems[i].connections.contains(&id_j) {
+                    systems[i].connections.push(id_j);
+                }
+                if !systems[j].connections.contains(&id_i) {
+                    systems[j].connections.push(id_i);
+                }
+            }
+        }
+        Galaxy::from_systems(systems, Vec::new(), num_systems as u64)
+    }
+    fn from_systems(systems: Vec<StarSystem>, mut parties: Vec<Party>, next_id: u64) -> Galaxy {
+        if parties.is_empty() {
+            parties.push(Party::new(party::DEFAULT_PARTY));
+        }
+        let mut galaxy = Galaxy { version: GALAXY_VERSION, systems, next_id, parties, redacted: false, index: HashMap::new() };
+        galaxy.reindex(0);
+        galaxy
+    }
+    // Hand-edited and old saves can have lanes to systems that don't exist, or that only
+    // one of their ends lists. Everything else expects both ends to be there.
+    fn drop_broken_lanes(&mut self) {
+        let lanes: HashSet<(SystemId, SystemId)> = self.systems.iter()
+            .flat_map(|sys| sys.connections.iter().map(|&conn| (sys.id, conn)))
+            .collect();
+        for sys in self.systems.iter_mut() {
+            let id = sys.id;
+            sys.connections.retain(|&conn| conn != id && lanes.contains(&(conn, id)));
+        }
+    }
+    // Refreshes the id lookup for every system from `start` onwards.
+    fn reindex(&mut self, start: usize) {
+        for (i, sys) in self.systems.iter().enumerate().skip(start) {
+            self.index.insert(sys.id, i);
+        }
+    }
+    pub fn systems(&self) -> &[StarSystem] {
+        &self.systems
+    }
+    pub fn systems_mut(&mut self) -> std::slice::IterMut<'_, StarSystem> {
+        self.systems.iter_mut()
+    }
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+    pub fn contains(&self, id: SystemId) -> bool {
+        self.index.contains_key(&id)
+    }
+    pub fn index_of(&self, id: SystemId) -> Option<usize> {
+        self.index.get(&id).copied()
+    }
+    pub fn get(&self, id: SystemId) -> Option<&StarSystem> {
+        self.index_of(id).map(|i| &self.systems[i])
+    }
+    pub fn get_mut(&mut self, id: SystemId) -> Option<&mut StarSystem> {
+        self.index_of(id).map(|i| &mut self.systems[i])
+    }
+    /// Builds a new scanned system resting at `origin`, with a fresh id and a name no
+    /// other system uses. It isn't part of the galaxy until it is inserted.
+    pub fn new_system(&mut self, origin: Vec3) -> StarSystem {
+        let id = SystemId(self.next_id);
+        self.next_id += 1;
+        StarSystem {
+            id,
+            position: origin,
+            origin,
+            drift_direction: Vec3::zero(),
+            connections: Vec::new(),
+            system_data: Some(crate::file_generator::generate_system_data()),
+            name: unique_name(&self.systems, id.0 as usize),
+            legacy_explored: false,
+            notes: String::new(),
+            tags: Vec::new()
+        }
+    }
+    /// Inserts a system at `index` in the system list and mirrors its connections onto
+    /// the systems they point at. This is the inverse of `remove_system`.
+    pub fn insert_system(&mut self, index: usize, system: StarSystem) {
+        let id = system.id;
+        let connections = system.connections.clone();
+        self.next_id = self.next_id.max(id.0 + 1);
+        self.systems.insert(index, system);
+        self.reindex(index);
+        for conn in connections {
+            if let Some(other) = self.get_mut(conn) && !other.connections.contains(&id) {
+                other.connections.push(id);
+            }
+        }
+    }
+    /// Removes a system and every lane leading to it. Returns where it was in the
+    /// system list along with the system itself.
+    pub fn remove_system(&mut self, id: SystemId) -> Option<(usize, StarSystem)> {
+        let index = self.index.remove(&id)?;
+        let removed = self.systems.remove(index);
+        self.reindex(index);
+        for sys in self.systems.iter_mut() {
+            sys.connections.retain(|&conn| conn != id);
+        }
+        Some((index, removed))
+    }
+    pub fn move_system(&mut self, id: SystemId, origin: Vec3) {
+        self[id].set_origin(origin);
+    }
+    pub fn is_connected(&self, a: SystemId, b: SystemId) -> bool {
+        self[a].connections.contains(&b)
+    }
+    pub fn connect(&mut self, a: SystemId, b: SystemId) {
+        if a == b || self.is_connected(a, b) {
+            return;
+        }
+        self[a].connections.push(b);
+        self[b].connections.push(a);
+    }
+    pub fn disconnect(&mut self, a: SystemId, b: SystemId) {
+        self[a].connections.retain(|&conn| conn != b);
+        self[b].connections.retain(|&conn| conn != a);
+    }
+    /// How many lanes away every system reachable from `start` is, counting the shortest route.
+    pub fn hops_from(&self, start: impl IntoIterator<Item = SystemId>) -> HashMap<SystemId, usize> {
+        let mut hops = HashMap::new();
+        let mut queue = VecDeque::new();
+        for id in start {
+            if hops.insert(id, 0).is_none() {
+                queue.push_back(id);
+            }
+        }
+        while let Some(id) = queue.pop_front() {
+            let next = hops[&id] + 1;
+            for &conn in &self[id].connections {
+                if let Entry::Vacant(entry) = hops.entry(conn) {
+                    entry.insert(next);
+                    queue.push_back(conn);
                 }
-                if !systems[j].connections.contains(&i) {
-                    systems[j].connections.push(i);
+            }
+        }
+        hops
+    }
+    /// The systems along a route from `start` to `end` with the fewest lanes, both ends
+    /// included, or None if no lanes lead there.
+    pub fn shortest_path(&self, start: SystemId, end: SystemId) -> Option<Vec<SystemId>> {
+        if !self.contains(start) || !self.contains(end) {
+            return None
+        }
+        // Each reached system maps to the one it was reached from
+        let mut came_from = HashMap::from([(start, start)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(id) = queue.pop_front() {
+            if id == end {
+                let mut path = vec![end];
+                let mut at = end;
+                while at != start {
+                    at = came_from[&at];
+                    path.push(at);
+                }
+                path.reverse();
+                return Some(path)
+            }
+            for &conn in &self[id].connections {
+                if let Entry::Vacant(entry) = came_from.entry(conn) {
+                    entry.insert(id);
+                    queue.push_back(conn);
                 }
             }
         }
-        Galaxy { systems }
+        None
+    }
+    pub fn parties(&self) -> &[Party] {
+        &self.parties
+    }
+    pub fn parties_mut(&mut self) -> &mut Vec<Party> {
+        &mut self.parties
+    }
+    /// Whether a system has been explored from a perspective.
+    pub fn is_explored(&self, id: SystemId, perspective: Perspective) -> bool {
+        match perspective {
+            Perspective::Everyone => {self.parties.iter().any(|party| party.has_explored(id))}
+            Perspective::Party(i) => {self.parties.get(i).is_some_and(|party| party.has_explored(id))}
+        }
+    }
+    /// The systems explored from a perspective.
+    pub fn explored(&self, perspective: Perspective) -> HashSet<SystemId> {
+        let parties = match perspective {
+            Perspective::Everyone => {&self.parties[..]}
+            Perspective::Party(i) => {self.parties.get(i..=i).unwrap_or(&[])}
+        };
+        // Parties remember systems that have since been deleted
+        parties.iter().flat_map(|party| party.explored.keys().copied()).filter(|id| self.contains(*id)).collect()
+    }
+    /// The systems players know about: the ones any party has explored and those a lane away from them.
+    pub fn visible_to_players(&self) -> HashSet<SystemId> {
+        let mut visible = HashSet::new();
+        for id in self.explored(Perspective::Everyone) {
+            visible.insert(id);
+            visible.extend(self[id].connections.iter().copied());
+        }
+        visible
+    }
+    /// Copies of the `visible` systems as players see them. Explored systems keep their
+    /// planets, notes and tags, systems on the frontier only show where they are, and
+    /// lanes leading out of sight are left off. They sit at rest rather than mid-drift.
+    pub fn players_view(&self, visible: &HashSet<SystemId>) -> Vec<StarSystem> {
+        let explored = self.explored(Perspective::Everyone);
+        self.systems.iter().filter(|sys| visible.contains(&sys.id)).map(|sys| {
+            let mut seen = sys.clone();
+            seen.set_origin(sys.origin);
+            seen.connections.retain(|conn| visible.contains(conn));
+            seen.redact(explored.contains(&sys.id));
+            seen
+        }).collect()
+    }
+    /// The galaxy with only what players can see in it.
+    pub fn for_players(&self) -> Galaxy {
+        let systems = self.players_view(&self.visible_to_players());
+        let mut galaxy = Galaxy::from_systems(systems, self.parties.clone(), self.next_id);
+        galaxy.redacted = true;
+        galaxy
+    }
+    /// A copy to hand to players, with every system redacted as `perspective` sees it. A party's
+    /// copy only has that party in it, and only the planets it has scanned. With
+    /// `hide_unknown_lanes` only lanes with an explored system at one end are left.
+    pub fn redacted(&self, perspective: Perspective, hide_unknown_lanes: bool) -> Galaxy {
+        let explored = self.explored(perspective);
+        let party = match perspective {
+            Perspective::Everyone => {None}
+            Perspective::Party(i) => {self.parties.get(i)}
+        };
+        let systems = self.systems.iter().map(|sys| {
+            let mut copy = sys.clone();
+            copy.redact(explored.contains(&sys.id));
+            if hide_unknown_lanes && !explored.contains(&sys.id) {
+                copy.connections.retain(|conn| explored.contains(conn));
+            }
+            if let (Some(party), Some(data)) = (party, &mut copy.system_data) {
+                let mut planet = 0;
+                data.planets.retain(|_| {
+                    planet += 1;
+                    party.has_scanned(sys.id, planet - 1)
+                });
+            }
+            copy
+        }).collect();
+        let parties = match party {
+            None => {self.parties.clone()}
+            Some(party) => {
+                // Only the scanned planets are left, so they're numbered from 0 in the copy
+                let mut party = party.clone();
+                for planets in party.scanned.values_mut() {
+                    *planets = planets.values().enumerate().map(|(planet, at)| (planet, *at)).collect();
+                }
+                vec![party]
+            }
+        };
+        let mut galaxy = Galaxy::from_systems(systems, parties, self.next_id);
+        galaxy.redacted = true;
+        galaxy
+    }
+    /// Whether this is a copy made for players.
+    pub fn is_redacted(&self) -> bool {
+        self.redacted
+    }
+    /// Average distance of the systems from the galaxy centre.
+    pub fn radius(&self) -> f32 {
+        if self.systems.is_empty() {
+            return 50.0;
+        }
+        self.systems.iter().map(|sys| sys.origin.length()).sum::<f32>() / self.systems.len() as f32
     }
     pub fn wiggle(&mut self, dt: f32) {
         for sys in self.systems.iter_mut() {
             sys.drift(dt)
         }
     }
-    pub fn closest_system_to_mouse(&self, rl: &mut RaylibHandle, camera: &Camera3D) -> Option<usize> {
-        let mouse_pos = rl.get_mouse_position();
-        let ray = rl.get_screen_to_world_ray(mouse_pos, camera);
-
-        let mut closest: Option<usize> = None;
-        let mut min_distance = f32::MAX;
-
-        for (i, system) in self.systems.iter().enumerate() {
-            if ray_sphere_intersect(ray.position, ray.direction, system.position, 2.0) {
-                let dist = (ray.position - system.position).length();
-                if dist < min_distance {
-                    min_distance = dist;
-                    closest = Some(i);
+    /// The system drawn under the mouse, if any. Systems are picked at the size they're
+    /// drawn with from `perspective`, plus a few pixels so they stay easy to hit when zoomed out.
+    #[cfg(feature = "gui")]
+    pub fn closest_system_to_mouse(&self, rl: &RaylibHandle, camera: &Camera3D, perspective: Perspective) -> Option<SystemId> {
+        let mut picker = Picker::new(View::of_window(rl, camera), rl.get_mouse_position());
+        for system in self.systems.iter() {
+            // Use the unhovered size so the pick doesn't flicker as the hover changes it
+            let explored = self.is_explored(system.id, perspective);
+            picker.sphere(system.id, system.position.into(), system.map_style(None, explored).0 * 1.2);
+        }
+        picker.nearest()
+    }
+    /// The lane drawn under the mouse, if any, with the lower id first.
+    #[cfg(feature = "gui")]
+    pub fn closest_lane_to_mouse(&self, rl: &RaylibHandle, camera: &Camera3D) -> Option<(SystemId, SystemId)> {
+        let mut picker = Picker::new(View::of_window(rl, camera), rl.get_mouse_position());
+        for system in self.systems.iter() {
+            for &other in system.connections.iter().filter(|other| **other > system.id) {
+                if let Some(other_system) = self.get(other) {
+                    picker.segment((system.id, other), system.position.into(), other_system.position.into());
                 }
             }
         }
-        closest 
+        picker.nearest()
+    }
+}
+
+/// Notes without the lines meant only for the GM, the ones starting with `GM:`.
+pub fn players_notes(notes: &str) -> String {
+    let lines: Vec<&str> = notes.lines().filter(|line| !line.trim_start().to_ascii_uppercase().starts_with("GM:")).collect();
+    lines.join("\n")
+}
+
+// Display names are hashes, so on the off chance two collide keep hashing until one is free.
+fn unique_name(systems: &[StarSystem], seed: usize) -> usize {
+    let mut seed = seed;
+    let mut name = crate::utils::hash_planet_id(seed) as u32 as usize;
+    while systems.iter().any(|sys| sys.name == name) {
+        seed += 1;
+        name = crate::utils::hash_planet_id(seed) as u32 as usize;
     }
+    name
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    /// A system as saves had it before parties, explored or not, with planets or not.
+    fn saved_system(id: Option<u64>, connections: &[u64], explored: bool, data: Option<&StarSystemData>) -> Value {
+        let mut system = json!({
+            "position": [1.0, 2.0, 3.0],
+            "origin": [1.0, 2.0, 3.0],
+            "drift_direction": [0.0, 0.0, 0.0],
+            "connections": connections,
+            "name": 1000 + id.unwrap_or(0),
+            "system_data": data,
+            "explored": explored,
+        });
+        if let Some(id) = id {
+            system["id"] = json!(id);
+        }
+        system
+    }
+
+    fn load(save: Value) -> Galaxy {
+        serde_json::from_value(save).unwrap()
+    }
+
+    fn ids(ids: &[u64]) -> Vec<SystemId> {
+        ids.iter().copied().map(SystemId).collect()
+    }
+
+    /// A scanned system with three planets, the middle one with a moon, and notes on all of
+    /// them with a line for the GM.
+    fn noted_data() -> StarSystemData {
+        let mut data = StarSystemData { star_mass: SOLAR_MASS, planets: Vec::new() };
+        for class in [PlanetClass::Volcanic, PlanetClass::Terran, PlanetClass::GasGiant] {
+            let planet = data.add_planet(class);
+            data.planets[planet].moons.clear();
+            data.planets[planet].notes = "Dusty\nGM: buried ship".to_string();
+        }
+        let moon = data.planets[1].add_moon(MoonType::RoundDusty);
+        data.planets[1].moons[moon].notes = "gm: cult base\nTidally locked".to_string();
+        data
+    }
+
+    /// Systems 0 to 4 in a line, all scanned and noted. Red has explored 0 and 1 and scanned
+    /// the last two planets of 0, Blue has explored 4.
+    fn charted() -> Galaxy {
+        let systems: Vec<Value> = (0..5u64).map(|id| {
+            let connections: Vec<u64> = [id.checked_sub(1), Some(id + 1).filter(|next| *next < 5)].into_iter().flatten().collect();
+            let mut system = saved_system(Some(id), &connections, false, Some(&noted_data()));
+            system["notes"] = json!("Trade hub\n  GM: the governor is a spy\nOpen port");
+            system["tags"] = json!(["station"]);
+            system
+        }).collect();
+        load(json!({
+            "version": 2,
+            "systems": systems,
+            "parties": [
+                {"name": "Red", "explored": {"0": 10, "1": 20}, "scanned": {"0": {"1": 30, "2": 40}}},
+                {"name": "Blue", "explored": {"4": 50}},
+            ]
+        }))
+    }
+
+    /// Every lane as its two ends, the way each end lists it.
+    fn lanes(galaxy: &Galaxy) -> Vec<(u64, u64)> {
+        galaxy.systems().iter().flat_map(|sys| sys.connections.iter().map(|conn| (sys.id.0, conn.0))).collect()
+    }
 
-pub fn ray_sphere_intersect(ray_pos: Vector3, ray_dir: Vector3, sphere_pos: Vector3, sphere_radius: f32) -> bool {
-    let l = Vector3 {
-        x: sphere_pos.x - ray_pos.x,
-        y: sphere_pos.y - ray_pos.y,
-        z: sphere_pos.z - ray_pos.z,
-    };
+    #[test]
+    fn gm_lines_are_taken_out_of_notes() {
+        assert_eq!(players_notes("Trade hub\nGM: the governor is a spy\nOpen port"), "Trade hub\nOpen port");
+        assert_eq!(players_notes("  gm: hidden\nGm:also hidden\nGMs are people too"), "GMs are people too");
+        assert_eq!(players_notes("GM: all of it"), "");
+        assert_eq!(players_notes("Nothing secret"), "Nothing secret");
+    }
 
-    let tca = l.x*ray_dir.x + l.y*ray_dir.y + l.z*ray_dir.z;
-    if tca < 0.0 { return false; }
+    #[test]
+    fn explored_systems_keep_all_but_gm_notes() {
+        let galaxy = charted().redacted(Perspective::Everyone, false);
+        assert!(galaxy.is_redacted());
+        for id in [0, 1, 4] {
+            let system = &galaxy[SystemId(id)];
+            assert_eq!(system.notes, "Trade hub\nOpen port");
+            assert_eq!(system.tags, ["station"]);
+            let data = system.system_data.as_ref().unwrap();
+            assert_eq!(data.planets.len(), 3);
+            assert!(data.planets.iter().all(|planet| planet.notes == "Dusty"));
+            assert_eq!(data.planets[1].moons[0].notes, "Tidally locked");
+        }
+    }
+
+    #[test]
+    fn unexplored_systems_only_keep_where_they_are() {
+        let original = charted();
+        let galaxy = original.redacted(Perspective::Everyone, false);
+        for id in [2, 3] {
+            let system = &galaxy[SystemId(id)];
+            assert!(system.system_data.is_none());
+            assert!(system.notes.is_empty());
+            assert!(system.tags.is_empty());
+            assert_eq!(system.origin(), original[SystemId(id)].origin());
+            assert_eq!(system.name, original[SystemId(id)].name);
+        }
+        // Without hiding lanes they're all still there
+        assert_eq!(lanes(&galaxy), lanes(&original));
+    }
 
-    let d2 = l.x*l.x + l.y*l.y + l.z*l.z - tca*tca;
-    d2 <= sphere_radius * sphere_radius
+    #[test]
+    fn unknown_lanes_can_be_hidden() {
+        let galaxy = charted().redacted(Perspective::Everyone, true);
+        // 2 and 3 are both unexplored, the rest of the lanes have an explored end
+        assert_eq!(lanes(&galaxy), [(0, 1), (1, 0), (1, 2), (2, 1), (3, 4), (4, 3)]);
+        let red = charted().redacted(Perspective::Party(0), true);
+        assert_eq!(lanes(&red), [(0, 1), (1, 0), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn party_copies_only_have_what_the_party_found() {
+        let original = charted();
+        let galaxy = original.redacted(Perspective::Party(0), false);
+        assert_eq!(galaxy.parties().len(), 1);
+        let red = &galaxy.parties()[0];
+        assert_eq!(red.name, "Red");
+        assert_eq!(galaxy.explored(Perspective::Everyone), HashSet::from([SystemId(0), SystemId(1)]));
+        // Blue's system is as unknown to Red as any other
+        assert!(galaxy[SystemId(4)].system_data.is_none());
+        assert!(galaxy[SystemId(4)].notes.is_empty());
+        // Only the planets Red scanned are left, numbered from 0 with their scan times
+        let planets = &galaxy[SystemId(0)].system_data.as_ref().unwrap().planets;
+        let scanned = &original[SystemId(0)].system_data.as_ref().unwrap().planets[1..];
+        assert_eq!(planets.iter().map(|planet| planet.class).collect::<Vec<_>>(), scanned.iter().map(|planet| planet.class).collect::<Vec<_>>());
+        assert_eq!(red.scanned[&SystemId(0)], BTreeMap::from([(0, 30), (1, 40)]));
+        assert_eq!(red.explored, BTreeMap::from([(SystemId(0), 10), (SystemId(1), 20)]));
+        // Explored but unscanned, so no planets at all
+        assert!(galaxy[SystemId(1)].system_data.as_ref().unwrap().planets.is_empty());
+        assert_eq!(galaxy[SystemId(1)].notes, "Trade hub\nOpen port");
+
+        let blue = original.redacted(Perspective::Party(1), false);
+        assert_eq!(blue.parties()[0].name, "Blue");
+        assert_eq!(blue.explored(Perspective::Everyone), HashSet::from([SystemId(4)]));
+        assert!(blue[SystemId(0)].system_data.is_none());
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_galaxy() {
+        for shape in Shape::ALL {
+            let first = serde_json::to_string(&Galaxy::with_seed(42, 40, 3, 1.0, 50.0, shape)).unwrap();
+            let second = serde_json::to_string(&Galaxy::with_seed(42, 40, 3, 1.0, 50.0, shape)).unwrap();
+            let other = serde_json::to_string(&Galaxy::with_seed(43, 40, 3, 1.0, 50.0, shape)).unwrap();
+            assert_eq!(first, second);
+            assert_ne!(first, other);
+        }
+    }
+
+    #[test]
+    fn bad_params_are_caught() {
+        assert!(Galaxy::check_params(200, 5, 250.0, 50.0).is_ok());
+        assert!(Galaxy::check_params(1, 0, 250.0, 50.0).is_err());
+        assert!(Galaxy::check_params(10, 10, 250.0, 50.0).is_err());
+        for (amplitude, radius) in [(0.0, 50.0), (-1.0, 50.0), (f64::NAN, 50.0), (f64::INFINITY, 50.0), (250.0, 0.0)] {
+            assert!(Galaxy::check_params(200, 5, amplitude, radius).is_err());
+        }
+    }
+
+    #[test]
+    fn version_0_connections_become_ids() {
+        let data = StarSystemData::generate(&mut StdRng::seed_from_u64(1));
+        let galaxy = load(json!({
+            "systems": [
+                saved_system(None, &[1, 2], true, Some(&data)),
+                saved_system(None, &[0], false, None),
+                saved_system(None, &[0], true, None),
+            ]
+        }));
+        let found: Vec<SystemId> = galaxy.systems().iter().map(StarSystem::id).collect();
+        assert_eq!(found, ids(&[0, 1, 2]));
+        assert_eq!(galaxy[SystemId(0)].connections, ids(&[1, 2]));
+        assert_eq!(galaxy[SystemId(2)].connections, ids(&[0]));
+        assert_eq!(galaxy.next_id, 3);
+        assert_eq!(galaxy.version, GALAXY_VERSION);
+    }
+
+    #[test]
+    fn explored_systems_go_to_the_first_party() {
+        let data = StarSystemData::generate(&mut StdRng::seed_from_u64(2));
+        let galaxy = load(json!({
+            "version": 1,
+            "next_id": 50,
+            "systems": [
+                saved_system(Some(10), &[20], true, Some(&data)),
+                saved_system(Some(20), &[10, 30], false, None),
+                saved_system(Some(30), &[20], true, None),
+            ]
+        }));
+        assert_eq!(galaxy[SystemId(20)].connections, ids(&[10, 30]));
+        assert_eq!(galaxy.next_id, 50);
+        assert_eq!(galaxy.parties().len(), 1);
+        let players = &galaxy.parties()[0];
+        assert_eq!(players.name, party::DEFAULT_PARTY);
+        assert_eq!(players.explored.keys().copied().collect::<Vec<_>>(), ids(&[10, 30]));
+        assert!(players.explored.values().all(|at| *at == 0));
+        // They'd seen every planet of the systems they explored
+        for planet in 0..data.planets.len() {
+            assert!(players.has_scanned(SystemId(10), planet));
+        }
+        assert!(!players.scanned.contains_key(&SystemId(30)));
+        assert!(galaxy.is_explored(SystemId(30), Perspective::Party(0)));
+        assert!(!galaxy.is_explored(SystemId(20), Perspective::Everyone));
+    }
+
+    #[test]
+    fn broken_lanes_are_dropped() {
+        let galaxy = load(json!({
+            "systems": [
+                saved_system(None, &[1, 2, 9], false, None),
+                saved_system(None, &[0, 2], false, None),
+                saved_system(None, &[0, 2], false, None),
+            ]
+        }));
+        // 9 doesn't exist, 2 doesn't list 1 back, and 2 can't lead to itself
+        assert_eq!(galaxy[SystemId(0)].connections, ids(&[1, 2]));
+        assert_eq!(galaxy[SystemId(1)].connections, ids(&[0]));
+        assert_eq!(galaxy[SystemId(2)].connections, ids(&[0]));
+        assert_eq!(galaxy.hops_from([SystemId(1)]).len(), 3);
+    }
+
+    #[test]
+    fn next_id_is_past_every_system() {
+        let galaxy = load(json!({
+            "version": 1,
+            "next_id": 2,
+            "systems": [saved_system(Some(7), &[], false, None)]
+        }));
+        assert_eq!(galaxy.next_id, 8);
+    }
+
+    #[test]
+    fn migrated_saves_round_trip() {
+        let galaxy = load(json!({
+            "version": 1,
+            "systems": [
+                saved_system(Some(0), &[1], true, None),
+                saved_system(Some(1), &[0], false, None),
+            ]
+        }));
+        let saved = serde_json::to_value(&galaxy).unwrap();
+        assert!(saved["systems"][0].get("explored").is_none());
+        let reloaded = load(saved);
+        assert_eq!(reloaded.parties(), galaxy.parties());
+        assert!(reloaded.is_explored(SystemId(0), Perspective::Everyone));
+    }
 }
@@ -0,0 +1,652 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use raylib::prelude::*;
+
+use crate::config::{self, Config, GalaxyParams};
+use crate::file_generator;
+use crate::gameloop::{menu_pressed, scaled_font};
+use crate::map::{Galaxy, Shape, SystemId};
+use crate::party::{self, Perspective};
+use crate::ui::{TextField, TextFieldEvent};
+
+/// How many saves the title menu remembers.
+const MAX_RECENT: usize = 8;
+
+/// How long the wizard's settings have to stay put before the preview is made again, so
+/// holding a key down doesn't generate a galaxy every frame.
+const PREVIEW_DELAY: Duration = Duration::from_millis(250);
+
+const SYSTEMS_RANGE: (usize, usize) = (10, 2000);
+const CONNECTIONS_RANGE: (usize, usize) = (1, 12);
+/// Jitter goes up in steps of a quarter, since small values matter as much as big ones.
+const JITTER_RANGE: (f64, f64) = (0.01, 500.0);
+const RADIUS_RANGE: (f64, f64) = (10.0, 150.0);
+
+const TITLE_HELP: &str = "Up/Down: Choose   Enter: Open   Delete: Forget Save";
+const WIZARD_HELP: &str = "Up/Down: Choose   Left/Right Or Drag: Change   R: New Seed   Enter: Type   Esc: Back";
+
+/// Where the list of recent saves is kept, next to the config file.
+fn recent_path() -> Option<PathBuf> {
+    Some(config::config_path()?.with_file_name("recent.json"))
+}
+
+/// The saves rim last wrote, newest first, leaving out any that have gone missing.
+pub(crate) fn recent_saves() -> Vec<String> {
+    let saves: Vec<String> = recent_path()
+        .and_then(|path| fs::read(path).ok())
+        .and_then(|data| serde_json::from_slice(&data).ok())
+        .unwrap_or_default();
+    saves.into_iter().filter(|save| Path::new(save).exists()).collect()
+}
+
+/// Puts a save at the top of the recent list. Failing to is no reason to bother anyone.
+pub(crate) fn remember_save(path: &String) {
+    let path = fs::canonicalize(path).map_or(path.clone(), |path| path.display().to_string());
+    let mut saves = recent_saves();
+    saves.retain(|save| *save != path);
+    saves.insert(0, path);
+    saves.truncate(MAX_RECENT);
+    write_recent(&saves);
+}
+
+fn write_recent(saves: &[String]) {
+    if let Some(path) = recent_path() {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, serde_json::to_vec(saves).unwrap());
+    }
+}
+
+/// A recent save as the title menu shows it.
+struct Slot {
+    path: String,
+    /// Where its systems rest seen from above, scaled to fit between -1 and 1, for the thumbnail.
+    points: Vec<(f32, f32)>,
+    /// Lanes between `points`, by index.
+    lanes: Vec<(usize, usize)>,
+    details: String,
+}
+
+impl Slot {
+    fn load(path: String) -> Slot {
+        let saved = file_generator::modified(&path)
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or("an unknown date".to_string(), |time| party::format_date(time.as_secs()));
+        let galaxy = match file_generator::load_file(&path) {
+            None => {
+                let details = format!("Couldn't be read, saved {}", saved);
+                return Slot { path, points: Vec::new(), lanes: Vec::new(), details }
+            }
+            Some(galaxy) => {galaxy}
+        };
+        let systems = galaxy.systems();
+        let extent = systems.iter().map(|system| system.origin().x.abs().max(system.origin().z.abs())).fold(1.0, f32::max);
+        let points = systems.iter().map(|system| (system.origin().x / extent, system.origin().z / extent)).collect();
+        let index: HashMap<SystemId, usize> = systems.iter().enumerate().map(|(i, system)| (system.id(), i)).collect();
+        let mut lanes = Vec::new();
+        for (i, system) in systems.iter().enumerate() {
+            for conn in &system.connections {
+                match index.get(conn) {
+                    Some(&j) if j > i => {lanes.push((i, j))}
+                    _ => {}
+                }
+            }
+        }
+        let parties = galaxy.parties().len();
+        let kind = if galaxy.is_redacted() { "player copy, " } else { "" };
+        let details = format!(
+            "{} systems, {} explored, {} {}, {}saved {}",
+            galaxy.len(), galaxy.explored(Perspective::Everyone).len(), parties,
+            if parties == 1 { "party" } else { "parties" }, kind, saved
+        );
+        Slot { path, points, lanes, details }
+    }
+
+    fn name(&self) -> String {
+        Path::new(&self.path).file_name().map_or(self.path.clone(), |name| name.to_string_lossy().to_string())
+    }
+}
+
+/// A row of the title menu.
+#[derive(Clone, Copy, PartialEq)]
+enum TitleRow {
+    /// Opens the save written last.
+    Continue,
+    NewGalaxy,
+    OpenFile,
+    Slot(usize),
+    Quit,
+}
+
+/// A setting in the new galaxy wizard.
+#[derive(Clone, Copy, PartialEq)]
+enum Setting {
+    Systems,
+    Connections,
+    Jitter,
+    Radius,
+    Shape,
+    Seed,
+    File,
+    Create,
+}
+
+impl Setting {
+    const ALL: [Setting; 8] = [
+        Setting::Systems, Setting::Connections, Setting::Jitter, Setting::Radius,
+        Setting::Shape, Setting::Seed, Setting::File, Setting::Create,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Setting::Systems => "Systems",
+            Setting::Connections => "Lanes Per System",
+            Setting::Jitter => "Jitter",
+            Setting::Radius => "Radius",
+            Setting::Shape => "Shape",
+            Setting::Seed => "Seed",
+            Setting::File => "File",
+            Setting::Create => "Create Galaxy",
+        }
+    }
+}
+
+/// The new galaxy wizard, with the galaxy its settings make so far.
+struct Wizard {
+    params: GalaxyParams,
+    seed: u64,
+    file: String,
+    selected: usize,
+    preview: Galaxy,
+    /// When the settings last changed, if the preview hasn't caught up with them.
+    changed: Option<Instant>,
+    /// The seed or file name being typed.
+    typing: Option<TextField>,
+    /// Whether a slider is being dragged with the mouse.
+    dragging: bool,
+}
+
+impl Wizard {
+    fn new(params: &GalaxyParams) -> Wizard {
+        let mut params = params.clone();
+        params.systems = params.systems.clamp(SYSTEMS_RANGE.0, SYSTEMS_RANGE.1);
+        params.connections_per_system = params.connections_per_system.clamp(CONNECTIONS_RANGE.0, CONNECTIONS_RANGE.1);
+        params.amplitude = params.amplitude.clamp(JITTER_RANGE.0, JITTER_RANGE.1);
+        params.radius = params.radius.clamp(RADIUS_RANGE.0, RADIUS_RANGE.1);
+        let seed = rand::random::<u32>() as u64;
+        let preview = generate(&params, seed);
+        Wizard { params, seed, file: free_file_name(), selected: 0, preview, changed: None, typing: None, dragging: false }
+    }
+
+    /// How far along its slider a setting is, from 0 to 1, or None if it doesn't have one.
+    fn fraction(&self, setting: Setting) -> Option<f32> {
+        let params = &self.params;
+        let fraction = match setting {
+            Setting::Systems => {(params.systems - SYSTEMS_RANGE.0) as f64 / (SYSTEMS_RANGE.1 - SYSTEMS_RANGE.0) as f64}
+            Setting::Connections => {
+                (params.connections_per_system - CONNECTIONS_RANGE.0) as f64 / (CONNECTIONS_RANGE.1 - CONNECTIONS_RANGE.0) as f64
+            }
+            Setting::Jitter => {(params.amplitude / JITTER_RANGE.0).ln() / (JITTER_RANGE.1 / JITTER_RANGE.0).ln()}
+            Setting::Radius => {(params.radius - RADIUS_RANGE.0) / (RADIUS_RANGE.1 - RADIUS_RANGE.0)}
+            _ => {return None}
+        };
+        Some(fraction as f32)
+    }
+
+    fn set_fraction(&mut self, setting: Setting, fraction: f32) {
+        let fraction = fraction.clamp(0.0, 1.0) as f64;
+        let params = &mut self.params;
+        match setting {
+            Setting::Systems => {
+                let systems = SYSTEMS_RANGE.0 as f64 + fraction * (SYSTEMS_RANGE.1 - SYSTEMS_RANGE.0) as f64;
+                params.systems = (systems / 10.0).round() as usize * 10;
+            }
+            Setting::Connections => {
+                let connections = CONNECTIONS_RANGE.0 as f64 + fraction * (CONNECTIONS_RANGE.1 - CONNECTIONS_RANGE.0) as f64;
+                params.connections_per_system = connections.round() as usize;
+            }
+            Setting::Jitter => {params.amplitude = JITTER_RANGE.0 * (JITTER_RANGE.1 / JITTER_RANGE.0).powf(fraction)}
+            Setting::Radius => {params.radius = (RADIUS_RANGE.0 + fraction * (RADIUS_RANGE.1 - RADIUS_RANGE.0)).round()}
+            _ => {return}
+        }
+        self.changed = Some(Instant::now());
+    }
+
+    /// Nudges a setting one step left (-1) or right (1).
+    fn step(&mut self, setting: Setting, direction: i32) {
+        let params = &mut self.params;
+        match setting {
+            Setting::Systems => {
+                params.systems = params.systems.saturating_add_signed(direction as isize * 10).clamp(SYSTEMS_RANGE.0, SYSTEMS_RANGE.1);
+            }
+            Setting::Connections => {
+                params.connections_per_system = params.connections_per_system.saturating_add_signed(direction as isize)
+                    .clamp(CONNECTIONS_RANGE.0, CONNECTIONS_RANGE.1);
+            }
+            Setting::Jitter => {params.amplitude = (params.amplitude * 1.25f64.powi(direction)).clamp(JITTER_RANGE.0, JITTER_RANGE.1)}
+            Setting::Radius => {params.radius = (params.radius + direction as f64 * 5.0).clamp(RADIUS_RANGE.0, RADIUS_RANGE.1)}
+            Setting::Shape => {
+                let index = Shape::ALL.iter().position(|shape| *shape == params.shape).unwrap_or(0) as i32;
+                params.shape = Shape::ALL[(index + direction).rem_euclid(Shape::ALL.len() as i32) as usize];
+            }
+            Setting::Seed => {self.seed = self.seed.saturating_add_signed(direction as i64)}
+            Setting::File | Setting::Create => {return}
+        }
+        self.changed = Some(Instant::now());
+    }
+
+    fn value_text(&self, setting: Setting) -> String {
+        match setting {
+            Setting::Systems => {self.params.systems.to_string()}
+            Setting::Connections => {self.params.connections_per_system.to_string()}
+            Setting::Jitter => {format!("{:.2}", self.params.amplitude)}
+            Setting::Radius => {format!("{:.0}", self.params.radius)}
+            Setting::Shape => {self.params.shape.name().to_string()}
+            Setting::Seed => {self.seed.to_string()}
+            Setting::File => {self.file.clone()}
+            Setting::Create => {String::new()}
+        }
+    }
+
+    /// Makes the preview again once the settings have settled, or straight away with `now`.
+    fn refresh(&mut self, now: bool) {
+        match self.changed {
+            Some(changed) if now || changed.elapsed() >= PREVIEW_DELAY => {
+                self.preview = generate(&self.params, self.seed);
+                self.changed = None;
+            }
+            _ => {}
+        }
+    }
+}
+
+fn generate(params: &GalaxyParams, seed: u64) -> Galaxy {
+    Galaxy::with_seed(seed, params.systems, params.connections_per_system, params.amplitude, params.radius, params.shape)
+}
+
+/// The first of galaxy.rim, galaxy-2.rim... that isn't taken.
+fn free_file_name() -> String {
+    (1..).map(|n| if n == 1 { "galaxy.rim".to_string() } else { format!("galaxy-{}.rim", n) })
+        .find(|name| !Path::new(name).exists())
+        .unwrap()
+}
+
+enum Screen {
+    Title,
+    Wizard(Box<Wizard>),
+    /// A path being typed to open.
+    Open(TextField),
+}
+
+/// Everything the title menu's screens share.
+struct Menu {
+    slots: Vec<Slot>,
+    selected: usize,
+    notice: Option<(String, f32)>,
+    font_size: i32,
+}
+
+impl Menu {
+    fn rows(&self) -> Vec<TitleRow> {
+        let mut rows = Vec::new();
+        if !self.slots.is_empty() {
+            rows.push(TitleRow::Continue);
+        }
+        rows.push(TitleRow::NewGalaxy);
+        rows.push(TitleRow::OpenFile);
+        rows.extend((0..self.slots.len()).map(TitleRow::Slot));
+        rows.push(TitleRow::Quit);
+        rows
+    }
+
+    fn notify(&mut self, text: String) {
+        self.notice = Some((text, 4.0));
+    }
+
+    /// Reads a save to play, or says why it can't be.
+    fn open(&mut self, path: &String) -> Option<(String, Galaxy)> {
+        match file_generator::load_file(path) {
+            Some(galaxy) => {Some((path.clone(), galaxy))}
+            None => {
+                self.notify(format!("Couldn't read {} as a galaxy", path));
+                None
+            }
+        }
+    }
+}
+
+/// The title menu shown when rim is started without a save. Returns the save to open, or a
+/// new galaxy with where to save it, or None to quit.
+pub(crate) fn title_menu(rl: &mut RaylibHandle, thread: &RaylibThread, config: &Config) -> Option<(String, Galaxy)> {
+    let slots = recent_saves().into_iter().map(Slot::load).collect();
+    let mut menu = Menu { slots, selected: 0, notice: None, font_size: config.hud.small_font_size };
+    let mut screen = Screen::Title;
+    let mut yaw: f32 = 0.0;
+    while !rl.window_should_close() {
+        let dt = rl.get_frame_time();
+        yaw += dt * 0.2;
+        menu.font_size = scaled_font(rl, &config.window, config.hud.small_font_size);
+        if let Some((_, remaining)) = &mut menu.notice {
+            *remaining -= dt;
+            if *remaining <= 0.0 {
+                menu.notice = None;
+            }
+        }
+        let mut next = None;
+        let mut create = false;
+        match &mut screen {
+            Screen::Title => {
+                let rows = menu.rows();
+                if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+                    menu.selected = (menu.selected + rows.len() - 1) % rows.len();
+                }
+                else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+                    menu.selected = (menu.selected + 1) % rows.len();
+                }
+                else if let (true, TitleRow::Slot(slot)) = (rl.is_key_pressed(KeyboardKey::KEY_DELETE), rows[menu.selected]) {
+                    // Only forgets the save, the file stays where it is
+                    let slot = menu.slots.remove(slot);
+                    write_recent(&menu.slots.iter().map(|slot| slot.path.clone()).collect::<Vec<String>>());
+                    menu.selected = menu.selected.min(menu.rows().len() - 1);
+                    menu.notify(format!("Forgot {}", slot.name()));
+                }
+                else if menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+                    match rows[menu.selected] {
+                        TitleRow::Continue => {
+                            let path = menu.slots[0].path.clone();
+                            if let Some(opened) = menu.open(&path) {
+                                return Some(opened)
+                            }
+                        }
+                        TitleRow::Slot(slot) => {
+                            let path = menu.slots[slot].path.clone();
+                            if let Some(opened) = menu.open(&path) {
+                                return Some(opened)
+                            }
+                        }
+                        TitleRow::NewGalaxy => {next = Some(Screen::Wizard(Box::new(Wizard::new(&config.galaxy))))}
+                        TitleRow::OpenFile => {next = Some(Screen::Open(TextField::new("Open", String::new())))}
+                        TitleRow::Quit => {return None}
+                    }
+                }
+            }
+            Screen::Open(field) => {
+                match field.update(rl) {
+                    None | Some(TextFieldEvent::Changed) => {}
+                    Some(TextFieldEvent::Cancel) => {next = Some(Screen::Title)}
+                    Some(TextFieldEvent::Submit) => {
+                        let path = field.text.trim().to_string();
+                        if let Some(opened) = menu.open(&path) {
+                            return Some(opened)
+                        }
+                    }
+                }
+            }
+            Screen::Wizard(wizard) => {
+                match update_wizard(rl, &mut menu, wizard) {
+                    WizardEvent::None => {}
+                    WizardEvent::Back => {next = Some(Screen::Title)}
+                    WizardEvent::Create => {create = true}
+                }
+                wizard.refresh(false);
+            }
+        }
+        screen = match screen {
+            Screen::Wizard(mut wizard) if create => {
+                wizard.refresh(true);
+                return Some((wizard.file, wizard.preview))
+            }
+            screen => {screen}
+        };
+        if let Some(next) = next {
+            screen = next;
+        }
+
+        let mut d = rl.begin_drawing(thread);
+        d.clear_background(Color::BLACK);
+        let font_size = menu.font_size;
+        match &screen {
+            Screen::Title => {draw_title(&mut d, &menu, font_size)}
+            Screen::Open(field) => {
+                draw_title(&mut d, &menu, font_size);
+                let width = (d.get_screen_width() / 2).max(font_size * 20);
+                let x = (d.get_screen_width() - width) / 2;
+                let y = d.get_screen_height() / 2;
+                field.draw(&mut d, x, y, width, font_size);
+            }
+            Screen::Wizard(wizard) => {draw_wizard(&mut d, wizard, font_size, yaw)}
+        }
+        if let Some((text, remaining)) = &menu.notice {
+            d.draw_text(text, font_size / 2, d.get_screen_height() - font_size * 2, font_size, Color::new(200, 200, 200, 255).alpha(remaining.min(1.0)));
+        }
+    }
+    None
+}
+
+enum WizardEvent {
+    None,
+    Back,
+    Create,
+}
+
+fn update_wizard(rl: &mut RaylibHandle, menu: &mut Menu, wizard: &mut Wizard) -> WizardEvent {
+    let setting = Setting::ALL[wizard.selected];
+    if let Some(field) = &mut wizard.typing {
+        match field.update(rl) {
+            None | Some(TextFieldEvent::Changed) => {}
+            Some(TextFieldEvent::Cancel) => {wizard.typing = None}
+            Some(TextFieldEvent::Submit) => {
+                let text = field.text.trim().to_string();
+                wizard.typing = None;
+                match setting {
+                    Setting::Seed => {
+                        match text.parse() {
+                            Ok(seed) => {
+                                wizard.seed = seed;
+                                wizard.changed = Some(Instant::now());
+                            }
+                            Err(_) => {menu.notify(format!("{} isn't a seed, seeds are whole numbers", text))}
+                        }
+                    }
+                    _ if text.is_empty() => {}
+                    _ if Path::new(&text).extension().is_none() => {wizard.file = format!("{}.rim", text)}
+                    _ => {wizard.file = text}
+                }
+            }
+        }
+        return WizardEvent::None
+    }
+
+    // Dragging along a slider's bar sets it
+    let layout = WizardLayout::new(menu.font_size);
+    if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+        let mouse = rl.get_mouse_position();
+        let row = (0..Setting::ALL.len())
+            .find(|row| wizard.fraction(Setting::ALL[*row]).is_some() && layout.bar(*row).check_collision_point_rec(mouse));
+        if let Some(row) = row {
+            wizard.selected = row;
+            wizard.dragging = true;
+        }
+    }
+    if !rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_LEFT) {
+        wizard.dragging = false;
+    }
+    if wizard.dragging {
+        let fraction = (rl.get_mouse_x() - layout.bar_x) as f32 / layout.bar_width as f32;
+        wizard.set_fraction(Setting::ALL[wizard.selected], fraction);
+        return WizardEvent::None
+    }
+
+    if menu_pressed(rl, KeyboardKey::KEY_ESCAPE, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_RIGHT) {
+        return WizardEvent::Back
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_UP, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_UP) {
+        wizard.selected = (wizard.selected + Setting::ALL.len() - 1) % Setting::ALL.len();
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_DOWN, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_DOWN) {
+        wizard.selected = (wizard.selected + 1) % Setting::ALL.len();
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_LEFT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_LEFT) || rl.is_key_pressed_repeat(KeyboardKey::KEY_LEFT) {
+        wizard.step(setting, -1);
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_RIGHT, GamepadButton::GAMEPAD_BUTTON_LEFT_FACE_RIGHT) || rl.is_key_pressed_repeat(KeyboardKey::KEY_RIGHT) {
+        wizard.step(setting, 1);
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_R, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_UP) {
+        wizard.seed = rand::random::<u32>() as u64;
+        wizard.changed = Some(Instant::now());
+    }
+    else if menu_pressed(rl, KeyboardKey::KEY_ENTER, GamepadButton::GAMEPAD_BUTTON_RIGHT_FACE_DOWN) {
+        match setting {
+            Setting::Seed => {wizard.typing = Some(TextField::new("Seed", String::new()))}
+            Setting::File => {wizard.typing = Some(TextField::new("File", wizard.file.clone()))}
+            Setting::Create if Path::new(&wizard.file).exists() => {
+                menu.notify(format!("{} already exists, pick another file", wizard.file));
+            }
+            Setting::Create => {return WizardEvent::Create}
+            _ => {}
+        }
+    }
+    WizardEvent::None
+}
+
+/// Where the wizard's rows and slider bars go, worked out the same way for drawing them
+/// and for dragging the sliders.
+struct WizardLayout {
+    margin: i32,
+    line_height: i32,
+    top: i32,
+    bar_x: i32,
+    bar_width: i32,
+}
+
+impl WizardLayout {
+    fn new(font_size: i32) -> WizardLayout {
+        let margin = font_size;
+        // Room for the longest label
+        let bar_x = margin * 2 + font_size * 9;
+        WizardLayout { margin, line_height: font_size * 2, top: margin * 5, bar_x, bar_width: font_size * 12 }
+    }
+
+    fn row_y(&self, row: usize) -> i32 {
+        self.top + row as i32 * self.line_height
+    }
+
+    /// The bar of the slider on `row`, a little taller than it's drawn so it's easy to grab.
+    fn bar(&self, row: usize) -> Rectangle {
+        let y = self.row_y(row);
+        Rectangle::new(self.bar_x as f32, y as f32 - self.margin as f32 / 4.0, self.bar_width as f32, self.line_height as f32 * 0.75)
+    }
+}
+
+fn draw_title(d: &mut RaylibDrawHandle, menu: &Menu, font_size: i32) {
+    let margin = font_size;
+    let line_height = font_size * 3 / 2;
+    let text_color = Color::new(200, 200, 200, 255);
+
+    let thumbnail = line_height * 2;
+    let width = d.get_screen_width() - margin;
+    let rows = menu.rows();
+    let heights: Vec<i32> = rows.iter().map(|row| match row {
+        TitleRow::Slot(_) => {thumbnail + font_size / 2}
+        _ => {line_height}
+    }).collect();
+    // Scroll so the selected row stays on screen
+    let top = margin * 6;
+    let selected_bottom = top + heights[..=menu.selected].iter().sum::<i32>();
+    let mut y = top - (selected_bottom - (d.get_screen_height() - margin * 3)).max(0);
+    for (row, (item, &height)) in rows.iter().zip(&heights).enumerate() {
+        if row == menu.selected {
+            d.draw_rectangle(margin / 2, y - font_size / 4, width, height, Color::POWDERBLUE.alpha(0.2));
+        }
+        match item {
+            TitleRow::Continue => {d.draw_text(&format!("Continue {}", menu.slots[0].name()), margin, y, font_size, text_color)}
+            TitleRow::NewGalaxy => {d.draw_text("New Galaxy...", margin, y, font_size, text_color)}
+            TitleRow::OpenFile => {d.draw_text("Open File...", margin, y, font_size, text_color)}
+            TitleRow::Quit => {d.draw_text("Quit", margin, y, font_size, text_color)}
+            TitleRow::Slot(slot) => {
+                let slot = &menu.slots[*slot];
+                draw_thumbnail(d, slot, margin, y, thumbnail);
+                let x = margin * 2 + thumbnail;
+                d.draw_text(&slot.name(), x, y, font_size, text_color);
+                d.draw_text(&slot.details, x, y + line_height, font_size * 3 / 4, text_color.alpha(0.7));
+                d.draw_text(&slot.path, x, y + line_height * 2, font_size * 3 / 4, text_color.alpha(0.5));
+            }
+        }
+        y += height;
+    }
+    // Over any rows scrolled up past the top
+    d.draw_rectangle(0, 0, d.get_screen_width(), top - font_size / 2, Color::BLACK);
+    d.draw_text("RIM", margin, margin, font_size * 3, text_color);
+    d.draw_text(TITLE_HELP, margin, margin + font_size * 7 / 2, font_size * 3 / 4, text_color.alpha(0.6));
+}
+
+/// A save's systems and lanes seen from above, in a square `size` pixels wide.
+fn draw_thumbnail(d: &mut RaylibDrawHandle, slot: &Slot, x: i32, y: i32, size: i32) {
+    d.draw_rectangle(x, y, size, size, Color::new(20, 20, 30, 255));
+    let half = size as f32 / 2.0;
+    let to_screen = |(px, py): (f32, f32)| Vector2::new(x as f32 + half + px * half * 0.9, y as f32 + half + py * half * 0.9);
+    for &(i, j) in &slot.lanes {
+        d.draw_line_v(to_screen(slot.points[i]), to_screen(slot.points[j]), Color::new(255, 255, 255, 25));
+    }
+    for &point in &slot.points {
+        d.draw_circle_v(to_screen(point), 1.0, Color::new(130, 110, 150, 255));
+    }
+}
+
+fn draw_wizard(d: &mut RaylibDrawHandle, wizard: &Wizard, font_size: i32, yaw: f32) {
+    // The preview turns slowly behind the settings
+    let distance = wizard.preview.radius() * 3.0;
+    let position = Vector3::new(yaw.cos() * distance, distance * 0.5, yaw.sin() * distance);
+    let camera = Camera3D::perspective(position, Vector3::zero(), Vector3::new(0.0, 1.0, 0.0), 45.0);
+    {
+        let mut d3 = d.begin_mode3D(camera);
+        let galaxy = &wizard.preview;
+        for system in galaxy.systems() {
+            // Drawn as if explored, which is easier to see than how a new galaxy starts out
+            let (size, color) = system.map_style(None, true);
+            d3.draw_sphere(system.position, size, color);
+            for &conn in &system.connections {
+                if conn > system.id() {
+                    d3.draw_line_3D(system.position, galaxy[conn].position, Color::new(255, 255, 255, 40));
+                }
+            }
+        }
+    }
+
+    let layout = WizardLayout::new(font_size);
+    let margin = layout.margin;
+    let text_color = Color::new(200, 200, 200, 255);
+    let width = layout.bar_x + layout.bar_width + font_size * 6;
+    let height = layout.row_y(Setting::ALL.len()) + margin;
+    d.draw_rectangle(margin / 2, margin / 2, width, height, Color::BLACK.alpha(0.7));
+    let title = if wizard.changed.is_some() { "NEW GALAXY (updating...)" } else { "NEW GALAXY" };
+    d.draw_text(title, margin, margin, font_size * 3 / 2, text_color);
+    d.draw_text(WIZARD_HELP, margin, margin + font_size * 2, font_size * 3 / 4, text_color.alpha(0.6));
+    for (row, setting) in Setting::ALL.iter().enumerate() {
+        let y = layout.row_y(row);
+        if row == wizard.selected {
+            d.draw_rectangle(margin / 2, y - font_size / 2, width, layout.line_height, Color::POWDERBLUE.alpha(0.2));
+        }
+        d.draw_text(setting.label(), margin, y, font_size, text_color);
+        let value = wizard.value_text(*setting);
+        match wizard.fraction(*setting) {
+            None => {d.draw_text(&value, layout.bar_x, y, font_size, text_color)}
+            Some(fraction) => {
+                let bar_y = y + font_size / 2 - 2;
+                d.draw_rectangle(layout.bar_x, bar_y, layout.bar_width, 4, Color::new(80, 80, 90, 255));
+                let knob = layout.bar_x + (fraction * layout.bar_width as f32) as i32;
+                d.draw_rectangle(layout.bar_x, bar_y, knob - layout.bar_x, 4, Color::POWDERBLUE.alpha(0.8));
+                d.draw_circle(knob, bar_y + 2, font_size as f32 / 3.0, Color::POWDERBLUE);
+                d.draw_text(&value, layout.bar_x + layout.bar_width + margin, y, font_size, text_color);
+            }
+        }
+    }
+    if let Some(field) = &wizard.typing {
+        field.draw(d, margin, height + margin, width - margin, font_size);
+    }
+}
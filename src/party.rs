@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use crate::map::SystemId;
+
+/// A group of players exploring the galaxy, and what they've found so far.
+/// Times are seconds since 1970, and 0 when they aren't known, like for systems
+/// explored before saves had parties.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Party {
+    pub name: String,
+    /// The systems the party has been to, with when they first got there.
+    #[serde(default)]
+    pub explored: BTreeMap<SystemId, u64>,
+    /// The planets the party has scanned, by system and then planet, with when.
+    #[serde(default)]
+    pub scanned: BTreeMap<SystemId, BTreeMap<usize, u64>>,
+}
+
+impl Party {
+    pub fn new(name: &str) -> Party {
+        Party { name: name.to_string(), explored: BTreeMap::new(), scanned: BTreeMap::new() }
+    }
+
+    pub fn has_explored(&self, id: SystemId) -> bool {
+        self.explored.contains_key(&id)
+    }
+
+    pub fn has_scanned(&self, id: SystemId, planet: usize) -> bool {
+        self.scanned.get(&id).is_some_and(|planets| planets.contains_key(&planet))
+    }
+}
+
+/// The name the party made for old saves is given.
+pub const DEFAULT_PARTY: &str = "Players";
+
+/// Whose knowledge the map is showing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Perspective {
+    /// What any party knows, which is how the GM sees the galaxy.
+    #[default]
+    Everyone,
+    /// What the party at this index in `Galaxy::parties` knows.
+    Party(usize),
+}
+
+/// The current time for discoveries.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+/// A discovery time as a date like 2024-03-09, or "an unknown date" for 0.
+pub fn format_date(time: u64) -> String {
+    if time == 0 {
+        return "an unknown date".to_string()
+    }
+    // Days to a civil date, from Howard Hinnant's date algorithms
+    let days = (time / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+
+use raylib::prelude::{Camera3D, RaylibHandle, Vector2, Vector3};
+
+/// How many pixels past the edge of something the mouse can be and still pick it,
+/// so small or zoomed out things stay easy to hit.
+pub const TOLERANCE_PIXELS: f32 = 6.0;
+
+/// What an orthographic camera shows on a screen of a given size. Picking is worked out
+/// in screen space with this rather than with rays, so sizes and tolerances are in pixels.
+/// Every camera in Rim is orthographic.
+#[derive(Debug, Clone, Copy)]
+pub struct View {
+    position: Vector3,
+    forward: Vector3,
+    right: Vector3,
+    up: Vector3,
+    pixels_per_unit: f32,
+    center: Vector2,
+}
+
+impl View {
+    pub fn new(camera: &Camera3D, width: f32, height: f32) -> View {
+        let forward = (camera.target - camera.position).normalized();
+        let right = forward.cross(camera.up).normalized();
+        View {
+            position: camera.position,
+            forward,
+            right,
+            up: right.cross(forward),
+            pixels_per_unit: height / camera.fovy,
+            center: Vector2::new(width / 2.0, height / 2.0),
+        }
+    }
+
+    /// The view of `camera` in the current window.
+    pub fn of_window(rl: &RaylibHandle, camera: &Camera3D) -> View {
+        View::new(camera, rl.get_screen_width() as f32, rl.get_screen_height() as f32)
+    }
+
+    /// Where a point lands on screen, and how far in front of the camera it is.
+    pub fn project(&self, point: Vector3) -> (Vector2, f32) {
+        let relative = point - self.position;
+        let screen = Vector2::new(
+            self.center.x + relative.dot(self.right) * self.pixels_per_unit,
+            self.center.y - relative.dot(self.up) * self.pixels_per_unit,
+        );
+        (screen, relative.dot(self.forward))
+    }
+}
+
+/// Something under the mouse. `distance` is how many pixels outside its drawn edge the
+/// mouse is, 0 when it's right over it.
+#[derive(Debug, Clone, Copy)]
+struct Hit<T> {
+    item: T,
+    distance: f32,
+    depth: f32,
+}
+
+/// Finds what's under the mouse among spheres and line segments, like systems and lanes.
+/// When several things are hit the one the mouse is closest to wins, then the one closest
+/// to the camera, then the smallest item, so the answer never depends on the order
+/// things were offered in.
+pub struct Picker<T> {
+    view: View,
+    mouse: Vector2,
+    tolerance: f32,
+    best: Option<Hit<T>>,
+}
+
+impl<T: Copy + Ord> Picker<T> {
+    pub fn new(view: View, mouse: Vector2) -> Picker<T> {
+        Picker { view, mouse, tolerance: TOLERANCE_PIXELS, best: None }
+    }
+
+    /// A sphere of `radius` world units, as drawn.
+    pub fn sphere(&mut self, item: T, center: Vector3, radius: f32) {
+        let (screen, depth) = self.view.project(center);
+        let distance = (screen - self.mouse).length() - radius * self.view.pixels_per_unit;
+        self.offer(Hit { item, distance: distance.max(0.0), depth });
+    }
+
+    /// A line from `a` to `b`, drawn a pixel wide.
+    pub fn segment(&mut self, item: T, a: Vector3, b: Vector3) {
+        let (screen_a, depth_a) = self.view.project(a);
+        let (screen_b, depth_b) = self.view.project(b);
+        let along = screen_b - screen_a;
+        let length_squared = along.dot(along);
+        let t = if length_squared > 0.0 { ((self.mouse - screen_a).dot(along) / length_squared).clamp(0.0, 1.0) } else { 0.0 };
+        let closest = screen_a + along.scale_by(t);
+        let depth = depth_a + (depth_b - depth_a) * t;
+        self.offer(Hit { item, distance: (closest - self.mouse).length(), depth });
+    }
+
+    fn offer(&mut self, hit: Hit<T>) {
+        // Things behind the camera aren't drawn
+        if hit.depth < 0.0 || hit.distance > self.tolerance {
+            return
+        }
+        let better = match &self.best {
+            None => true,
+            Some(best) => {
+                hit.distance.total_cmp(&best.distance)
+                    .then(hit.depth.total_cmp(&best.depth))
+                    .then(hit.item.cmp(&best.item)) == Ordering::Less
+            }
+        };
+        if better {
+            self.best = Some(hit);
+        }
+    }
+
+    pub fn nearest(&self) -> Option<T> {
+        self.best.map(|hit| hit.item)
+    }
+}
+
+/// Point where a ray crosses the plane through `plane_pos` with the given normal.
+pub fn ray_plane_intersect(ray_pos: Vector3, ray_dir: Vector3, plane_pos: Vector3, plane_normal: Vector3) -> Option<Vector3> {
+    let denom = ray_dir.dot(plane_normal);
+    if denom.abs() < 1e-6 { return None; }
+    let t = (plane_pos - ray_pos).dot(plane_normal) / denom;
+    if t < 0.0 { return None; }
+    Some(ray_pos + ray_dir.scale_by(t))
+}
+
+/// First point where a ray enters a sphere, if it hits it at all.
+pub fn ray_sphere_hit(ray_pos: Vector3, ray_dir: Vector3, sphere_pos: Vector3, sphere_radius: f32) -> Option<Vector3> {
+    let l = sphere_pos - ray_pos;
+    let tca = l.dot(ray_dir);
+    let d2 = l.dot(l) - tca * tca;
+    let r2 = sphere_radius * sphere_radius;
+    if d2 > r2 { return None; }
+    let thc = (r2 - d2).sqrt();
+    let t = if tca - thc >= 0.0 { tca - thc } else { tca + thc };
+    if t < 0.0 { return None; }
+    Some(ray_pos + ray_dir.scale_by(t))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Looking straight down at the origin from above, 10 pixels to a unit on a 1000x1000 screen.
+    fn top_down(fovy: f32) -> View {
+        let camera = Camera3D::orthographic(Vector3::new(0.0, 100.0, 0.0), Vector3::zero(), Vector3::new(0.0, 0.0, -1.0), fovy);
+        View::new(&camera, 1000.0, 1000.0)
+    }
+
+    fn picker(view: View, x: f32, y: f32) -> Picker<u32> {
+        Picker::new(view, Vector2::new(x, y))
+    }
+
+    #[test]
+    fn projects_onto_the_screen() {
+        let view = top_down(100.0);
+        let (screen, depth) = view.project(Vector3::new(10.0, 0.0, -20.0));
+        assert!((screen.x - 600.0).abs() < 1e-3);
+        assert!((screen.y - 300.0).abs() < 1e-3);
+        assert!((depth - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn spheres_are_hit_at_their_drawn_size_plus_tolerance() {
+        let view = top_down(100.0);
+        // A radius of 1 is 10 pixels across
+        for (x, hit) in [(500.0, true), (509.0, true), (515.0, true), (517.0, false)] {
+            let mut picker = picker(view, x, 500.0);
+            picker.sphere(1, Vector3::zero(), 1.0);
+            assert_eq!(picker.nearest().is_some(), hit, "mouse at x = {}", x);
+        }
+    }
+
+    #[test]
+    fn tolerance_is_in_pixels_whatever_the_zoom() {
+        // Zoomed out, a tiny sphere is still pickable from a few pixels away
+        let mut zoomed_out = picker(top_down(1000.0), 504.0, 500.0);
+        zoomed_out.sphere(1, Vector3::zero(), 0.01);
+        assert_eq!(zoomed_out.nearest(), Some(1));
+        // Zoomed in, the same world distance is many pixels and misses
+        let mut zoomed_in = picker(top_down(1.0), 504.0 + 400.0, 500.0);
+        zoomed_in.sphere(1, Vector3::zero(), 0.01);
+        assert_eq!(zoomed_in.nearest(), None);
+    }
+
+    #[test]
+    fn the_sphere_closest_to_the_mouse_wins() {
+        let view = top_down(100.0);
+        let mut picker = picker(view, 512.0, 500.0);
+        picker.sphere(1, Vector3::zero(), 0.5);
+        picker.sphere(2, Vector3::new(1.5, 0.0, 0.0), 0.5);
+        assert_eq!(picker.nearest(), Some(2));
+    }
+
+    #[test]
+    fn overlapping_spheres_resolve_to_the_one_in_front() {
+        let view = top_down(100.0);
+        for order in [[1, 2], [2, 1]] {
+            let mut picker = picker(view, 500.0, 500.0);
+            for item in order {
+                let height = if item == 1 { 0.0 } else { 5.0 };
+                picker.sphere(item, Vector3::new(0.0, height, 0.0), 1.0);
+            }
+            assert_eq!(picker.nearest(), Some(2));
+        }
+    }
+
+    #[test]
+    fn ties_resolve_the_same_way_in_any_order() {
+        let view = top_down(100.0);
+        for order in [[3, 7], [7, 3]] {
+            let mut picker = picker(view, 500.0, 500.0);
+            for item in order {
+                picker.sphere(item, Vector3::zero(), 1.0);
+            }
+            assert_eq!(picker.nearest(), Some(3));
+        }
+    }
+
+    #[test]
+    fn things_behind_the_camera_are_ignored() {
+        let mut picker = picker(top_down(100.0), 500.0, 500.0);
+        picker.sphere(1, Vector3::new(0.0, 150.0, 0.0), 1.0);
+        assert_eq!(picker.nearest(), None);
+    }
+
+    #[test]
+    fn segments_are_hit_along_their_length_but_not_past_their_ends() {
+        let view = top_down(100.0);
+        let (a, b) = (Vector3::new(-10.0, 0.0, 0.0), Vector3::new(10.0, 0.0, 0.0));
+        for (x, y, hit) in [(500.0, 504.0, true), (580.0, 495.0, true), (500.0, 520.0, false), (605.0, 500.0, true), (620.0, 500.0, false)] {
+            let mut picker = picker(view, x, y);
+            picker.segment(1, a, b);
+            assert_eq!(picker.nearest().is_some(), hit, "mouse at {}, {}", x, y);
+        }
+    }
+
+    #[test]
+    fn a_sphere_under_the_mouse_beats_a_lane_next_to_it() {
+        let view = top_down(100.0);
+        let mut picker = picker(view, 500.0, 503.0);
+        picker.segment(1, Vector3::new(-10.0, 0.0, 0.0), Vector3::new(10.0, 0.0, 0.0));
+        picker.sphere(2, Vector3::zero(), 1.0);
+        assert_eq!(picker.nearest(), Some(2));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::map::{Galaxy, StarSystem, SystemId};
+
+/// Most results the search box lists.
+pub(crate) const MAX_RESULTS: usize = 12;
+
+/// Systems matching every word of `query`, best matches first. A word matches a system's
+/// hex name, its tags, its notes or the classes of its planets and moons, ignoring case,
+/// so "ocean" finds systems with an ocean world.
+pub(crate) fn search(galaxy: &Galaxy, query: &str) -> Vec<SystemId> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Vec::new()
+    }
+    let mut results: Vec<(u32, &StarSystem)> = galaxy.systems().iter()
+        .filter_map(|system| {
+            let fields = Fields::of(system);
+            terms.iter().map(|term| fields.score(term)).try_fold(0, |total, score| score.map(|score| total + score))
+                .map(|score| (score, system))
+        })
+        .collect();
+    results.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.name.cmp(&b.1.name)));
+    results.into_iter().take(MAX_RESULTS).map(|(_, system)| system.id()).collect()
+}
+
+/// The searchable text of a system, lowercased once up front.
+struct Fields {
+    name: String,
+    tags: Vec<String>,
+    notes: String,
+    classes: Vec<String>,
+}
+
+impl Fields {
+    fn of(system: &StarSystem) -> Fields {
+        let mut classes = Vec::new();
+        if let Some(data) = &system.system_data {
+            for planet in &data.planets {
+                classes.push(format!("{:?}", planet.class).to_lowercase());
+                classes.extend(planet.moons.iter().map(|moon| format!("{:?}", moon.moon_type).to_lowercase()));
+            }
+        }
+        Fields {
+            name: format!("{:x}", system.name),
+            tags: system.tags.iter().map(|tag| tag.to_lowercase()).collect(),
+            notes: system.notes.to_lowercase(),
+            classes,
+        }
+    }
+
+    /// How well one word matches, or None if it doesn't. Names and whole tags count
+    /// for more than a word turning up somewhere in the notes.
+    fn score(&self, term: &str) -> Option<u32> {
+        if self.name == term {
+            Some(8)
+        }
+        else if self.name.starts_with(term) {
+            Some(6)
+        }
+        else if self.tags.iter().any(|tag| tag == term) {
+            Some(5)
+        }
+        else if self.tags.iter().any(|tag| tag.contains(term)) {
+            Some(3)
+        }
+        else if self.classes.iter().any(|class| class.contains(term)) {
+            Some(2)
+        }
+        else if self.notes.contains(term) {
+            Some(1)
+        }
+        else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rim::system::{MoonType, PlanetClass, StarSystemData, SOLAR_MASS};
+    use serde_json::json;
+
+    /// A system's name, tags, notes, and planets with their moons.
+    type Spec<'a> = (usize, &'a [&'a str], &'a str, &'a [(PlanetClass, &'a [MoonType])]);
+
+    /// Unconnected systems made to `Spec`s.
+    fn galaxy(systems: &[Spec]) -> Galaxy {
+        let systems: Vec<_> = systems.iter().enumerate().map(|(i, (name, tags, notes, planets))| {
+            let mut data = StarSystemData { star_mass: SOLAR_MASS, planets: Vec::new() };
+            for (class, moons) in planets.iter() {
+                let planet = data.add_planet(*class);
+                let planet = &mut data.planets[planet];
+                planet.moons.clear();
+                for moon in moons.iter() {
+                    planet.add_moon(*moon);
+                }
+            }
+            json!({
+                "id": i, "position": [0.0, 0.0, 0.0], "origin": [0.0, 0.0, 0.0], "drift_direction": [0.0, 0.0, 0.0],
+                "connections": [], "name": name, "system_data": data, "tags": tags, "notes": notes,
+            })
+        }).collect();
+        serde_json::from_value(json!({"version": 2, "systems": systems})).unwrap()
+    }
+
+    fn names(galaxy: &Galaxy, found: Vec<SystemId>) -> Vec<usize> {
+        found.into_iter().map(|id| galaxy[id].name).collect()
+    }
+
+    #[test]
+    fn names_tags_notes_and_classes_match() {
+        let galaxy = galaxy(&[
+            (0xA1, &["Capital"], "", &[]),
+            (0xB2, &[], "Pirates hide here", &[(PlanetClass::Desert, &[])]),
+            (0xC3, &[], "", &[(PlanetClass::OceanWorld, &[])]),
+            (0xD4, &[], "", &[(PlanetClass::GasGiant, &[MoonType::SubsurfaceOcean])]),
+        ]);
+        assert_eq!(names(&galaxy, search(&galaxy, "a1")), [0xA1]);
+        assert_eq!(names(&galaxy, search(&galaxy, "CAPITAL")), [0xA1]);
+        assert_eq!(names(&galaxy, search(&galaxy, "pirates")), [0xB2]);
+        assert_eq!(names(&galaxy, search(&galaxy, "desert")), [0xB2]);
+        assert_eq!(names(&galaxy, search(&galaxy, "subsurface")), [0xD4]);
+        // Ocean worlds and subsurface ocean moons score the same, so they go by name
+        assert_eq!(names(&galaxy, search(&galaxy, "ocean")), [0xC3, 0xD4]);
+        assert!(search(&galaxy, "volcanic").is_empty());
+    }
+
+    #[test]
+    fn every_word_has_to_match() {
+        let galaxy = galaxy(&[
+            (0xA1, &[], "pirates", &[(PlanetClass::OceanWorld, &[])]),
+            (0xB2, &[], "pirates", &[]),
+            (0xC3, &[], "", &[(PlanetClass::OceanWorld, &[])]),
+        ]);
+        assert_eq!(names(&galaxy, search(&galaxy, "ocean pirates")), [0xA1]);
+        assert!(search(&galaxy, "   ").is_empty());
+    }
+
+    #[test]
+    fn better_matches_come_first() {
+        let galaxy = galaxy(&[
+            (0x1, &[], "abc", &[]),
+            (0x2, &["abcd"], "", &[]),
+            (0x3, &["abc"], "", &[]),
+            (0xABC1, &[], "", &[]),
+            (0xABC, &[], "", &[]),
+        ]);
+        // Whole name, start of a name, whole tag, part of a tag, notes
+        assert_eq!(names(&galaxy, search(&galaxy, "abc")), [0xABC, 0xABC1, 0x3, 0x2, 0x1]);
+    }
+
+    #[test]
+    fn results_are_capped() {
+        let systems: Vec<Spec> = (0..MAX_RESULTS + 5).map(|i| -> Spec { (0x100 + i, &[], "match", &[]) }).collect();
+        let galaxy = galaxy(&systems);
+        assert_eq!(search(&galaxy, "match").len(), MAX_RESULTS);
+    }
+}
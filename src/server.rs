@@ -0,0 +1,420 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use crate::map::{Galaxy, StarSystem, SystemId};
+use crate::party::Perspective;
+
+/// What the JSON API can be asked to do. Requests are parsed on the server's threads and
+/// carried out by the game loop, on the same galaxy the viewer shows.
+///
+/// - `GET /systems` lists every system
+/// - `GET /systems/<id>` gives a system with its planets and moons
+/// - `GET /focus` and `POST /focus {"id": <id or null>}` read and move the map's focus
+/// - `POST /systems/<id>/explored {"explored": true, "party": "..."}` marks a system explored
+///   by a party, or not. Without a party it's the one the viewer has chosen
+/// - `POST /systems/<id>/notes {"text": "..."}` adds a line to a system's notes
+/// - `GET /events` streams server-sent events: `changed` with the ids of systems that
+///   changed, and `focus` when the focus moves
+///
+/// Every POST has to be sent as `application/json`, even without a body.
+#[derive(Debug, Clone)]
+pub(crate) enum Command {
+    ListSystems,
+    GetSystem(SystemId),
+    GetFocus,
+    SetFocus(Option<SystemId>),
+    SetExplored(SystemId, bool, Option<String>),
+    AddNote(SystemId, String),
+}
+
+/// A status code and JSON body to send back.
+pub(crate) struct Response {
+    status: u16,
+    body: Value,
+}
+
+impl Response {
+    pub fn ok(body: Value) -> Response {
+        Response { status: 200, body }
+    }
+
+    pub fn error(status: u16, message: &str) -> Response {
+        Response { status, body: json!({ "error": message }) }
+    }
+
+    pub fn no_system(id: SystemId) -> Response {
+        Response::error(404, &format!("No system with id {}", id.0))
+    }
+}
+
+/// A command waiting for the game loop to answer it.
+pub(crate) struct ApiRequest {
+    pub command: Command,
+    reply: Sender<Response>,
+}
+
+impl ApiRequest {
+    pub fn respond(self, response: Response) {
+        // The connection may have timed out and gone already
+        let _ = self.reply.send(response);
+    }
+}
+
+/// The game loop's end of a running API server.
+pub(crate) struct Server {
+    pub port: u16,
+    requests: Receiver<ApiRequest>,
+    broadcasts: Sender<Broadcast>,
+    reported_focus: Option<SystemId>,
+}
+
+enum Broadcast {
+    Subscribe(TcpStream),
+    Event(String),
+}
+
+/// The longest request body read, which is plenty for a note.
+const MAX_BODY: usize = 64 * 1024;
+/// How long a request waits for the game loop before giving up.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Server {
+    /// Starts listening on `port`, on localhost only.
+    pub fn start(port: u16) -> io::Result<Server> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let port = listener.local_addr()?.port();
+        let (request_sender, requests) = mpsc::channel();
+        let (broadcasts, broadcast_receiver) = mpsc::channel();
+        thread::spawn(move || broadcast_events(broadcast_receiver));
+        let subscribe = broadcasts.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let (requests, subscribe) = (request_sender.clone(), subscribe.clone());
+                thread::spawn(move || handle_connection(stream, port, requests, subscribe));
+            }
+        });
+        Ok(Server { port, requests, broadcasts, reported_focus: None })
+    }
+
+    /// The requests that came in since the last call.
+    pub fn requests(&self) -> Vec<ApiRequest> {
+        self.requests.try_iter().collect()
+    }
+
+    pub fn changed(&self, systems: &[SystemId]) {
+        if !systems.is_empty() {
+            let ids: Vec<u64> = systems.iter().map(|id| id.0).collect();
+            self.send_event("changed", json!({ "systems": ids }));
+        }
+    }
+
+    /// Sends a `focus` event if the focus isn't what listeners were last told.
+    pub fn focus_changed(&mut self, focus: Option<SystemId>) {
+        if focus != self.reported_focus {
+            self.reported_focus = focus;
+            self.send_event("focus", json!({ "id": focus.map(|id| id.0) }));
+        }
+    }
+
+    fn send_event(&self, event: &str, data: Value) {
+        let _ = self.broadcasts.send(Broadcast::Event(format!("event: {}\ndata: {}\n\n", event, data)));
+    }
+}
+
+/// A system as it appears in the list of systems.
+pub(crate) fn system_summary(galaxy: &Galaxy, system: &StarSystem) -> Value {
+    let origin = system.origin();
+    let explored_by: Vec<&str> = galaxy.parties().iter()
+        .filter(|party| party.has_explored(system.id()))
+        .map(|party| party.name.as_str())
+        .collect();
+    json!({
+        "id": system.id().0,
+        "name": format!("{:X}", system.name),
+        "position": [origin.x, origin.y, origin.z],
+        "connections": system.connections.iter().map(|id| id.0).collect::<Vec<u64>>(),
+        "explored": galaxy.is_explored(system.id(), Perspective::Everyone),
+        "explored_by": explored_by,
+        "scanned": system.system_data.is_some(),
+        "tags": system.tags,
+    })
+}
+
+/// Everything about a system, with its planets and moons if it's been scanned.
+pub(crate) fn system_details(galaxy: &Galaxy, system: &StarSystem) -> Value {
+    let mut details = system_summary(galaxy, system);
+    details["notes"] = json!(system.notes);
+    if let Some(data) = &system.system_data {
+        let planets: Vec<Value> = data.planets.iter().enumerate().map(|(i, planet)| {
+            let moons: Vec<Value> = planet.moons.iter().enumerate().map(|(j, moon)| json!({
+                "designation": system.moon_designation(i, j),
+                "type": moon.moon_type,
+                "mass": moon.mass,
+                "orbital_radius": moon.orbital_radius,
+                "notes": moon.notes,
+            })).collect();
+            json!({
+                "designation": system.planet_designation(i),
+                "class": planet.class,
+                "mass": planet.mass,
+                "orbit_radius": planet.orbit_radius,
+                "rings": planet.rings.as_ref().map(|rings| [rings.inner, rings.outer]),
+                "notes": planet.notes,
+                "moons": moons,
+            })
+        }).collect();
+        details["star_mass"] = json!(data.star_solar_masses());
+        details["habitability"] = json!(data.habitability());
+        details["planets"] = json!(planets);
+    }
+    details
+}
+
+// Answers one request and closes the connection, or hands it to the broadcaster
+// if it's asking for events.
+fn handle_connection(mut stream: TcpStream, port: u16, requests: Sender<ApiRequest>, subscribe: Sender<Broadcast>) {
+    let _ = stream.set_read_timeout(Some(REPLY_TIMEOUT));
+    let (method, path, body) = match read_request(&stream, port) {
+        Ok(request) => {request}
+        Err(response) => {
+            write_response(&mut stream, response);
+            return
+        }
+    };
+    if method == "GET" && path == "/events" {
+        let headers = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+        if stream.write_all(headers.as_bytes()).is_ok() {
+            let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+            let _ = subscribe.send(Broadcast::Subscribe(stream));
+        }
+        return
+    }
+    let response = match parse_command(&method, &path, &body) {
+        Err(response) => {response}
+        Ok(command) => {
+            let (reply, response) = mpsc::channel();
+            let _ = requests.send(ApiRequest { command, reply });
+            response.recv_timeout(REPLY_TIMEOUT)
+                .unwrap_or_else(|_| Response::error(503, "The viewer didn't answer in time"))
+        }
+    };
+    write_response(&mut stream, response);
+}
+
+// The method, path and body of a request. Query strings are ignored.
+fn read_request(stream: impl Read, port: u16) -> Result<(String, String, Vec<u8>), Response> {
+    let bad_request = |_| Response::error(400, "Could not read the request");
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(bad_request)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => {(method.to_string(), target.to_string())}
+        _ => {return Err(Response::error(400, "Malformed request line"))}
+    };
+    let mut content_length = 0;
+    let mut json = false;
+    let mut host = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).map_err(bad_request)? == 0 || header.trim().is_empty() {
+            break
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => {content_length = value.parse().map_err(|_| Response::error(400, "Bad Content-Length"))?}
+                "content-type" => {json = value.starts_with("application/json")}
+                "host" => {host = Some(value.to_ascii_lowercase())}
+                _ => {}
+            }
+        }
+    }
+    if content_length > MAX_BODY {
+        return Err(Response::error(413, "Request body is too large"))
+    }
+    // A web page whose domain was pointed at 127.0.0.1 still sends its own domain as the
+    // host, so checking it stops pages from reading the galaxy that way
+    let allowed = [format!("localhost:{}", port), format!("127.0.0.1:{}", port)];
+    if !host.is_some_and(|host| allowed.contains(&host)) {
+        return Err(Response::error(403, "Requests must be made to localhost"))
+    }
+    // Insisting on JSON, body or not, means browsers ask before sending a POST from another
+    // site, so web pages can't quietly drive the viewer
+    if method == "POST" && !json {
+        return Err(Response::error(415, "POST requests must be application/json"))
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).map_err(bad_request)?;
+    let path = target.split('?').next().unwrap_or("").trim_end_matches('/').to_string();
+    Ok((method, path, body))
+}
+
+fn parse_command(method: &str, path: &str, body: &[u8]) -> Result<Command, Response> {
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let body: Value = if body.is_empty() {
+        json!({})
+    }
+    else {
+        serde_json::from_slice(body).map_err(|err| Response::error(400, &format!("Body is not valid JSON: {}", err)))?
+    };
+    let system_id = |segment: &str| {
+        segment.parse().map(SystemId).map_err(|_| Response::error(404, &format!("No system with id {}", segment)))
+    };
+    match (method, segments.as_slice()) {
+        ("GET", ["systems"]) => {Ok(Command::ListSystems)}
+        ("GET", ["systems", id]) => {Ok(Command::GetSystem(system_id(id)?))}
+        ("GET", ["focus"]) => {Ok(Command::GetFocus)}
+        ("POST", ["focus"]) => {
+            match body.get("id") {
+                None => {Err(Response::error(400, "id is required, null clears the focus"))}
+                Some(Value::Null) => {Ok(Command::SetFocus(None))}
+                Some(Value::Number(id)) => {
+                    let id = id.as_u64().ok_or_else(|| Response::error(400, "id must be a system id"))?;
+                    Ok(Command::SetFocus(Some(SystemId(id))))
+                }
+                _ => {Err(Response::error(400, "id must be a system id or null"))}
+            }
+        }
+        ("POST", ["systems", id, "explored"]) => {
+            let explored = match &body["explored"] {
+                Value::Bool(explored) => {*explored}
+                _ => {return Err(Response::error(400, "explored must be true or false"))}
+            };
+            let party = match &body["party"] {
+                Value::Null => {None}
+                Value::String(party) => {Some(party.clone())}
+                _ => {return Err(Response::error(400, "party must be a party's name"))}
+            };
+            Ok(Command::SetExplored(system_id(id)?, explored, party))
+        }
+        ("POST", ["systems", id, "notes"]) => {
+            match body["text"].as_str() {
+                Some(text) if !text.trim().is_empty() => {Ok(Command::AddNote(system_id(id)?, text.trim().to_string()))}
+                _ => {Err(Response::error(400, "text must be a non-empty string"))}
+            }
+        }
+        (_, ["systems"] | ["systems", _] | ["focus"] | ["systems", _, "explored" | "notes"]) => {
+            Err(Response::error(405, "Method not allowed"))
+        }
+        _ => {Err(Response::error(404, "Not found"))}
+    }
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) {
+    let body = response.body.to_string();
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        _ => "Service Unavailable",
+    };
+    let head = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status, reason, body.len());
+    let _ = stream.write_all(head.as_bytes()).and_then(|_| stream.write_all(body.as_bytes()));
+}
+
+// Sends every event to every listener, forgetting listeners that have gone away.
+fn broadcast_events(broadcasts: Receiver<Broadcast>) {
+    let mut listeners: Vec<TcpStream> = Vec::new();
+    for broadcast in broadcasts {
+        match broadcast {
+            Broadcast::Subscribe(stream) => {listeners.push(stream)}
+            Broadcast::Event(event) => {
+                listeners.retain_mut(|stream| stream.write_all(event.as_bytes()).and_then(|_| stream.flush()).is_ok());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORT: u16 = 7878;
+
+    fn request(head: &str, body: &str) -> Result<(String, String, Vec<u8>), Response> {
+        let text = format!("{}\r\nContent-Length: {}\r\n\r\n{}", head, body.len(), body);
+        read_request(text.as_bytes(), PORT)
+    }
+
+    fn status(result: Result<(String, String, Vec<u8>), Response>) -> u16 {
+        result.err().map_or(200, |response| response.status)
+    }
+
+    fn command(method: &str, path: &str, body: &str) -> Result<Command, u16> {
+        parse_command(method, path, body.as_bytes()).map_err(|response| response.status)
+    }
+
+    #[test]
+    fn reads_a_request() {
+        let (method, path, body) = request("POST /systems/3/notes/?x=1 HTTP/1.1\r\nHost: localhost:7878\r\nContent-Type: application/json", "{\"text\":\"hi\"}").ok().unwrap();
+        assert_eq!(method, "POST");
+        assert_eq!(path, "/systems/3/notes");
+        assert_eq!(body, b"{\"text\":\"hi\"}");
+    }
+
+    #[test]
+    fn only_localhost_is_answered() {
+        assert_eq!(status(request("GET /systems HTTP/1.1\r\nHost: 127.0.0.1:7878", "")), 200);
+        assert_eq!(status(request("GET /systems HTTP/1.1\r\nHost: LocalHost:7878", "")), 200);
+        assert_eq!(status(request("GET /systems HTTP/1.1\r\nHost: evil.example:7878", "")), 403);
+        assert_eq!(status(request("GET /systems HTTP/1.1\r\nHost: localhost:80", "")), 403);
+        assert_eq!(status(request("GET /systems HTTP/1.1", "")), 403);
+    }
+
+    #[test]
+    fn posts_must_be_json_even_without_a_body() {
+        let host = "Host: localhost:7878";
+        assert_eq!(status(request(&format!("POST /focus HTTP/1.1\r\n{}", host), "")), 415);
+        assert_eq!(status(request(&format!("POST /focus HTTP/1.1\r\n{}\r\nContent-Type: text/plain", host), "{\"id\":null}")), 415);
+        assert_eq!(status(request(&format!("POST /focus HTTP/1.1\r\n{}\r\nContent-Type: application/json", host), "")), 200);
+    }
+
+    #[test]
+    fn oversized_bodies_are_refused() {
+        let head = format!("POST /focus HTTP/1.1\r\nHost: localhost:7878\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n", MAX_BODY + 1);
+        assert_eq!(status(read_request(head.as_bytes(), PORT)), 413);
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(command("GET", "/systems", ""), Ok(Command::ListSystems)));
+        assert!(matches!(command("GET", "/systems/12", ""), Ok(Command::GetSystem(SystemId(12)))));
+        assert!(matches!(command("POST", "/focus", "{\"id\": 4}"), Ok(Command::SetFocus(Some(SystemId(4))))));
+        assert!(matches!(command("POST", "/focus", "{\"id\": null}"), Ok(Command::SetFocus(None))));
+        assert!(matches!(command("POST", "/systems/2/explored", "{\"explored\": false, \"party\": \"Crew\"}"),
+            Ok(Command::SetExplored(SystemId(2), false, Some(party))) if party == "Crew"));
+        assert!(matches!(command("POST", "/systems/2/notes", "{\"text\": \" ruins \"}"),
+            Ok(Command::AddNote(SystemId(2), text)) if text == "ruins"));
+    }
+
+    #[test]
+    fn empty_bodies_change_nothing() {
+        assert_eq!(command("POST", "/focus", "").err(), Some(400));
+        assert_eq!(command("POST", "/focus", "{}").err(), Some(400));
+        assert_eq!(command("POST", "/systems/2/explored", "").err(), Some(400));
+        assert_eq!(command("POST", "/systems/2/explored", "{\"party\": \"Crew\"}").err(), Some(400));
+        assert_eq!(command("POST", "/systems/2/notes", "").err(), Some(400));
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert_eq!(command("GET", "/systems/abc", "").err(), Some(404));
+        assert_eq!(command("DELETE", "/systems", "").err(), Some(405));
+        assert_eq!(command("GET", "/planets", "").err(), Some(404));
+        assert_eq!(command("POST", "/focus", "{not json").err(), Some(400));
+        assert_eq!(command("POST", "/focus", "{\"id\": -1}").err(), Some(400));
+        assert_eq!(command("POST", "/systems/2/explored", "{\"explored\": \"yes\"}").err(), Some(400));
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::map::{Galaxy, StarSystem, SystemId};
+use crate::party::Party;
+
+/// The port a session is hosted on unless another is given.
+pub(crate) const DEFAULT_PORT: u16 = 7318;
+
+/// How long joining waits for the host to send the galaxy.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+/// How many messages can wait for a player before they're dropped for not keeping up.
+const PLAYER_BACKLOG: usize = 256;
+/// How long writing to a player can stall before they're given up on.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the host tells players, one JSON object a line. Players only ever get what
+/// `Galaxy::for_players` lets them see.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) enum Message {
+    /// Everything visible, sent once when a player joins.
+    Galaxy(Galaxy),
+    /// Systems that came into view or changed, to add or replace.
+    Upsert(Vec<StarSystem>),
+    /// Systems that went out of view or were deleted.
+    Remove(Vec<SystemId>),
+    /// The system the GM has focused on the map, if players can see it.
+    Focus(Option<SystemId>),
+    /// The route the GM has plotted, cut down to the systems players can see.
+    Route(Vec<SystemId>),
+    /// Every party and what they've found, whenever any of it changes.
+    Parties(Vec<Party>),
+}
+
+/// One side of a shared session.
+pub(crate) enum Session {
+    /// This rim owns the galaxy and players connect to it.
+    Host(Host),
+    /// This rim shows a host's galaxy and can't change it.
+    Player(Player),
+}
+
+/// The GM's end of a session. Everything players are sent goes through `update`,
+/// which works out what changed in what they can see since last time.
+pub(crate) struct Host {
+    pub port: u16,
+    joined: Receiver<TcpStream>,
+    players: Vec<SyncSender<String>>,
+    /// Each visible system as it was last sent, to send only what changed.
+    sent: HashMap<SystemId, String>,
+    visible: HashSet<SystemId>,
+    sent_focus: Option<SystemId>,
+    sent_route: Vec<SystemId>,
+    sent_parties: String,
+    /// Whether `sent` has been filled in yet.
+    started: bool,
+}
+
+impl Host {
+    /// Starts listening for players on `port`. Only other rims on this computer can join,
+    /// unless `lan` opens it to every interface so players on the LAN can. Anyone who can
+    /// reach the port can join, so `lan` is only for networks the GM trusts.
+    pub fn start(port: u16, lan: bool) -> io::Result<Host> {
+        let address = if lan { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+        let listener = TcpListener::bind((address, port))?;
+        let port = listener.local_addr()?.port();
+        let (join, joined) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if join.send(stream).is_err() {
+                    break
+                }
+            }
+        });
+        Ok(Host {
+            port, joined, players: Vec::new(), sent: HashMap::new(), visible: HashSet::new(),
+            sent_focus: None, sent_route: Vec::new(), sent_parties: String::new(), started: false
+        })
+    }
+
+    /// Sends players whatever has changed since the last call, and the whole visible galaxy
+    /// to anyone who has just joined. `changed` says whether the galaxy may have changed.
+    /// Returns how many players joined.
+    pub fn update(&mut self, galaxy: &Galaxy, changed: bool, focus: Option<SystemId>, route: &[SystemId]) -> usize {
+        if changed || !self.started {
+            self.started = true;
+            self.visible = galaxy.visible_to_players();
+            let removed: Vec<SystemId> = self.sent.keys().filter(|id| !self.visible.contains(id)).copied().collect();
+            for id in &removed {
+                self.sent.remove(id);
+            }
+            if !removed.is_empty() {
+                self.send(&Message::Remove(removed));
+            }
+            let mut upserts = Vec::new();
+            for system in galaxy.players_view(&self.visible) {
+                let json = serde_json::to_string(&system).unwrap();
+                if self.sent.get(&system.id()) != Some(&json) {
+                    self.sent.insert(system.id(), json);
+                    upserts.push(system);
+                }
+            }
+            if !upserts.is_empty() {
+                self.send(&Message::Upsert(upserts));
+            }
+            let parties = serde_json::to_string(galaxy.parties()).unwrap();
+            if parties != self.sent_parties {
+                self.sent_parties = parties;
+                self.send(&Message::Parties(galaxy.parties().to_vec()));
+            }
+        }
+        let focus = focus.filter(|id| self.visible.contains(id));
+        if focus != self.sent_focus {
+            self.sent_focus = focus;
+            self.send(&Message::Focus(focus));
+        }
+        let route: Vec<SystemId> = route.iter().copied().filter(|id| self.visible.contains(id)).collect();
+        if route != self.sent_route {
+            self.sent_route = route;
+            self.send(&Message::Route(self.sent_route.clone()));
+        }
+
+        let joined: Vec<TcpStream> = self.joined.try_iter().collect();
+        let num_joined = joined.len();
+        if num_joined > 0 {
+            let welcome = [
+                Message::Galaxy(galaxy.for_players()),
+                Message::Focus(self.sent_focus),
+                Message::Route(self.sent_route.clone()),
+            ];
+            let welcome: String = welcome.iter().map(encode).collect();
+            for stream in joined {
+                let (player, lines) = mpsc::sync_channel(PLAYER_BACKLOG);
+                let _ = player.try_send(welcome.clone());
+                let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                thread::spawn(move || write_lines(stream, lines));
+                self.players.push(player);
+            }
+        }
+        num_joined
+    }
+
+    /// How many players are connected, as far as the host can tell.
+    pub fn players(&self) -> usize {
+        self.players.len()
+    }
+
+    fn send(&mut self, message: &Message) {
+        let line = encode(message);
+        // A player's writer stops once their connection has gone, and players who stop
+        // reading are dropped once their backlog is full rather than kept in memory forever
+        self.players.retain(|player| player.try_send(line.clone()).is_ok());
+    }
+}
+
+fn encode(message: &Message) -> String {
+    format!("{}\n", serde_json::to_string(message).unwrap())
+}
+
+// Writes lines to a player until they go away or the host stops.
+fn write_lines(mut stream: TcpStream, lines: Receiver<String>) {
+    for line in lines {
+        if stream.write_all(line.as_bytes()).and_then(|_| stream.flush()).is_err() {
+            break
+        }
+    }
+}
+
+/// A player's end of a session.
+pub(crate) struct Player {
+    pub address: String,
+    messages: Receiver<Message>,
+    connected: bool,
+}
+
+impl Player {
+    /// Connects to a host at `address`, like `192.168.1.20:7318`, or `192.168.1.20` for the
+    /// default port, and waits for the galaxy to show.
+    pub fn join(address: &str) -> Result<(Player, Galaxy), String> {
+        let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+        let stream = TcpStream::connect(&address).map_err(|err| format!("Could not connect to {}: {}", address, err))?;
+        let mut reader = BufReader::new(stream);
+        reader.get_ref().set_read_timeout(Some(JOIN_TIMEOUT)).map_err(|err| err.to_string())?;
+        let galaxy = match read_message(&mut reader) {
+            Some(Message::Galaxy(galaxy)) => {galaxy}
+            _ => {return Err(format!("{} didn't send a galaxy", address))}
+        };
+        reader.get_ref().set_read_timeout(None).map_err(|err| err.to_string())?;
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            while let Some(message) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break
+                }
+            }
+        });
+        Ok((Player { address, messages, connected: true }, galaxy))
+    }
+
+    /// The messages that came in since the last call.
+    pub fn messages(&mut self) -> Vec<Message> {
+        let mut messages = Vec::new();
+        loop {
+            match self.messages.try_recv() {
+                Ok(message) => {messages.push(message)}
+                Err(TryRecvError::Empty) => {break}
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break
+                }
+            }
+        }
+        messages
+    }
+
+    /// False once the host has gone away.
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+}
+
+// The next message from the host, or None if the connection closed or sent something unreadable.
+fn read_message(reader: &mut BufReader<TcpStream>) -> Option<Message> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => {None}
+        Ok(_) => {serde_json::from_str(&line).ok()}
+    }
+}
+
+/// Adds or replaces systems sent by the host. Systems already on the map keep drifting
+/// from where they are rather than jumping back to rest.
+pub(crate) fn upsert_systems(galaxy: &mut Galaxy, systems: Vec<StarSystem>) {
+    for system in systems {
+        match galaxy.get_mut(system.id()) {
+            None => {
+                let index = galaxy.len();
+                galaxy.insert_system(index, system);
+            }
+            Some(existing) => {
+                let (position, drift) = (existing.position, existing.drift_direction);
+                *existing = system;
+                existing.position = position;
+                existing.drift_direction = drift;
+            }
+        }
+    }
+}
+
+pub(crate) fn remove_systems(galaxy: &mut Galaxy, ids: &[SystemId]) {
+    for &id in ids {
+        galaxy.remove_system(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Shape;
+    use std::time::Instant;
+
+    fn galaxy() -> Galaxy {
+        let mut galaxy = Galaxy::with_seed(7, 30, 2, 1.0, 40.0, Shape::Sphere);
+        let first = galaxy.systems()[0].id();
+        galaxy.parties_mut()[0].explored.insert(first, 0);
+        galaxy
+    }
+
+    /// Keeps the host updating until a player has joined it over loopback.
+    fn join(host: &mut Host, galaxy: &Galaxy) -> (Player, Galaxy) {
+        let address = format!("127.0.0.1:{}", host.port);
+        let joining = thread::spawn(move || Player::join(&address));
+        let started = Instant::now();
+        while host.update(galaxy, false, None, &[]) == 0 {
+            assert!(started.elapsed() < JOIN_TIMEOUT, "nobody joined");
+            thread::sleep(Duration::from_millis(10));
+        }
+        joining.join().unwrap().unwrap()
+    }
+
+    /// Waits for the next message from the host that `pick` wants.
+    fn wait_for<T>(player: &mut Player, mut pick: impl FnMut(Message) -> Option<T>) -> T {
+        let started = Instant::now();
+        loop {
+            if let Some(found) = player.messages().into_iter().find_map(&mut pick) {
+                return found
+            }
+            assert!(started.elapsed() < JOIN_TIMEOUT, "nothing came");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn players_join_on_loopback_and_get_changes() {
+        let mut galaxy = galaxy();
+        let mut host = Host::start(0, false).unwrap();
+        host.update(&galaxy, true, None, &[]);
+        let (mut player, joined) = join(&mut host, &galaxy);
+        assert_eq!(joined.len(), galaxy.visible_to_players().len());
+        assert_eq!(host.players(), 1);
+
+        // Exploring a system on the frontier brings the ones past it into view
+        let visible = galaxy.visible_to_players();
+        let frontier = galaxy.systems().iter().map(|system| system.id())
+            .find(|id| visible.contains(id) && galaxy[*id].connections.iter().any(|next| !visible.contains(next)))
+            .unwrap();
+        galaxy.parties_mut()[0].explored.insert(frontier, 0);
+        host.update(&galaxy, true, None, &[]);
+        let upserted = wait_for(&mut player, |message| match message {
+            Message::Upsert(systems) => {Some(systems)}
+            _ => {None}
+        });
+        assert!(upserted.iter().any(|system| system.id() == frontier && system.system_data.is_some()));
+
+        galaxy.parties_mut()[0].explored.remove(&frontier);
+        host.update(&galaxy, true, None, &[]);
+        let removed = wait_for(&mut player, |message| match message {
+            Message::Remove(ids) => {Some(ids)}
+            _ => {None}
+        });
+        assert!(!removed.is_empty());
+        assert!(removed.iter().all(|id| !galaxy.visible_to_players().contains(id)));
+    }
+
+    #[test]
+    fn players_who_stop_reading_are_dropped() {
+        let mut host = Host::start(0, false).unwrap();
+        let (player, _lines) = mpsc::sync_channel(PLAYER_BACKLOG);
+        host.players.push(player);
+        for _ in 0..PLAYER_BACKLOG {
+            host.send(&Message::Focus(None));
+        }
+        assert_eq!(host.players(), 1);
+        host.send(&Message::Focus(None));
+        assert_eq!(host.players(), 0);
+    }
+}
@@ -0,0 +1,183 @@
+use std::f32::consts::{PI, TAU};
+use std::fmt::Write;
+
+#[cfg(feature = "gui")]
+use raylib::prelude::Camera3D;
+
+use crate::map::{Galaxy, StarSystem};
+use crate::party::Perspective;
+use crate::vector::Vec3;
+
+/// How the galaxy sphere is flattened onto the page.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Projection {
+    /// Looking straight down the y axis.
+    TopDown,
+    /// Longitude and latitude on the sphere, like a world map.
+    Equirectangular,
+    /// Whatever an orthographic camera sees, so the export matches the screen.
+    #[cfg(feature = "gui")]
+    Camera { position: Vec3, target: Vec3, up: Vec3, fovy: f32 },
+}
+
+impl Projection {
+    pub fn from_name(name: &str) -> Option<Projection> {
+        match name.to_ascii_lowercase().as_str() {
+            "top" | "topdown" | "top-down" => Some(Projection::TopDown),
+            "equirect" | "equirectangular" => Some(Projection::Equirectangular),
+            _ => None
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn from_camera(camera: &Camera3D) -> Projection {
+        Projection::Camera { position: camera.position.into(), target: camera.target.into(), up: camera.up.into(), fovy: camera.fovy }
+    }
+
+    fn size(&self) -> (f32, f32) {
+        match self {
+            Projection::Equirectangular => (2000.0, 1000.0),
+            _ => (1200.0, 1200.0),
+        }
+    }
+
+    /// Page coordinates of a point and its depth, where larger depth is closer to the viewer.
+    fn project(&self, point: Vec3, radius: f32) -> (f32, f32, f32) {
+        let (width, height) = self.size();
+        match self {
+            Projection::TopDown => {
+                let scale = height / (2.4 * radius);
+                (width / 2.0 + point.x * scale, height / 2.0 + point.z * scale, point.y)
+            }
+            Projection::Equirectangular => {
+                let lon = point.z.atan2(point.x);
+                let lat = (point.y / point.length().max(f32::EPSILON)).clamp(-1.0, 1.0).asin();
+                ((lon + PI) / TAU * width, (PI / 2.0 - lat) / PI * height, 0.0)
+            }
+            #[cfg(feature = "gui")]
+            Projection::Camera { position, target, up, fovy } => {
+                let forward = (*target - *position).normalized();
+                let right = forward.cross(*up).normalized();
+                let true_up = right.cross(forward);
+                let relative = point - *target;
+                let scale = height / fovy;
+                (width / 2.0 + relative.dot(right) * scale,
+                 height / 2.0 - relative.dot(true_up) * scale,
+                 -relative.dot(forward))
+            }
+        }
+    }
+
+    fn scale(&self, radius: f32) -> f32 {
+        match self {
+            Projection::TopDown => self.size().1 / (2.4 * radius),
+            Projection::Equirectangular => self.size().1 / (1.2 * radius),
+            #[cfg(feature = "gui")]
+            Projection::Camera { fovy, .. } => self.size().1 / fovy,
+        }
+    }
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> String {
+    format!("rgb({},{},{})", r, g, b)
+}
+
+fn header(out: &mut String, width: f32, height: f32) {
+    let _ = writeln!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">", w = width, h = height);
+    let _ = writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"black\"/>");
+    out.push_str("<g font-family=\"monospace\" fill=\"rgb(200,200,200)\">\n");
+}
+
+fn footer(out: &mut String) {
+    out.push_str("</g>\n</svg>\n");
+}
+
+/// The galaxy map as an SVG, with systems coloured as they are on the map view.
+pub(crate) fn galaxy_svg(galaxy: &Galaxy, projection: Projection, labels: bool) -> String {
+    let (width, height) = projection.size();
+    let radius = galaxy.radius();
+    let scale = projection.scale(radius);
+    let mut out = String::new();
+    header(&mut out, width, height);
+
+    let (lane_color, lane_opacity) = ([255, 255, 255], 40.0 / 255.0);
+    for system in galaxy.systems() {
+        let (x1, y1, _) = projection.project(system.origin(), radius);
+        for &conn in &system.connections {
+            if conn < system.id() {
+                continue
+            }
+            let (x2, y2, _) = projection.project(galaxy[conn].origin(), radius);
+            let segments = if let Projection::Equirectangular = projection && (x2 - x1).abs() > width / 2.0 {
+                // Lanes crossing the edge of the map wrap around to the other side
+                let shift = if x2 > x1 { -width } else { width };
+                vec![(x1, y1, x2 + shift, y2), (x1 - shift, y1, x2, y2)]
+            }
+            else {
+                vec![(x1, y1, x2, y2)]
+            };
+            for (ax, ay, bx, by) in segments {
+                let _ = writeln!(out, "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-opacity=\"{:.2}\"/>",
+                    ax, ay, bx, by, rgb(lane_color), lane_opacity);
+            }
+        }
+    }
+
+    // Far systems first so near ones are drawn over them
+    let mut systems: Vec<(&StarSystem, (f32, f32, f32))> = galaxy.systems().iter()
+        .map(|system| (system, projection.project(system.origin(), radius)))
+        .collect();
+    systems.sort_by(|a, b| a.1.2.partial_cmp(&b.1.2).unwrap());
+    for (system, (x, y, _)) in systems {
+        let (size, color) = system.map_style_rgb(None, galaxy.is_explored(system.id(), Perspective::Everyone));
+        let r = (size * scale).max(1.5);
+        let _ = writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\" fill-opacity=\"0.5\"/>", x, y, r * 1.2, rgb(color));
+        let _ = writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>", x, y, r, rgb(color));
+        if labels {
+            let _ = writeln!(out, "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"10\">{:X}</text>", x + r * 1.5, y + 3.0, system.name);
+        }
+    }
+    footer(&mut out);
+    out
+}
+
+/// A top-down diagram of a system's planets on their orbits, like the star system view.
+/// Returns None for systems that haven't been scanned.
+pub(crate) fn orrery_svg(system: &StarSystem) -> Option<String> {
+    let data = system.system_data.as_ref()?;
+    let (width, height) = (1200.0, 1200.0);
+    let (cx, cy) = (width / 2.0, height / 2.0);
+    let outermost = data.planets.iter().map(|p| p.orbit_radius).fold(0.5, f64::max) as f32;
+    let scale = (width / 2.0 - 120.0) / outermost;
+    // Bodies are far too small to see at orbit scale, so they get their own
+    let body_scale = 12.0;
+
+    let mut out = String::new();
+    header(&mut out, width, height);
+    let _ = writeln!(out, "<text x=\"20\" y=\"40\" font-size=\"28\">System {:X}</text>", system.name);
+    let _ = writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{:.1}\" fill=\"{}\"/>",
+        cx, cy, data.star_display_radius() * body_scale, rgb([255, 255, 0]));
+
+    for (i, planet) in data.planets.iter().enumerate() {
+        let orbit = planet.orbit_radius as f32 * scale;
+        let angle = planet.orbit_completion as f32 * TAU;
+        let (px, py) = (cx + orbit * angle.cos(), cy - orbit * angle.sin());
+        let planet_radius = planet.display_radius() * body_scale;
+        let _ = writeln!(out, "<circle cx=\"{}\" cy=\"{}\" r=\"{:.1}\" fill=\"none\" stroke=\"white\" stroke-opacity=\"0.3\"/>", cx, cy, orbit);
+
+        let moon_spread = planet_radius + 6.0;
+        let max_moon_orbit = planet.moons.iter().map(|m| m.orbital_radius).fold(f64::EPSILON, f64::max) as f32;
+        for moon in &planet.moons {
+            let moon_orbit = moon_spread + moon.orbital_radius as f32 / max_moon_orbit * 20.0;
+            let moon_angle = moon.orbit_completion as f32 * TAU;
+            let _ = writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"none\" stroke=\"white\" stroke-opacity=\"0.15\"/>", px, py, moon_orbit);
+            let _ = writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\"/>",
+                px + moon_orbit * moon_angle.cos(), py - moon_orbit * moon_angle.sin(), rgb(moon.moon_type.rgb()));
+        }
+        let _ = writeln!(out, "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\" fill=\"{}\"/>", px, py, planet_radius, rgb(planet.class.rgb()));
+        let _ = writeln!(out, "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"14\">{} {:?} ({} moons)</text>",
+            px + moon_spread + 24.0, py + 5.0, system.planet_designation(i), planet.class, planet.moons.len());
+    }
+    footer(&mut out);
+    Some(out)
+}
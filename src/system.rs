@@ -1,10 +1,19 @@
 #![allow(dead_code)]
 use rand::prelude::*;
-use raylib::{ffi::PI, prelude::Vector3, prelude::Camera3D, prelude::RaylibHandle};
-use crate::utils::{self, rotate_vector, vector3_serde};
+use crate::utils::rotate_vector;
+use crate::vector::Vec3;
+#[cfg(feature = "gui")]
+use std::f64::consts::PI;
+#[cfg(feature = "gui")]
+use crate::picking::{Picker, View};
+#[cfg(feature = "gui")]
+use raylib::prelude::{Camera3D, Color, RaylibHandle};
 use serde::{Serialize, Deserialize};
+/// Kilograms in a solar mass.
+pub const SOLAR_MASS: f64 = 1.989e30;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct StarSystemData {
+pub struct StarSystemData {
     pub star_mass: f64,
     pub planets: Vec<Planet> 
 }
@@ -12,49 +21,71 @@ pub(crate) struct StarSystemData {
 
 impl StarSystemData {
     pub fn new() -> StarSystemData {
-        let mut rng = thread_rng();
-        let min_star_mass: f64 = 3.28875 * (10.0 as f64).powi(29);
-        let max_star_mass: f64 = 8.77000 * (10.0 as f64).powi(31);
+        StarSystemData::generate(&mut thread_rng())
+    }
+    /// A random system drawn from `rng`, so a seeded generator always gives the same one.
+    pub fn generate<R: Rng>(rng: &mut R) -> StarSystemData {
+        let min_star_mass: f64 = 3.28875 * 10.0_f64.powi(29);
+        let max_star_mass: f64 = 8.77000 * 10.0_f64.powi(31);
         let star_mass = rng.gen_range(min_star_mass..max_star_mass);
+        let num_planets = rng.gen_range(0..=10);
         StarSystemData {
             star_mass,
-            planets: generate_planets(rng.gen_range(0..=10))
+            planets: generate_planets(rng, num_planets)
         }  
     }
-    pub fn closest_planet_to_mouse(&self, rl: &mut RaylibHandle, camera: &Camera3D) -> Option<usize> {
-        let mouse_pos = rl.get_mouse_position();
-        let ray = rl.get_screen_to_world_ray(mouse_pos, camera);
-
-        let mut closest: Option<usize> = None;
-        let mut min_distance = f32::MAX;
-
+    /// Star mass in solar masses rather than kilograms.
+    pub fn star_solar_masses(&self) -> f64 {
+        self.star_mass / SOLAR_MASS
+    }
+    /// Rough score for how much of the system could support life: each planet's
+    /// habitability plus a bit for every moon with an ocean under its ice.
+    pub fn habitability(&self) -> f64 {
+        self.planets.iter().map(|planet| {
+            let ocean_moons = planet.moons.iter().filter(|moon| moon.moon_type == MoonType::SubsurfaceOcean).count();
+            planet.class.habitability() + 0.25 * ocean_moons as f64
+        }).sum()
+    }
+    pub fn star_display_radius(&self) -> f32 {
+        self.star_mass.log10() as f32 / 20.0
+    }
+    /// The planet drawn under the mouse in the star system view, if any.
+    #[cfg(feature = "gui")]
+    pub fn closest_planet_to_mouse(&self, rl: &RaylibHandle, camera: &Camera3D) -> Option<usize> {
+        let mut picker = Picker::new(View::of_window(rl, camera), rl.get_mouse_position());
         for (i, planet) in self.planets.iter().enumerate() {
-            let planet_pos = utils::point_on_3d_circle(planet.orbit_normal, planet.orbit_radius as f32, planet.orbit_completion as f32 * 2.0 * PI as f32);
-            if crate::map::ray_sphere_intersect(ray.position, ray.direction, planet_pos.scale_by(50.0), 2.0) {
-                let dist = (ray.position - planet_pos).length();
-                if dist < min_distance {
-                    min_distance = dist;
-                    closest = Some(i);
-                }
-            }
+            let planet_pos = crate::utils::point_on_3d_circle(planet.orbit_normal, planet.orbit_radius as f32 * 50.0, planet.orbit_completion as f32 * 2.0 * PI as f32);
+            picker.sphere(i, planet_pos.into(), planet.display_radius() * 1.2);
         }
-        closest 
+        picker.nearest()
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct Planet {
+pub struct Planet {
     pub mass: f64,
     pub orbit_completion: f64, //number from 0 to 1
     pub orbit_radius: f64,
-    #[serde(with = "vector3_serde")]
-    pub orbit_normal: Vector3,
+    pub orbit_normal: Vec3,
     pub class: PlanetClass,
-    pub moons: Vec<Moon>
+    pub moons: Vec<Moon>,
+    #[serde(default)]
+    pub rings: Option<Rings>,
+    #[serde(default)]
+    pub notes: String
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub(crate) enum PlanetClass {
+/// A flat ring system around a planet's equator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rings {
+    /// Inner edge, in planet radii.
+    pub inner: f64,
+    /// Outer edge, in planet radii.
+    pub outer: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlanetClass {
     Terran,
     GasGiant,
     IceGiant,
@@ -65,17 +96,18 @@ pub(crate) enum PlanetClass {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub(crate) struct Moon {
+pub struct Moon {
     pub moon_type: MoonType,
     pub mass: f64,
     pub orbital_radius: f64,
-    #[serde(with = "vector3_serde")]
-    pub orbit_normal: Vector3,
-    pub orbit_completion: f64
+    pub orbit_normal: Vec3,
+    pub orbit_completion: f64,
+    #[serde(default)]
+    pub notes: String
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub(crate) enum MoonType {
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MoonType {
     Asteroid,
     RoundDusty,
     SubsurfaceOcean,
@@ -83,11 +115,8 @@ pub(crate) enum MoonType {
 
 
 
-pub(crate) fn generate_planets(num_planets: usize) -> Vec<Planet> {
-    let mut rng = thread_rng();
-
-    // Define orbital order preference by class
-    let class_orbit_priority = vec![
+impl PlanetClass {
+    pub const ALL: [PlanetClass; 7] = [
         PlanetClass::Volcanic,    // closest
         PlanetClass::MetalWorld,
         PlanetClass::Terran,
@@ -97,123 +126,316 @@ pub(crate) fn generate_planets(num_planets: usize) -> Vec<Planet> {
         PlanetClass::IceGiant,    // farthest
     ];
 
+    pub fn next(self) -> PlanetClass {
+        let i = PlanetClass::ALL.iter().position(|c| *c == self).unwrap();
+        PlanetClass::ALL[(i + 1) % PlanetClass::ALL.len()]
+    }
+
     // Mass ranges in Earth masses
-    let class_mass_range = |class: &PlanetClass| match class {
-        PlanetClass::Volcanic => (0.1, 0.5),
-        PlanetClass::MetalWorld => (0.1, 1.0),
-        PlanetClass::Terran => (0.5, 5.0),
-        PlanetClass::Desert => (0.5, 3.0),
-        PlanetClass::OceanWorld => (0.8, 6.0),
-        PlanetClass::GasGiant => (50.0, 300.0),
-        PlanetClass::IceGiant => (10.0, 50.0),
-    };
+    pub fn mass_range(&self) -> (f64, f64) {
+        match self {
+            PlanetClass::Volcanic => (0.1, 0.5),
+            PlanetClass::MetalWorld => (0.1, 1.0),
+            PlanetClass::Terran => (0.5, 5.0),
+            PlanetClass::Desert => (0.5, 3.0),
+            PlanetClass::OceanWorld => (0.8, 6.0),
+            PlanetClass::GasGiant => (50.0, 300.0),
+            PlanetClass::IceGiant => (10.0, 50.0),
+        }
+    }
 
     // Base orbital radius ranges by class (in AU)
-    let class_orbit_range = |class: &PlanetClass| match class {
-        PlanetClass::Volcanic => (0.1, 0.2),
-        PlanetClass::MetalWorld => (0.2, 0.4),
-        PlanetClass::Terran => (0.3, 0.5),
-        PlanetClass::Desert => (0.2, 0.5),
-        PlanetClass::OceanWorld => (0.4, 0.6),
-        PlanetClass::GasGiant => (1.0, 1.7),
-        PlanetClass::IceGiant => (1.4, 2.0),
-    };
-
-    // Moon generation rules
-    let moon_types = [MoonType::Asteroid, MoonType::RoundDusty, MoonType::SubsurfaceOcean];
-    let moon_mass_range = |mt: &MoonType| match mt {
-        MoonType::Asteroid => (0.00001, 0.0001),
-        MoonType::RoundDusty => (0.0001, 0.01),
-        MoonType::SubsurfaceOcean => (0.005, 0.05),
-    };
-
-    // Pick a base inclination offset for the system
-
-    let mut planets = Vec::new();
-    let mut used_orbits: Vec<f64> = Vec::new();
+    pub fn orbit_range(&self) -> (f64, f64) {
+        match self {
+            PlanetClass::Volcanic => (0.1, 0.2),
+            PlanetClass::MetalWorld => (0.2, 0.4),
+            PlanetClass::Terran => (0.3, 0.5),
+            PlanetClass::Desert => (0.2, 0.5),
+            PlanetClass::OceanWorld => (0.4, 0.6),
+            PlanetClass::GasGiant => (1.0, 1.7),
+            PlanetClass::IceGiant => (1.4, 2.0),
+        }
+    }
 
-    for i in 0..num_planets {
-        // Pick class in rough order, but with some randomness
-        let class_index = ((i as f64 / num_planets as f64) * class_orbit_priority.len() as f64)
-            .round()
-            .clamp(0.0, (class_orbit_priority.len() - 1) as f64) as usize;
-        let class = *class_orbit_priority.to_owned().clone()
-            .get(class_index + rng.gen_range(0..=1).min(class_orbit_priority.len() - 1 - class_index))
-            .unwrap_or(&PlanetClass::Terran);
+    /// The colour planets of this class are drawn in, as red, green and blue, for
+    /// drawing them without raylib.
+    pub fn rgb(&self) -> [u8; 3] {
+        match self {
+            PlanetClass::Volcanic => [255, 165, 0],
+            PlanetClass::MetalWorld => [128, 128, 128],
+            PlanetClass::Terran => [0, 128, 0],
+            PlanetClass::Desert => [245, 245, 220],
+            PlanetClass::OceanWorld => [0, 0, 255],
+            PlanetClass::GasGiant => [128, 0, 128],
+            PlanetClass::IceGiant => [135, 206, 235],
+        }
+    }
 
-        // Get mass
-        let (m_min, m_max) = class_mass_range(&class);
-        let mass = rng.gen_range(m_min..m_max);
+    #[cfg(feature = "gui")]
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.rgb();
+        Color::new(r, g, b, 255)
+    }
 
-        // Get orbit radius ensuring no exact duplicates
-        let (o_min, o_max) = class_orbit_range(&class);
-        let mut orbit_radius;
-        loop {
-            orbit_radius = rng.gen_range(o_min..o_max);
-            if !used_orbits.iter().any(|&o| (o - orbit_radius).abs() < 0.05) {
-                used_orbits.push(orbit_radius);
-                break;
-            }
+    /// How well life could do on a planet of this class, from 0 to 1.
+    pub fn habitability(&self) -> f64 {
+        match self {
+            PlanetClass::Terran => 1.0,
+            PlanetClass::OceanWorld => 0.8,
+            PlanetClass::Desert => 0.4,
+            PlanetClass::IceGiant | PlanetClass::GasGiant => 0.05,
+            PlanetClass::Volcanic | PlanetClass::MetalWorld => 0.0,
         }
+    }
 
-        // Get orbit inclination within ±20° total spread
+    fn roll_rings<R: Rng>(&self, rng: &mut R) -> Option<Rings> {
+        let chance = match self {
+            PlanetClass::GasGiant => 0.5,
+            PlanetClass::IceGiant => 0.3,
+            _ => 0.02,
+        };
+        if !rng.gen_bool(chance) {
+            return None
+        }
+        let inner = rng.gen_range(1.3..1.7);
+        Some(Rings { inner, outer: inner + rng.gen_range(0.3..1.2) })
+    }
 
-        // Generate moons
-        let num_moons = match class {
+    fn roll_num_moons<R: Rng>(&self, rng: &mut R) -> usize {
+        match self {
             PlanetClass::GasGiant => rng.gen_range(5..15),
             PlanetClass::IceGiant => rng.gen_range(3..10),
             PlanetClass::Terran | PlanetClass::OceanWorld => rng.gen_range(0..3),
             _ => rng.gen_range(0..2),
-        };
+        }
+    }
+}
+
+impl MoonType {
+    pub const ALL: [MoonType; 3] = [MoonType::Asteroid, MoonType::RoundDusty, MoonType::SubsurfaceOcean];
+
+    pub fn next(self) -> MoonType {
+        let i = MoonType::ALL.iter().position(|t| *t == self).unwrap();
+        MoonType::ALL[(i + 1) % MoonType::ALL.len()]
+    }
+
+    /// The colour moons of this type are drawn in, as red, green and blue.
+    pub fn rgb(&self) -> [u8; 3] {
+        match self {
+            MoonType::Asteroid => [128, 128, 128],
+            MoonType::RoundDusty => [119, 136, 153],
+            MoonType::SubsurfaceOcean => [106, 90, 205],
+        }
+    }
 
+    #[cfg(feature = "gui")]
+    pub fn color(&self) -> Color {
+        let [r, g, b] = self.rgb();
+        Color::new(r, g, b, 255)
+    }
+
+    pub fn mass_range(&self) -> (f64, f64) {
+        match self {
+            MoonType::Asteroid => (0.00001, 0.0001),
+            MoonType::RoundDusty => (0.0001, 0.01),
+            MoonType::SubsurfaceOcean => (0.005, 0.05),
+        }
+    }
+}
+
+/// A newly generated system, the same as `StarSystemData::new`.
+impl Default for StarSystemData {
+    fn default() -> StarSystemData {
+        StarSystemData::new()
+    }
+}
+
+impl StarSystemData {
+    /// Adds a freshly generated planet of the given class and returns its index
+    /// in the (orbit sorted) planet list.
+    pub fn add_planet(&mut self, class: PlanetClass) -> usize {
+        let mut rng = thread_rng();
+        let used_orbits: Vec<f64> = self.planets.iter().map(|p| p.orbit_radius).collect();
+        let orbit_radius = free_orbit(&mut rng, class.orbit_range(), &used_orbits, 0.05);
+        let planet = Planet::generate(&mut rng, class, orbit_radius);
+        self.planets.push(planet);
+        self.sort_planets(self.planets.len() - 1)
+    }
+
+    pub fn remove_planet(&mut self, index: usize) -> Option<Planet> {
+        if index < self.planets.len() {
+            Some(self.planets.remove(index))
+        }
+        else {
+            None
+        }
+    }
+
+    /// Changes the class of a planet, rerolling everything that depends on it.
+    /// Returns the new index of the planet, since its orbit may have moved.
+    pub fn set_planet_class(&mut self, index: usize, class: PlanetClass) -> usize {
+        let mut rng = thread_rng();
+        let used_orbits: Vec<f64> = self.planets.iter().enumerate()
+            .filter(|&(i, _)| i != index)
+            .map(|(_, p)| p.orbit_radius)
+            .collect();
+        let orbit_radius = free_orbit(&mut rng, class.orbit_range(), &used_orbits, 0.05);
+        let mut planet = Planet::generate(&mut rng, class, orbit_radius);
+        planet.orbit_completion = self.planets[index].orbit_completion;
+        planet.orbit_normal = self.planets[index].orbit_normal;
+        planet.notes = self.planets[index].notes.clone();
+        self.planets[index] = planet;
+        self.sort_planets(index)
+    }
+
+    // Keeps planets ordered by orbit radius, returning where `index` ended up.
+    fn sort_planets(&mut self, index: usize) -> usize {
+        let radius = self.planets[index].orbit_radius;
+        self.planets.sort_by(|a, b| a.orbit_radius.partial_cmp(&b.orbit_radius).unwrap());
+        self.planets.iter().position(|p| p.orbit_radius == radius).unwrap_or(0)
+    }
+}
+
+impl Planet {
+    fn generate<R: Rng>(rng: &mut R, class: PlanetClass, orbit_radius: f64) -> Planet {
+        // Get mass
+        let (m_min, m_max) = class.mass_range();
+        let mass = rng.gen_range(m_min..m_max);
+
+        // Generate moons
+        let num_moons = class.roll_num_moons(rng);
         let mut moons = Vec::new();
         let mut used_moon_orbits: Vec<f64> = Vec::new();
 
         for _ in 0..num_moons {
-            let moon_type = &moon_types[rng.gen_range(0..moon_types.len())];
-            let (mm_min, mm_max) = moon_mass_range(moon_type);
-            let moon_mass = rng.gen_range(mm_min..mm_max);
-
-            let mut moon_orbit;
-            loop {
-                moon_orbit = rng.gen_range(0.01..0.05); // in AU
-                if !used_moon_orbits.iter().any(|&o| (o - moon_orbit).abs() < 0.001) {
-                    used_moon_orbits.push(moon_orbit);
-                    break;
-                }
-            }
-
-            moons.push(Moon {
-                moon_type: moon_type.to_owned().clone(),
-                mass: moon_mass,
-                orbital_radius: moon_orbit,
-                orbit_completion: rng.gen_range(0..10000) as f64 / 10000.0,
-                orbit_normal: random_orbit_normal(&mut rng, 20.0, Vector3::left()),
-            });
+            let moon_type = MoonType::ALL[rng.gen_range(0..MoonType::ALL.len())];
+            let moon_orbit = free_orbit(rng, (0.01, 0.05), &used_moon_orbits, 0.001); // in AU
+            used_moon_orbits.push(moon_orbit);
+            moons.push(Moon::generate(rng, moon_type, moon_orbit));
         }
         let orbit_completion = rng.gen_range(0.0..1.0);
-        planets.push(Planet {
+        Planet {
             mass,
             orbit_completion,
             orbit_radius,
-            orbit_normal: random_orbit_normal(&mut rng, 20.0, Vector3::left()),
+            orbit_normal: random_orbit_normal(rng, 20.0, Vec3::left()),
             class,
             moons,
-        });
+            rings: class.roll_rings(rng),
+            notes: String::new(),
+        }
+    }
+
+    pub fn display_radius(&self) -> f32 {
+        self.mass.powf(1.0 / 8.444) as f32 / 4.0
+    }
+
+    /// Sets the mass, clamped to what the planet's class allows. Moon orbits scale
+    /// with the planet's Hill sphere, so they are stretched by the cube root of the change.
+    pub fn set_mass(&mut self, mass: f64) {
+        let (m_min, m_max) = self.class.mass_range();
+        let mass = mass.clamp(m_min, m_max);
+        let scale = (mass / self.mass).cbrt();
+        for moon in &mut self.moons {
+            moon.orbital_radius *= scale;
+        }
+        self.mass = mass;
+    }
+
+    pub fn add_moon(&mut self, moon_type: MoonType) -> usize {
+        let mut rng = thread_rng();
+        let used_moon_orbits: Vec<f64> = self.moons.iter().map(|m| m.orbital_radius).collect();
+        let moon_orbit = free_orbit(&mut rng, (0.01, 0.05), &used_moon_orbits, 0.001);
+        self.moons.push(Moon::generate(&mut rng, moon_type, moon_orbit));
+        self.moons.len() - 1
+    }
+
+    pub fn remove_moon(&mut self, index: usize) -> Option<Moon> {
+        if index < self.moons.len() {
+            Some(self.moons.remove(index))
+        }
+        else {
+            None
+        }
+    }
+}
+
+impl Moon {
+    /// Radius to draw the moon with, on the same scale as `Planet::display_radius`.
+    pub fn display_radius(&self) -> f32 {
+        self.mass.powf(1.0 / 8.444) as f32 / 4.0
+    }
+
+    fn generate<R: Rng>(rng: &mut R, moon_type: MoonType, orbital_radius: f64) -> Moon {
+        let (mm_min, mm_max) = moon_type.mass_range();
+        Moon {
+            moon_type,
+            mass: rng.gen_range(mm_min..mm_max),
+            orbital_radius,
+            orbit_completion: rng.gen_range(0..10000) as f64 / 10000.0,
+            orbit_normal: random_orbit_normal(rng, 20.0, Vec3::left()),
+            notes: String::new(),
+        }
+    }
+
+    /// Changes the moon type and rerolls its mass to fit the new type.
+    pub fn set_type(&mut self, moon_type: MoonType) {
+        let (mm_min, mm_max) = moon_type.mass_range();
+        self.moon_type = moon_type;
+        self.mass = thread_rng().gen_range(mm_min..mm_max);
+    }
+}
+
+pub fn generate_planets<R: Rng>(rng: &mut R, num_planets: usize) -> Vec<Planet> {
+
+    // Define orbital order preference by class
+    let class_orbit_priority = PlanetClass::ALL;
+
+    let mut planets = Vec::new();
+    let mut used_orbits: Vec<f64> = Vec::new();
+
+    for i in 0..num_planets {
+        // Pick class in rough order, but with some randomness
+        let class_index = ((i as f64 / num_planets as f64) * class_orbit_priority.len() as f64)
+            .round()
+            .clamp(0.0, (class_orbit_priority.len() - 1) as f64) as usize;
+        let class = *class_orbit_priority
+            .get(class_index + rng.gen_range(0..=1).min(class_orbit_priority.len() - 1 - class_index))
+            .unwrap_or(&PlanetClass::Terran);
+
+        // Get orbit radius ensuring no exact duplicates. Narrow bands can fill up, which
+        // used to leave this looping forever.
+        let orbit_radius = free_orbit(rng, class.orbit_range(), &used_orbits, 0.05);
+        used_orbits.push(orbit_radius);
+
+        planets.push(Planet::generate(rng, class, orbit_radius));
     }
 
     planets.sort_by(|a, b| a.orbit_radius.partial_cmp(&b.orbit_radius).unwrap());
     planets
 }
 
+// Picks an orbit in `range` that keeps at least `spacing` from every used orbit.
+// Bands can fill up when editing, so after enough misses the orbit is pushed
+// just past the outermost one instead of looping forever.
+fn free_orbit<R: Rng>(rng: &mut R, range: (f64, f64), used_orbits: &[f64], spacing: f64) -> f64 {
+    for _ in 0..100 {
+        let orbit = rng.gen_range(range.0..range.1);
+        if !used_orbits.iter().any(|&o| (o - orbit).abs() < spacing) {
+            return orbit;
+        }
+    }
+    used_orbits.iter().cloned().fold(range.1, f64::max) + spacing
+}
+
 
-fn random_orbit_normal<R: Rng>(rng: &mut R, max_degrees: f32, base: Vector3) -> Vector3 {
+fn random_orbit_normal<R: Rng>(rng: &mut R, max_degrees: f32, base: Vec3) -> Vec3 {
     let max_radians = max_degrees.to_radians();
     // Random tilt axis
-    let axis = Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).normalized();
+    let axis = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)).normalized();
     // Random tilt angle
     let tilt = rng.gen_range(-max_radians..max_radians);
     // Rotate base vector
-    return rotate_vector(base, axis, tilt).normalized()
+    rotate_vector(base, axis, tilt).normalized()
 }
 
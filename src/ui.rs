@@ -0,0 +1,61 @@
+use raylib::prelude::*;
+
+/// What happened to a text field this frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TextFieldEvent {
+    Changed,
+    /// Enter was pressed.
+    Submit,
+    /// Escape was pressed.
+    Cancel,
+}
+
+/// A single line of typed text. While one is open the caller should stop reading
+/// actions, see `Input::set_suspended`, so typing doesn't also fly the camera around.
+pub(crate) struct TextField {
+    pub label: &'static str,
+    pub text: String,
+}
+
+impl TextField {
+    pub fn new(label: &'static str, text: String) -> TextField {
+        TextField { label, text }
+    }
+
+    pub fn update(&mut self, rl: &mut RaylibHandle) -> Option<TextFieldEvent> {
+        let mut event = None;
+        while let Some(c) = rl.get_char_pressed() {
+            if !c.is_control() {
+                self.text.push(c);
+                event = Some(TextFieldEvent::Changed);
+            }
+        }
+        let backspace = KeyboardKey::KEY_BACKSPACE;
+        if (rl.is_key_pressed(backspace) || rl.is_key_pressed_repeat(backspace)) && self.text.pop().is_some() {
+            event = Some(TextFieldEvent::Changed);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_KP_ENTER) {
+            event = Some(TextFieldEvent::Submit);
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+            event = Some(TextFieldEvent::Cancel);
+        }
+        event
+    }
+
+    /// Draws the label and text with a cursor in a box `width` pixels wide, returning the
+    /// box's height.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, x: i32, y: i32, width: i32, font_size: i32) -> i32 {
+        let padding = font_size / 3;
+        let height = font_size + padding * 2;
+        d.draw_rectangle(x, y, width, height, Color::BLACK.alpha(0.7));
+        d.draw_rectangle_lines(x, y, width, height, Color::POWDERBLUE.alpha(0.6));
+        let label = format!("{}: ", self.label);
+        let label_width = d.measure_text(&label, font_size);
+        d.draw_text(&label, x + padding, y + padding, font_size, Color::new(200, 200, 200, 160));
+        // Blink the cursor twice a second
+        let cursor = if (d.get_time() * 2.0) as i64 % 2 == 0 { "_" } else { "" };
+        d.draw_text(&format!("{}{}", self.text, cursor), x + padding + label_width, y + padding, font_size, Color::new(230, 230, 230, 255));
+        height
+    }
+}
@@ -0,0 +1,140 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+/// A point or direction in 3D space. The galaxy model uses this instead of raylib's
+/// `Vector3` so it doesn't need raylib, and converts to and from it with the `gui` feature.
+/// Written to files as an `[x, y, z]` array.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Vec3 {
+        Vec3 { x, y, z }
+    }
+
+    pub const fn zero() -> Vec3 {
+        Vec3::new(0.0, 0.0, 0.0)
+    }
+
+    pub const fn left() -> Vec3 {
+        Vec3::new(-1.0, 0.0, 0.0)
+    }
+
+    pub fn dot(&self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(*self).sqrt()
+    }
+
+    /// The vector scaled to a length of 1, or left alone if it has no length.
+    pub fn normalized(&self) -> Vec3 {
+        let length = self.length();
+        if length == 0.0 {
+            return *self
+        }
+        self.scale_by(1.0 / length)
+    }
+
+    pub fn scale_by(&self, factor: f32) -> Vec3 {
+        Vec3::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    pub fn scale(&mut self, factor: f32) {
+        *self = self.scale_by(factor);
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, other: Vec3) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, other: Vec3) {
+        *self = *self - other;
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, factor: f32) -> Vec3 {
+        self.scale_by(factor)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+    fn neg(self) -> Vec3 {
+        self.scale_by(-1.0)
+    }
+}
+
+impl Serialize for Vec3 {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        (self.x, self.y, self.z).serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for Vec3 {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Vec3, D::Error> {
+        let (x, y, z) = <(f32, f32, f32)>::deserialize(d)?;
+        Ok(Vec3 { x, y, z })
+    }
+}
+
+#[cfg(feature = "gui")]
+mod raylib_conversions {
+    use raylib::ffi;
+    use raylib::prelude::Vector3;
+
+    use super::Vec3;
+
+    impl From<Vec3> for Vector3 {
+        fn from(v: Vec3) -> Vector3 {
+            Vector3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vector3> for Vec3 {
+        fn from(v: Vector3) -> Vec3 {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    // Lets a `Vec3` be passed straight to raylib's drawing functions
+    impl From<Vec3> for ffi::Vector3 {
+        fn from(v: Vec3) -> ffi::Vector3 {
+            ffi::Vector3 { x: v.x, y: v.y, z: v.z }
+        }
+    }
+}
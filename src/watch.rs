@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::file_generator;
+
+/// How often the save is looked at.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Notices when something else changes the save while rim has it open, like a script
+/// regenerating it or the GM fixing the JSON by hand.
+pub(crate) struct SaveWatcher {
+    path: String,
+    /// When the save was last modified as far as rim knows, from loading, reloading or saving it.
+    known: Option<SystemTime>,
+    /// The modified time from the last look, to wait for a change to settle before reading it.
+    last: Option<SystemTime>,
+    /// A change that has already been reported, so it's only reported once.
+    reported: Option<SystemTime>,
+    checked: Instant,
+}
+
+impl SaveWatcher {
+    pub fn new(path: &String) -> SaveWatcher {
+        let known = file_generator::modified(path);
+        SaveWatcher { path: path.clone(), known, last: known, reported: None, checked: Instant::now() }
+    }
+
+    /// Returns the save's new modified time once it has changed on disk and stayed the same
+    /// for a look, so a file still being written isn't read half way. Each change is only
+    /// returned once.
+    pub fn poll(&mut self) -> Option<SystemTime> {
+        if self.checked.elapsed() < POLL_INTERVAL {
+            return None
+        }
+        self.checked = Instant::now();
+        let modified = file_generator::modified(&self.path);
+        let settled = modified == self.last;
+        self.last = modified;
+        match modified {
+            Some(time) if settled && modified != self.known && modified != self.reported => {
+                self.reported = modified;
+                Some(time)
+            }
+            _ => {None}
+        }
+    }
+
+    /// Marks the save as matching what's open, after loading it or writing to it.
+    pub fn caught_up(&mut self, modified: Option<SystemTime>) {
+        self.known = modified;
+        self.reported = None;
+    }
+
+    /// Whether the save was changed by something else since it was loaded.
+    pub fn newer_on_disk(&self) -> bool {
+        file_generator::modified(&self.path).is_some_and(|time| Some(time) != self.known)
+    }
+}